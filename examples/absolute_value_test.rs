@@ -9,7 +9,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr1);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr1, e);
@@ -22,7 +22,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr2);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr2, e);
@@ -35,7 +35,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr3);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr3, e);
@@ -48,7 +48,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr4);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr4, e);
@@ -61,7 +61,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr5);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr5, e);
@@ -74,7 +74,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr6);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr6, e);
@@ -87,7 +87,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr7);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr7, e);
@@ -100,7 +100,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr8);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr8, e);
@@ -113,7 +113,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr9);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr9, e);
@@ -126,7 +126,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr10);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr10, e);
@@ -139,7 +139,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr11);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr11, e);
@@ -152,7 +152,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr12);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr12, e);
@@ -165,7 +165,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr13);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr13, e);
@@ -178,7 +178,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr14);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr14, e);
@@ -23,7 +23,7 @@ fn main() {
             Ok(ast) => {
                 println!("表达式: {}", expr);
                 println!("AST: {:#?}", ast.expr);
-                println!("LaTeX: {}\n", ast.to_string());
+                println!("LaTeX: {}\n", ast.to_latex());
             }
             Err(e) => {
                 println!("解析错误 '{}': {}\n", expr, e);
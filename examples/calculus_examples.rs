@@ -9,7 +9,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr1);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr1, e);
@@ -22,7 +22,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr2);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr2, e);
@@ -35,7 +35,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr3);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr3, e);
@@ -48,7 +48,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr4);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr4, e);
@@ -61,10 +61,48 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr5);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr5, e);
         }
     }
+
+    // 测试求和与求积
+    let expr6 = "\\sum_{n=1}^{\\infty} \\frac{1}{n^2} = \\frac{\\pi^2}{6}";
+    match parse_display_math(expr6) {
+        Ok(ast) => {
+            println!("表达式: {}", expr6);
+            println!("AST: {:#?}", ast.expr);
+            println!("LaTeX: {}\n", ast.to_latex());
+        }
+        Err(e) => {
+            println!("解析错误 '{}': {}\n", expr6, e);
+        }
+    }
+
+    let expr7 = "n! = \\prod_{k=1}^{n} k";
+    match parse_inline_math(expr7) {
+        Ok(ast) => {
+            println!("表达式: {}", expr7);
+            println!("AST: {:#?}", ast.expr);
+            println!("LaTeX: {}\n", ast.to_latex());
+        }
+        Err(e) => {
+            println!("解析错误 '{}': {}\n", expr7, e);
+        }
+    }
+
+    // 测试极限
+    let expr8 = "\\lim_{x \\to 0} \\frac{\\sin(x)}{x} = 1";
+    match parse_display_math(expr8) {
+        Ok(ast) => {
+            println!("表达式: {}", expr8);
+            println!("AST: {:#?}", ast.expr);
+            println!("LaTeX: {}\n", ast.to_latex());
+        }
+        Err(e) => {
+            println!("解析错误 '{}': {}\n", expr8, e);
+        }
+    }
 }
\ No newline at end of file
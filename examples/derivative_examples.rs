@@ -9,7 +9,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr1);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr1, e);
@@ -22,7 +22,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr2);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr2, e);
@@ -35,7 +35,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr3);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr3, e);
@@ -48,10 +48,42 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr4);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr4, e);
         }
     }
-}
\ No newline at end of file
+    
+    // 测试其他导数写法
+    let forms = [
+        "\\frac{d}{dx} \\sin(x)",
+        "\\frac{d}{dx}\\left(x^2 + 1\\right)",
+        "\\frac{dy}{dx}",
+        "\\frac{d^2 y}{dx^2}",
+        "d/dx x^3",
+        "\\frac{\\partial f}{\\partial x}",
+        "\\frac{\\partial^2 f}{\\partial x \\partial y}",
+        "\\frac{\\partial}{\\partial t} u(x, t)",
+        "\\nabla f \\cdot \\vec{v}",
+        "\\nabla \\cdot \\vec{E} = \\frac{\\rho}{\\epsilon_0}",
+        "\\nabla \\times \\vec{B}",
+        "\\Delta u = \\nabla^2 u",
+        "f'(x) + f''(x) = 0",
+        "y'''",
+        "f^{(4)}(x)",
+        "(x^2)' = 2x",
+    ];
+    for expr in forms {
+        match parse_inline_math(expr) {
+            Ok(ast) => {
+                println!("表达式: {}", expr);
+                println!("AST: {:?}", ast.expr);
+                println!("LaTeX: {}\n", ast.to_latex());
+            }
+            Err(e) => {
+                println!("解析错误 '{}': {}\n", expr, e);
+            }
+        }
+    }
+}
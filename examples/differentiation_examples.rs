@@ -0,0 +1,84 @@
+use markdown_formula_parser::ast::{BinaryOperator, MathExpr};
+use markdown_formula_parser::parse_inline_math;
+
+fn main() {
+    println!("=== 符号求导测试 ===\n");
+
+    let examples = [
+        "x^2",
+        "3x^3 - 2x + 1",
+        "x \\sin(x)",
+        "\\frac{1}{x}",
+        "\\frac{x + 1}{x - 1}",
+        "\\sqrt{x}",
+        "\\sqrt[3]{x^2 + 1}",
+        "\\sin(x^2)",
+        "\\cos(2x)",
+        "\\tan(x)",
+        "e^{2x}",
+        "2^x",
+        "x^x",
+        "\\ln(x^2 + 1)",
+        "\\log(x)",
+        "\\exp(\\sin(x))",
+        "f(x^2)",
+        "f'(x)",
+        "a x^2 + b x + c",
+        "\\int_{0}^{x} \\sin(t) dt",
+        "\\frac{d}{dx}[x^3]",
+        "\\lim_{t \\to 0} x t",
+        "|x|",
+    ];
+
+    for expr in examples {
+        match parse_inline_math(expr) {
+            Ok(ast) => match ast.expr.differentiate("x") {
+                Ok(derivative) => println!("d/dx {} = {}", expr, derivative.to_latex()),
+                Err(e) => println!("d/dx {} 求导错误: {}", expr, e),
+            },
+            Err(e) => println!("解析错误 '{}': {}", expr, e),
+        }
+    }
+
+    // 检查作业中形如 \frac{d}{dx}[...] = ... 的导数计算，比较两边在若干点处的取值
+    println!("\n=== 检查导数计算 ===\n");
+    let answers = [
+        "\\frac{d}{dx}[x^2] = 2x",
+        "\\frac{d}{dx}[x^3 + x] = 3x^2 + 1",
+        "\\frac{d}{dx}[\\sin(x)] = -\\cos(x)",
+        "\\frac{d}{dx}[\\frac{1}{x}] = -\\frac{1}{x^2}",
+    ];
+    for answer in answers {
+        let Ok(ast) = parse_inline_math(answer) else {
+            println!("解析错误 '{}'", answer);
+            continue;
+        };
+        let MathExpr::BinaryOp { left, operator: BinaryOperator::Equals, right } = &ast.expr else {
+            println!("{} 不是等式", answer);
+            continue;
+        };
+        let MathExpr::Derivative { variable, order, expression } = left.as_ref() else {
+            println!("{} 的左边不是导数", answer);
+            continue;
+        };
+        let mut expected = expression.as_ref().clone();
+        for _ in 0..*order {
+            expected = match expected.differentiate(variable) {
+                Ok(derivative) => derivative,
+                Err(e) => {
+                    println!("{} 求导错误: {}", answer, e);
+                    break;
+                }
+            };
+        }
+        let correct = [0.5, 1.0, 2.0, 3.0].iter().all(|&value| {
+            let vars = [(variable.clone(), value)].into();
+            match (expected.evaluate(&vars), right.evaluate(&vars)) {
+                (Ok(a), Ok(b)) => (a - b).abs() <= 1e-9 * a.abs().max(1.0),
+                _ => false,
+            }
+        });
+        let verdict = if correct { "正确" } else { "错误" };
+        println!("{}: {}（导数为 {}）", answer, verdict, expected.to_latex());
+    }
+}
@@ -0,0 +1,64 @@
+use markdown_formula_parser::ast::MathExpr;
+use markdown_formula_parser::parse_display_math;
+use markdown_formula_parser::solve::solve_system;
+
+fn main() {
+    println!("=== 公式组测试 ===\n");
+
+    let examples = [
+        "\\begin{align} f(x) &= (x + 1)^2 \\label{eq:square} \\\\ &= x^2 + 2x + 1 \\nonumber \\end{align}",
+        "\\begin{align*} a &= b + c & d &= e \\\\ g &\\le h \\tag{1.2} \\end{align*}",
+        "\\begin{aligned} \\int_{0}^{1} x dx &= \\frac{1}{2} \\\\ &\\approx 0.5 \\end{aligned}",
+        "\\begin{eqnarray} x + y &=& 3 \\\\ x - y &=& 1 \\notag \\end{eqnarray}",
+        "\\begin{gather} E = m c^2 \\\\ F = m a \\\\ \\end{gather}",
+        "\\begin{split} (a + b)^2 &= (a + b)(a + b) \\\\ &= a^2 + 2 a b + b^2 \\end{split}",
+        "\\begin{align} x &= 1 \\label{eq:x \\end{align}",
+    ];
+
+    for expr in examples {
+        let ast = match parse_display_math(expr) {
+            Ok(ast) => ast,
+            Err(e) => {
+                println!("解析错误 '{}': {}\n", expr, e);
+                continue;
+            }
+        };
+        println!("表达式: {}", expr);
+        println!("LaTeX: {}", ast.to_latex());
+        if let MathExpr::EquationSystem { environment, rows } = &ast.expr {
+            println!("环境: {:?}", environment);
+            for (i, row) in rows.iter().enumerate() {
+                let relations = row.relations().iter().map(|r| r.to_latex().trim().to_string()).collect::<Vec<_>>();
+                println!(
+                    "  第 {} 行: {} 列, 关系式 {:?}, label: {:?}, tag: {:?}, nonumber: {}",
+                    i + 1,
+                    row.cells.len(),
+                    relations,
+                    row.label,
+                    row.tag,
+                    row.nonumber
+                );
+            }
+        }
+        println!("MathML: {}\n", ast.expr.to_mathml());
+    }
+
+    // 把公式组的各行作为方程组求解
+    println!("=== 解公式组中的方程组 ===\n");
+    let system = "\\begin{eqnarray} x + y &=& 3 \\\\ x - y &=& 1 \\end{eqnarray}";
+    if let Ok(ast) = parse_display_math(system)
+        && let MathExpr::EquationSystem { rows, .. } = &ast.expr
+    {
+        let equations = rows.iter().flat_map(|row| row.relations()).collect::<Vec<_>>();
+        match solve_system(&equations, &["x", "y"]) {
+            Ok(solution) => {
+                let solution = solution
+                    .iter()
+                    .map(|(name, value)| format!("{} = {}", name, value.to_latex()))
+                    .collect::<Vec<_>>();
+                println!("{} => {}", system, solution.join(", "));
+            }
+            Err(e) => println!("{} 求解错误: {}", system, e),
+        }
+    }
+}
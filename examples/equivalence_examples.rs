@@ -0,0 +1,42 @@
+use markdown_formula_parser::equiv::Equivalence;
+use markdown_formula_parser::parse_inline_math;
+
+fn main() {
+    println!("=== 等价判断测试 ===\n");
+
+    let pairs = [
+        ("2x + 2", "2(x+1)"),
+        ("(x+1)^2", "x^2 + 2x + 1"),
+        ("(x+1)^2", "x^2 + 1"),
+        ("\\frac{x^2 - 1}{x - 1}", "x + 1"),
+        ("\\sin(x)^2 + \\cos(x)^2", "1"),
+        ("\\ln(x^2)", "2 \\ln(x)"),
+        ("\\sqrt{x^2}", "|x|"),
+        ("\\sqrt{x^2}", "x"),
+        ("e^{a + b}", "e^{a} e^{b}"),
+        ("\\frac{1}{2} + \\frac{1}{3}", "\\frac{5}{6}"),
+        ("\\sum_{k=1}^{n} k", "\\frac{n^2 + n}{2}"),
+        ("\\int_{0}^{x} 2t dt", "x^2"),
+        ("x_1 + x_2", "x_2 + x_1"),
+        ("n!", "n \\cdot (n-1)!"),
+        ("\\alpha \\beta", "\\beta \\alpha + 1"),
+        ("x = 1", "1 = x"),
+        ("\\ln(-x^2 - 1)", "0"),
+    ];
+
+    for (a, b) in pairs {
+        let (Ok(left), Ok(right)) = (parse_inline_math(a), parse_inline_math(b)) else {
+            println!("解析错误 '{}' 或 '{}'", a, b);
+            continue;
+        };
+        match left.equivalent_to(&right) {
+            Equivalence::Equivalent => println!("{} 与 {}: 等价", a, b),
+            Equivalence::NotEquivalent { counterexample, left, right } => {
+                let values: Vec<String> =
+                    counterexample.iter().map(|(name, value)| format!("{} = {}", name, value)).collect();
+                println!("{} 与 {}: 不等价，{} 时两边分别为 {} 和 {}", a, b, values.join(", "), left, right);
+            }
+            Equivalence::Unknown { reason } => println!("{} 与 {}: 无法判断（{}）", a, b, reason),
+        }
+    }
+}
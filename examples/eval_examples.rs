@@ -0,0 +1,50 @@
+use markdown_formula_parser::parse_inline_math;
+use std::collections::HashMap;
+
+fn main() {
+    println!("=== 数值求值测试 ===\n");
+
+    let vars: HashMap<String, f64> = [
+        ("x".to_string(), 2.0),
+        ("y".to_string(), -3.0),
+        ("theta".to_string(), 0.5),
+        ("a_1".to_string(), 10.0),
+    ]
+    .into();
+    println!("变量取值: x = 2, y = -3, \\theta = 0.5, a_1 = 10\n");
+
+    let examples = [
+        "1 + 2 * 3",
+        "x^2 + y",
+        "\\frac{x + 1}{y}",
+        "\\sqrt[3]{-8} + \\sqrt{16}",
+        "5! - |y|",
+        "\\sin(\\theta)^2 + \\cos(\\theta)^2",
+        "\\ln(\\exp(x)) + \\log(100)",
+        "2 \\pi x",
+        "a_1 / 4",
+        "\\sum_{i=1}^{10} i^2",
+        "\\prod_{k=1}^{5} k",
+        "\\lim_{t \\to 0} \\frac{\\sin(t)}{t}",
+        "\\int_0^1 t^2 \\, dt",
+        "\\int_0^{\\pi} \\sin(t) \\mathrm{d}t",
+        "\\frac{1}{x - 2}",
+        "\\sqrt{y}",
+        "\\ln(0)",
+        "y!",
+        "\\sum_{n=1}^{\\infty} \\frac{1}{n^2}",
+        "\\int t \\, dt",
+        "z + 1",
+        "f(x)",
+    ];
+
+    for expr in examples {
+        match parse_inline_math(expr) {
+            Ok(ast) => match ast.expr.evaluate(&vars) {
+                Ok(value) => println!("{} = {}", expr, value),
+                Err(e) => println!("{} 求值错误: {}", expr, e),
+            },
+            Err(e) => println!("解析错误 '{}': {}", expr, e),
+        }
+    }
+}
@@ -1,9 +1,10 @@
-use markdown_formula_parser::parse_markdown_math;
+use markdown_formula_parser::markdown::{Delimiter, Delimiters};
+use markdown_formula_parser::{parse_markdown_math, parse_markdown_math_with, rewrite_markdown_math};
 
 fn main() {
     println!("=== Markdown公式提取测试 ===\n");
 
-    let markdown_examples = vec![
+    let markdown_examples = [
         // 基本文本与公式混合
         r#"这是行内公式 $E = mc^2$ 的示例。"#,
         
@@ -41,6 +42,17 @@ $$
         
         // 不完整或错误的公式
         r#"不完整的公式：$E = mc^"#,
+
+        // 代码、HTML 和转义中的 $ 不是公式
+        r#"价格为 \$5 到 \$10，代码 `$x$` 不是公式，而 $y^2$ 是。
+
+```
+$$ 代码块中的内容 $$
+```
+
+<div title="$a$">$b$</div>
+
+[链接](https://example.com/$c$) 之后的 $z$ 是公式。"#,
     ];
 
     for (i, markdown) in markdown_examples.iter().enumerate() {
@@ -52,11 +64,52 @@ $$
             println!("未找到数学公式。\n");
         } else {
             for (j, block) in math_blocks.iter().enumerate() {
-                println!("公式 {}: {}", j + 1, block.to_string());
+                println!("公式 {}: {}", j + 1, block);
                 println!("显示模式: {}", if block.display_style { "块级" } else { "行内" });
-                println!("表达式 AST: {:#?}\n", block.expr);
+                println!("文档中的位置: {:?}", block.expr.span());
+                println!("表达式 AST: {:#?}\n", block.expr.without_spans());
             }
         }
         println!("---\n");
     }
+
+    // Pandoc、Jupyter 和 GitLab 使用的其他分隔符
+    println!("=== 其他分隔符测试 ===\n");
+    let markdown = r#"Pandoc 导出的行内公式 \(a^2 + b^2 = c^2\) 和块级公式：
+\[
+\sum_{k=1}^{n} k = \frac{n(n+1)}{2}
+\]
+GitLab 的行内公式 $`E = mc^2`$ 和围栏公式：
+
+```math
+\int_{0}^{1} x^2 dx = \frac{1}{3}
+```"#;
+    println!("Markdown内容:\n{}\n", markdown);
+    let delimiter_sets = [
+        ("默认分隔符", Delimiters::default()),
+        ("全部分隔符", Delimiters::all()),
+        ("只识别 \\( \\) 和 \\[ \\]", Delimiters::none().with(Delimiter::Parenthesis).with(Delimiter::Bracket)),
+    ];
+    for (label, delimiters) in delimiter_sets {
+        println!("{}:", label);
+        let math_blocks = parse_markdown_math_with(markdown, delimiters);
+        if math_blocks.is_empty() {
+            println!("  未找到数学公式。");
+        }
+        for block in math_blocks {
+            println!("  {:?}: {}", block.delimiter, block.to_latex());
+        }
+    }
+
+    // 替换文档中的公式，其余文本保持不变
+    println!("\n=== 改写公式测试 ===\n");
+    let markdown = "勾股定理 $a^2+b^2=c^2$，错误的公式 $\\frac{1}{2$ 保持不变，\n\n$$\\sqrt{x}$$\n\n代码 `$y$` 也不变。";
+    println!("Markdown内容:\n{}\n", markdown);
+    println!("规范化 LaTeX:\n{}\n", rewrite_markdown_math(markdown, |block, _| Some(block.to_latex())));
+    println!("MathML:\n{}\n", rewrite_markdown_math(markdown, |block, _| Some(block.to_mathml())));
+    let images = rewrite_markdown_math(markdown, |block, source| {
+        // 只把块级公式替换为图片链接
+        block.display_style.then(|| format!("![{}](formula.svg)", source.trim_matches('$')))
+    });
+    println!("图片链接:\n{}", images);
 }
\ No newline at end of file
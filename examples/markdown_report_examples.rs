@@ -0,0 +1,54 @@
+use markdown_formula_parser::parse_markdown_math_report;
+
+// 用法: cargo run --example markdown_report_examples -- docs/a.md docs/b.md
+// 不带参数时检查内置的示例文档
+fn main() {
+    println!("=== Markdown公式检查 ===\n");
+
+    let files: Vec<String> = std::env::args().skip(1).collect();
+    let documents: Vec<(String, String)> = if files.is_empty() {
+        vec![(
+            "example.md".to_string(),
+            r#"# 示例文档
+
+质能方程 $E = mc^2$ 是正确的，而 $\frac{1}{2$ 缺少右花括号。
+
+$$
+\begin{pmatrix} a & b \\ c & d \end{bmatrix}
+$$
+
+未闭合的根号 $\sqrt[3]{x$ 和多余的括号 $(a + b))$。
+
+希腊字母 $\alpha + \beta \le \pi$ 可以识别，拼错的 $\lamda x$ 会被报告。
+"#
+            .to_string(),
+        )]
+    } else {
+        files
+            .into_iter()
+            .filter_map(|path| match std::fs::read_to_string(&path) {
+                Ok(text) => Some((path, text)),
+                Err(e) => {
+                    println!("无法读取 {}: {}", path, e);
+                    None
+                }
+            })
+            .collect()
+    };
+
+    let mut failures = 0;
+    for (path, markdown) in &documents {
+        let formulas = parse_markdown_math_report(markdown, true);
+        for formula in &formulas {
+            if let Err(e) = &formula.result {
+                failures += 1;
+                let (line, column) = e.line_column(markdown);
+                println!("{}:{}:{}: {}", path, line, column, e);
+                println!("    公式 ({}:{}): {}", formula.line, formula.column, formula.source.trim());
+            }
+        }
+        println!("{}: 共 {} 个公式\n", path, formulas.len());
+    }
+
+    println!("解析失败的公式: {}", failures);
+}
@@ -0,0 +1,44 @@
+use markdown_formula_parser::{parse_display_math, parse_inline_math};
+
+fn main() {
+    println!("=== MathML 输出测试 ===\n");
+
+    let inline_examples = [
+        "x_{i}^{2} + y^2",
+        "\\frac{a+b}{2}",
+        "\\sqrt[3]{x} - \\sqrt{y}",
+        "|x| \\cdot ||\\vec{v}||",
+        "\\sin(\\theta) = -\\cos(\\alpha)",
+        "n! = 2 * 3",
+    ];
+
+    for expr in inline_examples {
+        match parse_inline_math(expr) {
+            Ok(ast) => {
+                println!("表达式: {}", expr);
+                println!("MathML: {}\n", ast.to_mathml());
+            }
+            Err(e) => {
+                println!("解析错误 '{}': {}\n", expr, e);
+            }
+        }
+    }
+
+    let display_examples = [
+        "\\begin{pmatrix} a & b \\\\ c & d \\end{pmatrix}",
+        "\\frac{d}{dx}[x^2] = 2x",
+        "f'(x) = \\alpha x^{n-1}",
+    ];
+
+    for expr in display_examples {
+        match parse_display_math(expr) {
+            Ok(ast) => {
+                println!("表达式: {}", expr);
+                println!("MathML: {}\n", ast.to_mathml());
+            }
+            Err(e) => {
+                println!("解析错误 '{}': {}\n", expr, e);
+            }
+        }
+    }
+}
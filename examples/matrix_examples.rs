@@ -9,7 +9,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr1);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr1, e);
@@ -22,7 +22,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr2);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr2, e);
@@ -35,7 +35,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr3);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr3, e);
@@ -48,7 +48,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr4);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr4, e);
@@ -61,10 +61,38 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr5);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr5, e);
         }
     }
-}
\ No newline at end of file
+
+    // 测试其他环境
+    println!("=== 其他环境测试 ===\n");
+    let environments = [
+        "\\begin{Bmatrix} a & b \\\\ c & d \\end{Bmatrix}",
+        "\\begin{smallmatrix} 1 & 0 \\\\ 0 & 1 \\end{smallmatrix}",
+        "\\begin{array}{c|c} 1 & 2 \\\\ 3 & 4 \\end{array}",
+        "\\begin{cases} x & x \\geq 0 \\\\ -x & x < 0 \\end{cases}",
+        "\\begin{aligned} f(x) &= (x + 1)^2 \\\\ &= x^2 + 2x + 1 \\end{aligned}",
+        "\\begin{align*} a &= b + c \\\\ d &= e \\end{align*}",
+        "\\begin{gather} x + y = 1 \\\\ x - y = 0 \\end{gather}",
+        "\\begin{split} a &= b \\\\ &= c \\end{split}",
+        "\\begin{CD} A @>f>> B \\\\ @VgVV @VVhV \\\\ C @>>k> D \\end{CD}",
+        "\\begin{equation} x \\end{equation}",
+        "\\begin{align} x \\end{gather}",
+    ];
+    for expr in environments {
+        match parse_display_math(expr) {
+            Ok(ast) => {
+                println!("表达式: {}", expr);
+                println!("LaTeX: {}", ast.to_latex());
+                println!("MathML: {}\n", ast.expr.to_mathml());
+            }
+            Err(e) => {
+                println!("解析错误 '{}': {}\n", expr, e);
+            }
+        }
+    }
+}
@@ -9,7 +9,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr1);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr1, e);
@@ -22,7 +22,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr2);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr2, e);
@@ -35,7 +35,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr3);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr3, e);
@@ -48,7 +48,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr4);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr4, e);
@@ -61,7 +61,7 @@ fn main() {
         Ok(ast) => {
             println!("表达式: {}", expr5);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("解析错误 '{}': {}\n", expr5, e);
@@ -0,0 +1,66 @@
+use markdown_formula_parser::{parse_display_math, parse_inline_math};
+use std::collections::HashMap;
+
+fn main() {
+    println!("=== 分段函数测试 ===\n");
+
+    let examples = [
+        "f(x) = \\begin{cases} x & x \\ge 0 \\\\ -x & x < 0 \\end{cases}",
+        "\\begin{cases} 1 & \\text{if } 0 \\le x < 1 \\\\ 0 & \\text{otherwise} \\end{cases}",
+        "\\begin{cases} \\frac{\\sin(x)}{x} & x \\neq 0 \\\\ 1 & x = 0 \\end{cases}",
+        "\\begin{cases} x^2 & x > 0 \\\\ x & x \\le 0 \\\\ 0 \\end{cases}",
+        "\\begin{cases} 1 & x & 2 \\end{cases}",
+        "\\begin{cases} 1 & \\text{sometimes} \\end{cases}",
+        "\\begin{cases} x, & x \\ge 0 \\\\ -x, & x < 0. \\end{cases}",
+        "\\begin{cases} 1 & \\text{for } x > 0 \\\\ 2 & \\text{if } x \\text{ is even} \\\\ 0 & \\text{otherwise.} \\end{cases}",
+    ];
+
+    for expr in examples {
+        match parse_display_math(expr) {
+            Ok(ast) => {
+                println!("表达式: {}", expr);
+                println!("AST: {:?}", ast.expr.without_spans());
+                println!("LaTeX: {}", ast.to_latex());
+                println!("MathML: {}\n", ast.expr.to_mathml());
+            }
+            Err(e) => println!("解析错误 '{}': {}\n", expr, e),
+        }
+    }
+
+    // 依次检查各分支的条件，取第一个成立的分支
+    println!("=== 分段函数求值 ===\n");
+    let functions = [
+        "\\begin{cases} x & x \\ge 0 \\\\ -x & x < 0 \\end{cases}",
+        "\\begin{cases} 1 & \\text{if } 0 \\le x < 1 \\\\ 0 & \\text{otherwise} \\end{cases}",
+        "\\begin{cases} \\frac{1}{x} & x > 0 \\end{cases}",
+        "\\begin{cases} 1 & \\text{for } x > 0, \\\\ 0, & \\text{otherwise.} \\end{cases}",
+    ];
+    for expr in functions {
+        let Ok(ast) = parse_inline_math(expr) else {
+            println!("解析错误 '{}'", expr);
+            continue;
+        };
+        println!("{}", expr);
+        for x in [-2.0, 0.0, 0.5, 2.0] {
+            let vars = HashMap::from([("x".to_string(), x)]);
+            match ast.expr.evaluate(&vars) {
+                Ok(value) => println!("  x = {}: {}", x, value),
+                Err(e) => println!("  x = {}: 求值错误: {}", x, e),
+            }
+        }
+    }
+
+    // 逐段求导，以及与绝对值比较
+    println!("\n=== 求导与等价判断 ===\n");
+    let expr = "\\begin{cases} x^2 & x \\ge 0 \\\\ -x^2 & x < 0 \\end{cases}";
+    if let Ok(ast) = parse_inline_math(expr) {
+        match ast.expr.differentiate("x") {
+            Ok(derivative) => println!("d/dx {} = {}", expr, derivative.to_latex()),
+            Err(e) => println!("d/dx {} 求导错误: {}", expr, e),
+        }
+    }
+    let (a, b) = ("\\begin{cases} x & x \\ge 0 \\\\ -x & x < 0 \\end{cases}", "|x|");
+    if let (Ok(a_ast), Ok(b_ast)) = (parse_inline_math(a), parse_inline_math(b)) {
+        println!("{} 与 {}: {:?}", a, b, a_ast.equivalent_to(&b_ast));
+    }
+}
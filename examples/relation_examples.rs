@@ -0,0 +1,32 @@
+use markdown_formula_parser::parse_inline_math;
+
+fn main() {
+    println!("=== 关系运算符测试 ===\n");
+
+    let examples = [
+        "0 \\le x < 1",
+        "a \\neq b",
+        "x^2 + 1 > 0",
+        "\\pi \\approx 3.14",
+        "a \\equiv b",
+        "f(x) \\propto x^2",
+        "a = b = c",
+        "|x - 1| \\geq \\epsilon",
+        "\\sum_{i=1}^{n} i \\le n^2",
+        "x \\le",
+    ];
+
+    for expr in examples {
+        match parse_inline_math(expr) {
+            Ok(ast) => {
+                println!("表达式: {}", expr);
+                println!("AST: {:#?}", ast.expr);
+                println!("LaTeX: {}", ast.to_latex());
+                println!("MathML: {}\n", ast.to_mathml());
+            }
+            Err(e) => {
+                println!("解析错误 '{}': {}\n", expr, e);
+            }
+        }
+    }
+}
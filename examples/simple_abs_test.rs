@@ -14,8 +14,7 @@ fn main() {
             Ok(ast) => {
                 println!("表达式: {}", case);
                 println!("AST: {:#?}", ast);
-                // println!("LaTeX: {}\n", ast.to_latex());
-                println!();
+                println!("LaTeX: {}\n", ast.to_latex());
             }
             Err(e) => {
                 println!("解析错误 '{}': {}\n", case, e);
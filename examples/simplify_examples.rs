@@ -0,0 +1,66 @@
+use markdown_formula_parser::parse_inline_math;
+use markdown_formula_parser::simplify::{Rule, RuleSet};
+
+fn main() {
+    println!("=== 代数化简测试 ===\n");
+
+    let examples = [
+        "x \\cdot 1 + 0",
+        "x^1 - 0 \\cdot y",
+        "2 + 3 \\times 4 - 5!",
+        "2x + 3x - x",
+        "x y - y x + z",
+        "a - (b - (c + d))",
+        "2 (3 (x y))",
+        "\\frac{6}{4}",
+        "\\frac{2 x^3}{4 x}",
+        "\\frac{x^{2} y}{x^{5}}",
+        "x x^{2} x^{3}",
+        "(x^2)^3",
+        "-(-x)",
+        "\\sqrt{16} + \\sqrt[3]{27}",
+        "\\frac{(x + 1)}{(x + 1)}",
+    ];
+
+    for expr in examples {
+        match parse_inline_math(expr) {
+            Ok(ast) => println!("{} => {}", expr, ast.expr.simplify().to_latex()),
+            Err(e) => println!("解析错误 '{}': {}", expr, e),
+        }
+    }
+
+    // 只启用部分规则
+    println!("\n=== 按规则化简 ===\n");
+    let expr = "2 + 3 + (x + x) \\cdot 1";
+    let rule_sets = [
+        ("全部规则", RuleSet::all()),
+        ("不合并同类项", RuleSet::all().without(Rule::LikeTerms)),
+        ("只折叠常数", RuleSet::none().with(Rule::ConstantFolding)),
+        ("只去掉括号和恒等式", RuleSet::none().with(Rule::Parentheses).with(Rule::Identities)),
+        ("不启用任何规则", RuleSet::none()),
+    ];
+    if let Ok(ast) = parse_inline_math(expr) {
+        for (label, rules) in rule_sets {
+            println!("{}: {} => {}", label, expr, ast.expr.simplify_with(rules));
+        }
+    }
+
+    // 化简求导的结果
+    println!("\n=== 化简导数 ===\n");
+    let functions = ["\\frac{x + 1}{x - 1}", "x^2 \\cdot x^3", "(x + 1)(x - 1)", "x \\ln(x) - x"];
+    for expr in functions {
+        let Ok(ast) = parse_inline_math(expr) else {
+            println!("解析错误 '{}'", expr);
+            continue;
+        };
+        match ast.expr.differentiate("x") {
+            Ok(derivative) => println!(
+                "d/dx {} = {} = {}",
+                expr,
+                derivative.to_latex(),
+                derivative.simplify().to_latex()
+            ),
+            Err(e) => println!("d/dx {} 求导错误: {}", expr, e),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use markdown_formula_parser::parse_inline_math;
+use markdown_formula_parser::solve::solve_system;
+
+fn main() {
+    println!("=== 解方程测试 ===\n");
+
+    let equations = [
+        "2x + 3 = 7",
+        "3(x - 1) = x + 5",
+        "\\frac{x}{2} + \\frac{x}{3} = 5",
+        "x^2 - 5x + 6 = 0",
+        "x^2 = 2",
+        "2x^2 + 3x - 2 = 0",
+        "x^2 - 2x - 1 = 0",
+        "x^2 + x + 1 = 0",
+        "(x - 3)^2 = 0",
+        "-x^2 + 4 = 0",
+        "a x + b = 0",
+        "a x^2 + b x + c = 0",
+        "x + 1 = x + 2",
+        "2(x + 1) = 2x + 2",
+        "x^3 = 8",
+        "\\sin(x) = 0",
+        "y = 2",
+    ];
+
+    for equation in equations {
+        let ast = match parse_inline_math(equation) {
+            Ok(ast) => ast,
+            Err(e) => {
+                println!("解析错误 '{}': {}", equation, e);
+                continue;
+            }
+        };
+        match ast.expr.solve("x") {
+            Ok(solutions) if solutions.is_empty() => println!("{} => 无实数解", equation),
+            Ok(solutions) => {
+                let solutions: Vec<String> = solutions.iter().map(|s| format!("x = {}", s.to_latex())).collect();
+                println!("{} => {}", equation, solutions.join(", "));
+            }
+            Err(e) => println!("{} => 求解错误: {}", equation, e),
+        }
+    }
+
+    // 线性方程组
+    println!("\n=== 解方程组测试 ===\n");
+    let systems: [(&[&str], &[&str]); 5] = [
+        (&["x + y = 3", "x - y = 1"], &["x", "y"]),
+        (&["2x + 3y = 7", "4x - y = 0"], &["x", "y"]),
+        (&["x + y + z = 6", "2y + 5z = -4", "2x + 5y - z = 27"], &["x", "y", "z"]),
+        (&["x + y = 1", "2x + 2y = 3"], &["x", "y"]),
+        (&["x + y = 1", "2x + 2y = 2"], &["x", "y"]),
+    ];
+    for (sources, variables) in systems {
+        let equations: Vec<_> = sources
+            .iter()
+            .filter_map(|source| parse_inline_math(source).ok())
+            .map(|block| block.expr)
+            .collect();
+        match solve_system(&equations, variables) {
+            Ok(solutions) => {
+                let solutions: Vec<String> =
+                    solutions.iter().map(|(name, value)| format!("{} = {}", name, value.to_latex())).collect();
+                println!("{} => {}", sources.join(", "), solutions.join(", "));
+            }
+            Err(e) => println!("{} => 求解错误: {}", sources.join(", "), e),
+        }
+    }
+}
@@ -1,8 +1,20 @@
+use crate::diff::DiffError;
+use crate::equiv::Equivalence;
+use crate::markdown::Delimiter;
+use crate::eval::EvalError;
+use crate::simplify::RuleSet;
+use crate::solve::SolveError;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum MathExpr {
     // 基本元素
     Number(f64),
     Variable(String),
+    // 已知的符号命令，如 \alpha、\infty、\nabla；其他命令仍为 Variable("\\name")
+    Symbol(Symbol),
     
     // 二元运算
     BinaryOp {
@@ -49,17 +61,111 @@ pub enum MathExpr {
     // 括号
     Parenthesized(Box<MathExpr>),
     
-    // 矩阵
+    // 矩阵及其他按行和单元格排列的环境: \begin{pmatrix} a & b \\ c & d \end{pmatrix}
+    // CD 环境中每个对象和箭头各占一个单元格
     Matrix {
         rows: Vec<Vec<MathExpr>>,
-        matrix_type: String,  // 存储矩阵类型 (matrix, pmatrix, bmatrix, vmatrix, Vmatrix)
+        environment: Environment,
+    },
+
+    // 空的单元格，如 a & & b 中间的单元格，以及 aligned 中 &= 之前的部分
+    Empty,
+
+    // 按原文保存的正文: \text{otherwise}、\operatorname{sgn}，以及内容不是单个标识符的 \mathrm{...}
+    Text {
+        command: TextCommand,
+        text: String,
+    },
+
+    // 交换图中的箭头: @>f>>、@<<<、@VgVV、@AAA、@=、@|、@.
+    // 水平箭头的 first、second 分别为上方和下方的标注，竖直箭头的为左侧和右侧的标注
+    CdArrow {
+        direction: CdArrowDirection,
+        first: Option<Box<MathExpr>>,
+        second: Option<Box<MathExpr>>,
+    },
+
+    // 分段函数: \begin{cases} x & x \ge 0 \\ -x & x < 0 \end{cases}
+    // 每个分支为 (值, 条件)，条件为 None 表示 \text{otherwise}
+    Piecewise {
+        branches: Vec<(MathExpr, Option<MathExpr>)>,
+    },
+
+    // 多行对齐的公式组: align、aligned、eqnarray、gather、split
+    // \begin{align} f(x) &= (x + 1)^2 \label{eq:f} \\ &= x^2 + 2x + 1 \nonumber \end{align}
+    EquationSystem {
+        environment: Environment,
+        rows: Vec<EquationRow>,
     },
     
-    // 导数运算符
+    // 导数: \frac{d}{dx}[...]、\frac{d}{dx} f(x)、\frac{df}{dx}、\frac{d^2 y}{dx^2}、d/dx f
     Derivative {
         variable: String,
+        order: u32,
         expression: Box<MathExpr>,
     },
+
+    // 偏导数: \frac{\partial f}{\partial x}、\frac{\partial^2 f}{\partial x \partial y}、\frac{\partial}{\partial x}[...]
+    // variables 为分母中按书写顺序排列的求导变量及其阶数，相邻的相同变量合并
+    PartialDerivative {
+        variables: Vec<(String, u32)>,
+        expression: Box<MathExpr>,
+    },
+
+    // 撇号表示的导数: f'、f''、f^{(4)}、(x^2)'，order 为撇号个数或括号中的阶数
+    // 函数调用上的导数 f'(x) 中 expr 为函数调用 f(x)
+    Prime {
+        expr: Box<MathExpr>,
+        order: u32,
+    },
+
+    // 向量微分算子: \nabla f、\nabla \cdot F、\nabla \times F、\nabla^2 f、\Delta f
+    DifferentialOperator {
+        kind: DifferentialOperatorKind,
+        operand: Box<MathExpr>,
+    },
+
+    // 求和、求积: \sum_{index=lower}^{upper} body
+    // 下标为单个变量时只有 index，为其他表达式时只有 lower
+    BigOperator {
+        kind: BigOperatorKind,
+        index: Option<String>,
+        lower: Option<Box<MathExpr>>,
+        upper: Option<Box<MathExpr>>,
+        body: Box<MathExpr>,
+    },
+
+    // 极限: \lim_{variable \to target} body
+    Limit {
+        variable: String,
+        target: Box<MathExpr>,
+        body: Box<MathExpr>,
+    },
+
+    // 积分: \int_{lower}^{upper} integrand dx
+    // variables 为末尾微分中的积分变量，按书写顺序排列，没有写出微分时为空
+    // dot_product 表示被积函数与微分之间为点积，如 \oint_C \mathbf{F} \cdot d\mathbf{r}
+    Integral {
+        kind: IntegralKind,
+        lower: Option<Box<MathExpr>>,
+        upper: Option<Box<MathExpr>>,
+        integrand: Box<MathExpr>,
+        variables: Vec<String>,
+        dot_product: bool,
+    },
+
+    // 关系链: 0 \le x < 1，operands 比 operators 多一个
+    // 只有一个 = 的等式仍为 BinaryOp::Equals
+    Relation {
+        operands: Vec<MathExpr>,
+        operators: Vec<RelationOperator>,
+    },
+
+    // 带位置信息的节点，span 为字节范围（公式内，或经 parse_markdown_math 得到的文档内位置）
+    Spanned {
+        span: Range<usize>,
+        expr: Box<MathExpr>,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -80,43 +186,170 @@ pub enum UnaryOperator {
     Factorial,
 }
 
+// 符号命令，name 为不含反斜杠的命令名
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub unicode: char,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Letter,   // 希腊字母等，可以作为变量
+    Constant, // \pi、\infty 等有固定含义的常量
+    Operator, // \partial、\nabla 等作用于其后表达式的符号
+}
+
+// 已知的符号命令及其对应的字符
+const SYMBOLS: [(&str, char, SymbolKind); 55] = [
+    ("alpha", 'α', SymbolKind::Letter), ("beta", 'β', SymbolKind::Letter),
+    ("gamma", 'γ', SymbolKind::Letter), ("delta", 'δ', SymbolKind::Letter),
+    ("epsilon", 'ϵ', SymbolKind::Letter), ("varepsilon", 'ε', SymbolKind::Letter),
+    ("zeta", 'ζ', SymbolKind::Letter), ("eta", 'η', SymbolKind::Letter),
+    ("theta", 'θ', SymbolKind::Letter), ("vartheta", 'ϑ', SymbolKind::Letter),
+    ("iota", 'ι', SymbolKind::Letter), ("kappa", 'κ', SymbolKind::Letter),
+    ("lambda", 'λ', SymbolKind::Letter), ("mu", 'μ', SymbolKind::Letter),
+    ("nu", 'ν', SymbolKind::Letter), ("xi", 'ξ', SymbolKind::Letter),
+    ("varpi", 'ϖ', SymbolKind::Letter), ("rho", 'ρ', SymbolKind::Letter),
+    ("varrho", 'ϱ', SymbolKind::Letter), ("sigma", 'σ', SymbolKind::Letter),
+    ("varsigma", 'ς', SymbolKind::Letter), ("tau", 'τ', SymbolKind::Letter),
+    ("upsilon", 'υ', SymbolKind::Letter), ("phi", 'ϕ', SymbolKind::Letter),
+    ("varphi", 'φ', SymbolKind::Letter), ("chi", 'χ', SymbolKind::Letter),
+    ("psi", 'ψ', SymbolKind::Letter), ("omega", 'ω', SymbolKind::Letter),
+    ("Gamma", 'Γ', SymbolKind::Letter), ("Delta", 'Δ', SymbolKind::Letter),
+    ("Theta", 'Θ', SymbolKind::Letter), ("Lambda", 'Λ', SymbolKind::Letter),
+    ("Xi", 'Ξ', SymbolKind::Letter), ("Pi", 'Π', SymbolKind::Letter),
+    ("Sigma", 'Σ', SymbolKind::Letter), ("Upsilon", 'Υ', SymbolKind::Letter),
+    ("Phi", 'Φ', SymbolKind::Letter), ("Psi", 'Ψ', SymbolKind::Letter),
+    ("Omega", 'Ω', SymbolKind::Letter), ("ell", 'ℓ', SymbolKind::Letter),
+    ("aleph", 'ℵ', SymbolKind::Letter), ("beth", 'ℶ', SymbolKind::Letter),
+    ("imath", 'ı', SymbolKind::Letter), ("jmath", 'ȷ', SymbolKind::Letter),
+    ("wp", '℘', SymbolKind::Letter),
+    ("pi", 'π', SymbolKind::Constant), ("infty", '∞', SymbolKind::Constant),
+    ("hbar", 'ℏ', SymbolKind::Constant), ("emptyset", '∅', SymbolKind::Constant),
+    ("varnothing", '∅', SymbolKind::Constant),
+    ("partial", '∂', SymbolKind::Operator), ("nabla", '∇', SymbolKind::Operator),
+    ("forall", '∀', SymbolKind::Operator), ("exists", '∃', SymbolKind::Operator),
+    ("neg", '¬', SymbolKind::Operator),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelationOperator {
+    Equals,       // =
+    NotEquals,    // \neq
+    Less,         // <
+    LessEqual,    // \le
+    Greater,      // >
+    GreaterEqual, // \ge
+    Approx,       // \approx
+    Equiv,        // \equiv
+    Sim,          // \sim
+    Propto,       // \propto
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BigOperatorKind {
+    Sum,
+    Product,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DifferentialOperatorKind {
+    Gradient,   // \nabla f
+    Divergence, // \nabla \cdot F
+    Curl,       // \nabla \times F
+    Laplacian,  // \nabla^2 f 或 \Delta f
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntegralKind {
+    Single,  // \int
+    Double,  // \iint
+    Triple,  // \iiint
+    Contour, // \oint
+}
+
+// 按行和单元格排列的环境
+#[derive(Debug, Clone, PartialEq)]
+pub enum Environment {
+    Matrix,        // matrix
+    PMatrix,       // pmatrix，圆括号
+    BMatrix,       // bmatrix，方括号
+    BMatrixCurly,  // Bmatrix，花括号
+    VMatrix,       // vmatrix，行列式
+    VMatrixDouble, // Vmatrix，双竖线
+    SmallMatrix,   // smallmatrix，行内的小矩阵
+    // array，columns 为列格式，如 c|c、lcr
+    Array { columns: String },
+    Cases,                      // cases，解析为分段函数 Piecewise
+    Aligned,                    // aligned
+    Align { starred: bool },    // align、align*
+    Gather { starred: bool },   // gather、gather*
+    EqnArray { starred: bool }, // eqnarray、eqnarray*
+    Split,                      // split
+    CD,                         // CD，交换图
+}
+
+// 公式组中的一行，cells 为按 & 分隔的各列，以关系运算符开始的列（如 &= b）左侧为 Empty
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EquationRow {
+    pub cells: Vec<MathExpr>,
+    pub label: Option<String>, // \label{...}
+    pub tag: Option<String>,   // \tag{...}
+    pub nonumber: bool,        // \nonumber 或 \notag
+}
+
+// 内容按原文保存的命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextCommand {
+    Text,         // \text
+    Mathrm,       // \mathrm
+    Operatorname, // \operatorname
+}
+
+// 交换图中箭头的方向
+#[derive(Debug, Clone, PartialEq)]
+pub enum CdArrowDirection {
+    Right,          // @>>>
+    Left,           // @<<<
+    Down,           // @VVV
+    Up,             // @AAA
+    Equals,         // @=
+    VerticalEquals, // @|
+    Blank,          // @.，不画箭头
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MathBlock {
     pub expr: MathExpr,
     pub display_style: bool, // true for block ($$), false for inline ($)
+    // 从 Markdown 中提取时所用的分隔符，直接解析的公式为 None
+    pub delimiter: Option<Delimiter>,
 }
 
-impl MathExpr {
-    pub fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for MathExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
             MathExpr::Number(n) => format!("{}", n),
-            MathExpr::Variable(v) => {
-                // 特殊处理vec命令和导数运算符
-                if v.starts_with("\\vec{") {
-                    v.clone()
-                } else if v.starts_with("\\frac{d}{") && v.ends_with("}") {
-                    // 导数运算符
-                    v.clone()
-                } else {
-                    v.clone()
-                }
-            },
+            MathExpr::Symbol(symbol) => symbol.command(),
+            MathExpr::Variable(v) => v.clone(),
             MathExpr::BinaryOp { left, operator, right } => {
-                format!("({} {} {})", left.to_string(), operator.to_string(), right.to_string())
+                format!("({} {} {})", left, operator.to_string(), right)
             }
             MathExpr::UnaryOp { operator, expr } => {
                 match operator {
-                    UnaryOperator::Plus => format!("+{}", expr.to_string()),
-                    UnaryOperator::Minus => format!("-{}", expr.to_string()),
-                    UnaryOperator::Factorial => format!("{}!", expr.to_string()),
+                    UnaryOperator::Plus => format!("+{}", expr),
+                    UnaryOperator::Minus => format!("-{}", expr),
+                    UnaryOperator::Factorial => format!("{}!", expr),
                 }
             }
             MathExpr::FunctionCall { name, args } => {
                 // 特殊处理绝对值和范数
                 if name == "abs" && args.len() == 1 {
-                    format!("|{}|", args[0].to_string())
+                    format!("|{}|", args[0])
                 } else if name == "norm" && args.len() == 1 {
-                    format!("||{}||", args[0].to_string())
+                    format!("||{}||", args[0])
                 } else {
                     let args_str = args.iter()
                         .map(|arg| arg.to_string())
@@ -126,39 +359,373 @@ impl MathExpr {
                 }
             }
             MathExpr::Subscript { base, subscript } => {
-                format!("{}[{}]", base.to_string(), subscript.to_string())
+                format!("{}[{}]", base, subscript)
             }
             MathExpr::Superscript { base, superscript } => {
-                format!("{}^{{{}}}", base.to_string(), superscript.to_string())
+                format!("{}^{{{}}}", base, superscript)
             }
             MathExpr::Fraction { numerator, denominator } => {
-                format!("\\frac{{{}}}{{{}}}", numerator.to_string(), denominator.to_string())
+                format!("\\frac{{{}}}{{{}}}", numerator, denominator)
             }
             MathExpr::Root { radicand, index } => {
                 if let Some(idx) = index {
-                    format!("\\sqrt[{}]{{{}}}", idx.to_string(), radicand.to_string())
+                    format!("\\sqrt[{}]{{{}}}", idx, radicand)
                 } else {
-                    format!("\\sqrt{{{}}}", radicand.to_string())
+                    format!("\\sqrt{{{}}}", radicand)
                 }
             }
             MathExpr::Parenthesized(expr) => {
-                format!("({})", expr.to_string())
+                format!("({})", expr)
             }
-            MathExpr::Matrix { rows, matrix_type } => {
+            MathExpr::Matrix { rows, environment } => {
+                let separator = if *environment == Environment::CD { " " } else { " & " };
                 let rows_str = rows.iter()
                     .map(|row| {
                         row.iter()
                             .map(|cell| cell.to_string())
                             .collect::<Vec<_>>()
-                            .join(" & ")
+                            .join(separator)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" \\\\ ");
+                format!("{} {} \\end{{{}}}", environment.begin(), rows_str, environment.name())
+            }
+            MathExpr::Empty => String::new(),
+            MathExpr::Text { command, text } => format!("{}{{{}}}", command.command(), text),
+            MathExpr::CdArrow { direction, first, second } => {
+                let label = |label: &Option<Box<MathExpr>>| match label {
+                    Some(label) => format!("{{{}}}", label),
+                    None => String::new(),
+                };
+                direction.to_latex(&label(first), &label(second))
+            }
+            MathExpr::Piecewise { branches } => {
+                let branches = branches.iter()
+                    .map(|(value, condition)| match condition {
+                        Some(condition) => format!("{} & {}", value, condition),
+                        None => format!("{} & \\text{{otherwise}}", value),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" \\\\ ");
+                format!("\\begin{{cases}} {} \\end{{cases}}", branches)
+            }
+            MathExpr::EquationSystem { environment, rows } => {
+                let rows = rows.iter()
+                    .map(|row| {
+                        let cells = row.cells.iter()
+                            .map(|cell| cell.to_string())
+                            .collect::<Vec<_>>()
+                            .join(" & ");
+                        format!("{}{}", cells, row.commands())
                     })
                     .collect::<Vec<_>>()
                     .join(" \\\\ ");
-                format!("\\begin{{{}}} {} \\end{{{}}}", matrix_type, rows_str, matrix_type)
+                format!("\\begin{{{0}}} {1} \\end{{{0}}}", environment.name(), rows)
+            }
+            MathExpr::Derivative { variable, order: 1, expression } => {
+                format!("\\frac{{d}}{{d{}}}({})", variable, expression)
+            }
+            MathExpr::Derivative { variable, order, expression } => {
+                format!("\\frac{{d^{{{}}}}}{{d{}^{{{}}}}}({})", order, variable, order, expression)
+            }
+            MathExpr::PartialDerivative { variables, expression } => {
+                let order: u64 = variables.iter().map(|(_, order)| u64::from(*order)).sum();
+                let denominator = variables
+                    .iter()
+                    .map(|(variable, order)| match order {
+                        1 => format!("\\partial {}", variable),
+                        _ => format!("\\partial {}^{{{}}}", variable, order),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                match order {
+                    1 => format!("\\frac{{\\partial}}{{{}}}({})", denominator, expression),
+                    _ => format!("\\frac{{\\partial^{{{}}}}}{{{}}}({})", order, denominator, expression),
+                }
+            }
+            MathExpr::Prime { expr, order: order @ 1..=3 } => {
+                format!("{}{}", expr, "'".repeat(*order as usize))
             }
-            MathExpr::Derivative { variable, expression } => {
-                format!("\\frac{{d}}{{d{}}}({})", variable, expression.to_string())
+            MathExpr::Prime { expr, order } => {
+                format!("{}^{{({})}}", expr, order)
             }
+            MathExpr::DifferentialOperator { kind, operand } => {
+                format!("{}({})", kind.command(), operand)
+            }
+            MathExpr::BigOperator { kind, index, lower, upper, body } => {
+                let mut s = kind.command().to_string();
+                match (index, lower) {
+                    (Some(index), Some(lower)) => s.push_str(&format!("_{{{}={}}}", index, lower)),
+                    (Some(index), None) => s.push_str(&format!("_{{{}}}", index)),
+                    (None, Some(lower)) => s.push_str(&format!("_{{{}}}", lower)),
+                    (None, None) => {}
+                }
+                if let Some(upper) = upper {
+                    s.push_str(&format!("^{{{}}}", upper));
+                }
+                format!("{}({})", s, body)
+            }
+            MathExpr::Limit { variable, target, body } => {
+                format!("\\lim_{{{} \\to {}}}({})", variable, target, body)
+            }
+            MathExpr::Integral { kind, lower, upper, integrand, variables, dot_product } => {
+                let mut s = kind.command().to_string();
+                if let Some(lower) = lower {
+                    s.push_str(&format!("_{{{}}}", lower));
+                }
+                if let Some(upper) = upper {
+                    s.push_str(&format!("^{{{}}}", upper));
+                }
+                s.push_str(&format!("({})", integrand));
+                if *dot_product {
+                    s.push_str(" \\cdot");
+                }
+                for variable in variables {
+                    s.push_str(&format!(" d{}", variable));
+                }
+                s
+            }
+            MathExpr::Relation { operands, operators } => {
+                let mut s = format!("({}", operands[0]);
+                for (operator, operand) in operators.iter().zip(&operands[1..]) {
+                    s.push_str(&format!(" {} {}", operator.to_latex(), operand));
+                }
+                s.push(')');
+                s
+            }
+            MathExpr::Spanned { expr, .. } => expr.to_string(),
+        };
+        f.write_str(&s)
+    }
+}
+
+impl MathExpr {
+    // 节点的位置（未记录时为 None）
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            MathExpr::Spanned { span, .. } => Some(span.clone()),
+            _ => None,
+        }
+    }
+
+    // 去掉外层的位置包裹，得到实际的节点
+    pub fn unspanned(&self) -> &MathExpr {
+        match self {
+            MathExpr::Spanned { expr, .. } => expr.unspanned(),
+            _ => self,
+        }
+    }
+
+    // 递归去掉所有位置信息，用于结构比较
+    pub fn strip_spans(&mut self) {
+        while let MathExpr::Spanned { expr, .. } = self {
+            *self = std::mem::replace(expr.as_mut(), MathExpr::Number(0.0));
+        }
+        self.for_each_child_mut(&mut |child| child.strip_spans());
+    }
+
+    pub fn without_spans(&self) -> MathExpr {
+        let mut expr = self.clone();
+        expr.strip_spans();
+        expr
+    }
+
+    // 输出为能被重新解析为相同 AST 的 LaTeX
+    pub fn to_latex(&self) -> String {
+        crate::latex::to_latex(self)
+    }
+
+    // 代入变量的取值计算表达式的值
+    pub fn evaluate(&self, vars: &HashMap<String, f64>) -> Result<f64, EvalError> {
+        crate::eval::evaluate(self, vars)
+    }
+
+    // 对变量 variable 求导，结果为新的表达式
+    pub fn differentiate(&self, variable: &str) -> Result<MathExpr, DiffError> {
+        crate::diff::differentiate(self, variable)
+    }
+
+    // 把等式作为关于 variable 的一次或二次方程求解，返回全部实数解
+    pub fn solve(&self, variable: &str) -> Result<Vec<MathExpr>, SolveError> {
+        crate::solve::solve(self, variable)
+    }
+
+    // 按全部规则化简表达式
+    pub fn simplify(&self) -> MathExpr {
+        crate::simplify::simplify(self, RuleSet::all())
+    }
+
+    // 只按 rules 中的规则化简表达式
+    pub fn simplify_with(&self, rules: RuleSet) -> MathExpr {
+        crate::simplify::simplify(self, rules)
+    }
+
+    // 输出为 Presentation MathML 片段（不含外层的 <math> 元素）
+    pub fn to_mathml(&self) -> String {
+        crate::mathml::to_mathml(self)
+    }
+
+    // 将所有位置整体偏移 offset 个字节
+    pub fn shift_spans(&mut self, offset: usize) {
+        if let MathExpr::Spanned { span, .. } = self {
+            *span = span.start + offset..span.end + offset;
+        }
+        self.for_each_child_mut(&mut |child| child.shift_spans(offset));
+    }
+
+    // 依次访问所有直接子节点
+    pub(crate) fn for_each_child_mut(&mut self, f: &mut impl FnMut(&mut MathExpr)) {
+        match self {
+            MathExpr::Number(_) | MathExpr::Variable(_) | MathExpr::Symbol(_) | MathExpr::Empty | MathExpr::Text { .. } => {}
+            MathExpr::BinaryOp { left, right, .. } => {
+                f(left);
+                f(right);
+            }
+            MathExpr::UnaryOp { expr, .. } => f(expr),
+            MathExpr::FunctionCall { args, .. } => args.iter_mut().for_each(f),
+            MathExpr::Subscript { base, subscript } => {
+                f(base);
+                f(subscript);
+            }
+            MathExpr::Superscript { base, superscript } => {
+                f(base);
+                f(superscript);
+            }
+            MathExpr::Fraction { numerator, denominator } => {
+                f(numerator);
+                f(denominator);
+            }
+            MathExpr::Root { radicand, index } => {
+                f(radicand);
+                if let Some(index) = index {
+                    f(index);
+                }
+            }
+            MathExpr::Parenthesized(expr) => f(expr),
+            MathExpr::Matrix { rows, .. } => rows.iter_mut().flatten().for_each(f),
+            MathExpr::CdArrow { first, second, .. } => {
+                if let Some(first) = first {
+                    f(first);
+                }
+                if let Some(second) = second {
+                    f(second);
+                }
+            }
+            MathExpr::Relation { operands, .. } => operands.iter_mut().for_each(f),
+            MathExpr::Piecewise { branches } => {
+                for (value, condition) in branches {
+                    f(value);
+                    if let Some(condition) = condition {
+                        f(condition);
+                    }
+                }
+            }
+            MathExpr::EquationSystem { rows, .. } => rows.iter_mut().flat_map(|row| &mut row.cells).for_each(f),
+            MathExpr::Derivative { expression, .. } => f(expression),
+            MathExpr::PartialDerivative { expression, .. } => f(expression),
+            MathExpr::Prime { expr, .. } => f(expr),
+            MathExpr::DifferentialOperator { operand, .. } => f(operand),
+            MathExpr::BigOperator { lower, upper, body, .. } => {
+                if let Some(lower) = lower {
+                    f(lower);
+                }
+                if let Some(upper) = upper {
+                    f(upper);
+                }
+                f(body);
+            }
+            MathExpr::Limit { target, body, .. } => {
+                f(target);
+                f(body);
+            }
+            MathExpr::Integral { lower, upper, integrand, .. } => {
+                if let Some(lower) = lower {
+                    f(lower);
+                }
+                if let Some(upper) = upper {
+                    f(upper);
+                }
+                f(integrand);
+            }
+            MathExpr::Spanned { expr, .. } => f(expr),
+        }
+    }
+
+    // 依次访问所有直接子节点（只读）
+    pub(crate) fn for_each_child(&self, f: &mut impl FnMut(&MathExpr)) {
+        match self {
+            MathExpr::Number(_) | MathExpr::Variable(_) | MathExpr::Symbol(_) | MathExpr::Empty | MathExpr::Text { .. } => {}
+            MathExpr::BinaryOp { left, right, .. } => {
+                f(left);
+                f(right);
+            }
+            MathExpr::UnaryOp { expr, .. } => f(expr),
+            MathExpr::FunctionCall { args, .. } => args.iter().for_each(f),
+            MathExpr::Subscript { base, subscript } => {
+                f(base);
+                f(subscript);
+            }
+            MathExpr::Superscript { base, superscript } => {
+                f(base);
+                f(superscript);
+            }
+            MathExpr::Fraction { numerator, denominator } => {
+                f(numerator);
+                f(denominator);
+            }
+            MathExpr::Root { radicand, index } => {
+                f(radicand);
+                if let Some(index) = index {
+                    f(index);
+                }
+            }
+            MathExpr::Parenthesized(expr) => f(expr),
+            MathExpr::Matrix { rows, .. } => rows.iter().flatten().for_each(f),
+            MathExpr::CdArrow { first, second, .. } => {
+                if let Some(first) = first {
+                    f(first);
+                }
+                if let Some(second) = second {
+                    f(second);
+                }
+            }
+            MathExpr::Relation { operands, .. } => operands.iter().for_each(f),
+            MathExpr::Piecewise { branches } => {
+                for (value, condition) in branches {
+                    f(value);
+                    if let Some(condition) = condition {
+                        f(condition);
+                    }
+                }
+            }
+            MathExpr::EquationSystem { rows, .. } => rows.iter().flat_map(|row| &row.cells).for_each(f),
+            MathExpr::Derivative { expression, .. } => f(expression),
+            MathExpr::PartialDerivative { expression, .. } => f(expression),
+            MathExpr::Prime { expr, .. } => f(expr),
+            MathExpr::DifferentialOperator { operand, .. } => f(operand),
+            MathExpr::BigOperator { lower, upper, body, .. } => {
+                if let Some(lower) = lower {
+                    f(lower);
+                }
+                if let Some(upper) = upper {
+                    f(upper);
+                }
+                f(body);
+            }
+            MathExpr::Limit { target, body, .. } => {
+                f(target);
+                f(body);
+            }
+            MathExpr::Integral { lower, upper, integrand, .. } => {
+                if let Some(lower) = lower {
+                    f(lower);
+                }
+                if let Some(upper) = upper {
+                    f(upper);
+                }
+                f(integrand);
+            }
+            MathExpr::Spanned { expr, .. } => f(expr),
         }
     }
 }
@@ -177,6 +744,101 @@ impl BinaryOperator {
     }
 }
 
+impl Symbol {
+    // 查找符号表，name 不含反斜杠，如 alpha
+    pub fn from_command(name: &str) -> Option<Symbol> {
+        let (_, unicode, kind) = SYMBOLS.iter().find(|(command, _, _)| *command == name)?;
+        Some(Symbol {
+            name: name.to_string(),
+            kind: *kind,
+            unicode: *unicode,
+        })
+    }
+
+    // 对应的 LaTeX 命令，如 \alpha
+    pub fn command(&self) -> String {
+        format!("\\{}", self.name)
+    }
+}
+
+impl RelationOperator {
+    // 对应的 LaTeX 写法
+    pub fn to_latex(&self) -> &'static str {
+        match self {
+            RelationOperator::Equals => "=",
+            RelationOperator::NotEquals => "\\neq",
+            RelationOperator::Less => "<",
+            RelationOperator::LessEqual => "\\le",
+            RelationOperator::Greater => ">",
+            RelationOperator::GreaterEqual => "\\ge",
+            RelationOperator::Approx => "\\approx",
+            RelationOperator::Equiv => "\\equiv",
+            RelationOperator::Sim => "\\sim",
+            RelationOperator::Propto => "\\propto",
+        }
+    }
+
+    // 由命令名得到关系运算符，如 leq -> LessEqual
+    pub fn from_command(name: &str) -> Option<RelationOperator> {
+        let operator = match name {
+            "ne" | "neq" => RelationOperator::NotEquals,
+            "lt" => RelationOperator::Less,
+            "le" | "leq" => RelationOperator::LessEqual,
+            "gt" => RelationOperator::Greater,
+            "ge" | "geq" => RelationOperator::GreaterEqual,
+            "approx" => RelationOperator::Approx,
+            "equiv" => RelationOperator::Equiv,
+            "sim" => RelationOperator::Sim,
+            "propto" => RelationOperator::Propto,
+            _ => return None,
+        };
+        Some(operator)
+    }
+}
+
+impl BigOperatorKind {
+    // 对应的 LaTeX 命令
+    pub fn command(&self) -> &'static str {
+        match self {
+            BigOperatorKind::Sum => "\\sum",
+            BigOperatorKind::Product => "\\prod",
+        }
+    }
+}
+
+impl DifferentialOperatorKind {
+    // 对应的 LaTeX 写法
+    pub fn command(&self) -> &'static str {
+        match self {
+            DifferentialOperatorKind::Gradient => "\\nabla",
+            DifferentialOperatorKind::Divergence => "\\nabla \\cdot",
+            DifferentialOperatorKind::Curl => "\\nabla \\times",
+            DifferentialOperatorKind::Laplacian => "\\nabla^{2}",
+        }
+    }
+}
+
+impl IntegralKind {
+    // 对应的 LaTeX 命令
+    pub fn command(&self) -> &'static str {
+        match self {
+            IntegralKind::Single => "\\int",
+            IntegralKind::Double => "\\iint",
+            IntegralKind::Triple => "\\iiint",
+            IntegralKind::Contour => "\\oint",
+        }
+    }
+
+    // 积分的重数，即末尾应有的微分个数
+    pub fn arity(&self) -> usize {
+        match self {
+            IntegralKind::Double => 2,
+            IntegralKind::Triple => 3,
+            IntegralKind::Single | IntegralKind::Contour => 1,
+        }
+    }
+}
+
 impl UnaryOperator {
     pub fn to_string(&self) -> &str {
         match self {
@@ -187,12 +849,276 @@ impl UnaryOperator {
     }
 }
 
+impl Environment {
+    // 环境名称，与 \begin{...} 中的相同
+    pub fn name(&self) -> &'static str {
+        match self {
+            Environment::Matrix => "matrix",
+            Environment::PMatrix => "pmatrix",
+            Environment::BMatrix => "bmatrix",
+            Environment::BMatrixCurly => "Bmatrix",
+            Environment::VMatrix => "vmatrix",
+            Environment::VMatrixDouble => "Vmatrix",
+            Environment::SmallMatrix => "smallmatrix",
+            Environment::Array { .. } => "array",
+            Environment::Cases => "cases",
+            Environment::Aligned => "aligned",
+            Environment::Align { starred: false } => "align",
+            Environment::Align { starred: true } => "align*",
+            Environment::Gather { starred: false } => "gather",
+            Environment::Gather { starred: true } => "gather*",
+            Environment::EqnArray { starred: false } => "eqnarray",
+            Environment::EqnArray { starred: true } => "eqnarray*",
+            Environment::Split => "split",
+            Environment::CD => "CD",
+        }
+    }
+
+    // 由环境名称得到环境，array 的列格式为空，带 * 的名称只有 align*、gather* 和 eqnarray*
+    pub fn from_name(name: &str) -> Option<Environment> {
+        let environment = match name {
+            "matrix" => Environment::Matrix,
+            "pmatrix" => Environment::PMatrix,
+            "bmatrix" => Environment::BMatrix,
+            "Bmatrix" => Environment::BMatrixCurly,
+            "vmatrix" => Environment::VMatrix,
+            "Vmatrix" => Environment::VMatrixDouble,
+            "smallmatrix" => Environment::SmallMatrix,
+            "array" => Environment::Array { columns: String::new() },
+            "cases" => Environment::Cases,
+            "aligned" => Environment::Aligned,
+            "align" => Environment::Align { starred: false },
+            "align*" => Environment::Align { starred: true },
+            "gather" => Environment::Gather { starred: false },
+            "gather*" => Environment::Gather { starred: true },
+            "eqnarray" => Environment::EqnArray { starred: false },
+            "eqnarray*" => Environment::EqnArray { starred: true },
+            "split" => Environment::Split,
+            "CD" => Environment::CD,
+            _ => return None,
+        };
+        Some(environment)
+    }
+
+    // 多行对齐的公式组环境，解析为 EquationSystem
+    pub fn is_equation_system(&self) -> bool {
+        matches!(
+            self,
+            Environment::Aligned
+                | Environment::Align { .. }
+                | Environment::Gather { .. }
+                | Environment::EqnArray { .. }
+                | Environment::Split
+        )
+    }
+
+    // \begin{name}，array 带有列格式
+    pub fn begin(&self) -> String {
+        match self {
+            Environment::Array { columns } => format!("\\begin{{array}}{{{}}}", columns),
+            _ => format!("\\begin{{{}}}", self.name()),
+        }
+    }
+}
+
+impl EquationRow {
+    // 把各列合并为关系式，如 f(x) &= x^2 合并为 f(x) = x^2，eqnarray 的 a &=& b 合并为 a = b；
+    // 一行中有多组对齐的关系时得到多个关系式，续行 &= 2 的左侧仍为 Empty
+    pub fn relations(&self) -> Vec<MathExpr> {
+        let mut relations: Vec<MathExpr> = Vec::new();
+        for cell in &self.cells {
+            if *cell.unspanned() == MathExpr::Empty {
+                continue;
+            }
+            let Some(last) = relations.last_mut() else {
+                relations.push(cell.clone());
+                continue;
+            };
+            // 上一个关系式缺少右侧，如 a &=& b 中的 a =
+            if let Some(operand) = relation_operand(last, false)
+                && *operand.unspanned() == MathExpr::Empty
+            {
+                *operand = cell.clone();
+                continue;
+            }
+            // 这一列缺少左侧，如 f(x) &= x^2 中的 = x^2
+            let mut cell = cell.clone();
+            match relation_operand(&mut cell, true) {
+                Some(operand) if *operand.unspanned() == MathExpr::Empty => {
+                    *operand = std::mem::replace(last, MathExpr::Empty);
+                    *last = cell;
+                }
+                _ => relations.push(cell),
+            }
+        }
+        relations
+    }
+
+    // 行末的 \label、\tag 和 \nonumber，以空格开头
+    pub(crate) fn commands(&self) -> String {
+        let mut commands = String::new();
+        if let Some(label) = &self.label {
+            commands.push_str(&format!(" \\label{{{}}}", label));
+        }
+        if let Some(tag) = &self.tag {
+            commands.push_str(&format!(" \\tag{{{}}}", tag));
+        }
+        if self.nonumber {
+            commands.push_str(" \\nonumber");
+        }
+        commands
+    }
+}
+
+// 关系式的第一个或最后一个操作数，不是关系式时为 None
+fn relation_operand(expr: &mut MathExpr, first: bool) -> Option<&mut MathExpr> {
+    match expr {
+        MathExpr::Spanned { expr, .. } => relation_operand(expr, first),
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Equals, right } => {
+            Some(if first { left.as_mut() } else { right.as_mut() })
+        }
+        MathExpr::Relation { operands, .. } => {
+            if first {
+                operands.first_mut()
+            } else {
+                operands.last_mut()
+            }
+        }
+        _ => None,
+    }
+}
+
+impl TextCommand {
+    // 不带反斜杠的命令名对应的命令
+    pub fn from_name(name: &str) -> Option<TextCommand> {
+        match name {
+            "text" => Some(TextCommand::Text),
+            "mathrm" => Some(TextCommand::Mathrm),
+            "operatorname" => Some(TextCommand::Operatorname),
+            _ => None,
+        }
+    }
+
+    pub fn command(&self) -> &'static str {
+        match self {
+            TextCommand::Text => "\\text",
+            TextCommand::Mathrm => "\\mathrm",
+            TextCommand::Operatorname => "\\operatorname",
+        }
+    }
+}
+
+impl CdArrowDirection {
+    // first、second 为已经加上花括号的标注，没有标注时为空
+    pub fn to_latex(&self, first: &str, second: &str) -> String {
+        match self {
+            CdArrowDirection::Right => format!("@>{}>{}>", first, second),
+            CdArrowDirection::Left => format!("@<{}<{}<", first, second),
+            CdArrowDirection::Down => format!("@V{}V{}V", first, second),
+            CdArrowDirection::Up => format!("@A{}A{}A", first, second),
+            CdArrowDirection::Equals => "@=".to_string(),
+            CdArrowDirection::VerticalEquals => "@|".to_string(),
+            CdArrowDirection::Blank => "@.".to_string(),
+        }
+    }
+}
+
 impl MathBlock {
-    pub fn to_string(&self) -> String {
+    pub fn without_spans(&self) -> MathBlock {
+        MathBlock {
+            expr: self.expr.without_spans(),
+            display_style: self.display_style,
+            delimiter: self.delimiter,
+        }
+    }
+
+    pub fn to_latex(&self) -> String {
+        crate::latex::block_to_latex(self)
+    }
+
+    // 判断与另一个公式是否等价，见 equiv::check_equivalence
+    pub fn equivalent_to(&self, other: &MathBlock) -> Equivalence {
+        crate::equiv::check_equivalence(self, other)
+    }
+
+    // 输出完整的 <math> 元素，块级公式带有 display="block"
+    pub fn to_mathml(&self) -> String {
+        crate::mathml::block_to_mathml(self)
+    }
+}
+
+impl fmt::Display for MathBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.display_style {
-            format!("$$ {} $$", self.expr.to_string())
+            write!(f, "$$ {} $$", self.expr)
         } else {
-            format!("$ {} $", self.expr.to_string())
+            write!(f, "$ {} $", self.expr)
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_inline_math, parse_markdown_math, parse_math_block_with_spans};
+
+    #[test]
+    fn node_spans() {
+        let block = parse_math_block_with_spans("x + \\frac{1}{y}", false).unwrap();
+        assert_eq!(block.expr.span(), Some(0..15));
+        let MathExpr::BinaryOp { left, right, .. } = block.expr.unspanned() else {
+            panic!("expected a sum");
+        };
+        assert_eq!(left.span(), Some(0..1));
+        assert_eq!(right.span(), Some(4..15));
+        let MathExpr::Fraction { denominator, .. } = right.unspanned() else {
+            panic!("expected a fraction");
+        };
+        assert_eq!(denominator.span(), Some(13..14));
+    }
+
+    // 去掉位置信息后与不记录位置的解析结果相同
+    #[test]
+    fn without_spans() {
+        for source in ["x + \\frac{1}{y}", "\\int_0^1 x^2 dx", "\\begin{cases} x & x > 0 \\end{cases}"] {
+            let spanned = parse_math_block_with_spans(source, false).unwrap();
+            let plain = parse_inline_math(source).unwrap();
+            assert_ne!(spanned.expr, plain.expr);
+            assert_eq!(spanned.without_spans(), plain);
+            assert_eq!(plain.expr.span(), None);
+        }
+    }
+
+    // Markdown 中公式的位置为在整个文档中的字节范围
+    #[test]
+    fn document_spans() {
+        let blocks = parse_markdown_math("text $a^2$ end");
+        let MathExpr::Superscript { base, .. } = blocks[0].expr.unspanned() else {
+            panic!("expected a superscript");
+        };
+        assert_eq!(blocks[0].expr.span(), Some(6..9));
+        assert_eq!(base.span(), Some(6..7));
+
+        let mut expr = parse_math_block_with_spans("a^2", false).unwrap().expr;
+        expr.shift_spans(6);
+        assert_eq!(expr, blocks[0].expr);
+    }
+
+    // 对齐的各列合并为关系式，续行的左侧为 Empty
+    #[test]
+    fn equation_relations() {
+        let block = parse_inline_math("\\begin{align} f(x) &= (x+1)^2 \\\\ &= x^2 + 2x + 1 \\end{align}").unwrap();
+        let MathExpr::EquationSystem { rows, .. } = &block.expr else {
+            panic!("expected an equation system, found {:?}", block.expr);
+        };
+        let relations = |row: &EquationRow| row.relations().iter().map(MathExpr::to_latex).collect::<Vec<_>>();
+        assert_eq!(relations(&rows[0]), ["f(x) = \\left(x + 1\\right)^{2}"]);
+        assert_eq!(rows[1].relations()[0].to_string(), "( = ((x^{2} + (2 * x)) + 1))");
+
+        let block = parse_inline_math("\\begin{eqnarray} a &=& b \\\\ x &<& y & z &=& 1 \\end{eqnarray}").unwrap();
+        let MathExpr::EquationSystem { rows, .. } = &block.expr else {
+            panic!("expected an equation system, found {:?}", block.expr);
+        };
+        assert_eq!(relations(&rows[0]), ["a = b"]);
+        assert_eq!(relations(&rows[1]), ["x < y", "z = 1"]);
+    }
+}
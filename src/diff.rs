@@ -0,0 +1,509 @@
+use crate::ast::{BigOperatorKind, BinaryOperator, IntegralKind, MathExpr, UnaryOperator};
+use crate::parser::is_function_command;
+use std::fmt;
+
+// 符号求导
+
+// 求导错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffError {
+    // 不知道导数的函数
+    UnknownFunction { name: String },
+
+    // 函数参数个数不正确
+    WrongArgumentCount { function: String, expected: usize, found: usize },
+
+    // 无法求导的结构，例如等式、矩阵、极限
+    Unsupported { construct: String },
+}
+
+impl fmt::Display for DiffError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffError::UnknownFunction { name } => write!(f, "Unknown function: {}", name),
+            DiffError::WrongArgumentCount { function, expected, found } => write!(
+                f,
+                "Function {} expects {} argument(s), found {}",
+                function, expected, found
+            ),
+            DiffError::Unsupported { construct } => write!(f, "Cannot differentiate {}", construct),
+        }
+    }
+}
+
+impl std::error::Error for DiffError {}
+
+// 对变量 variable 求导
+//
+// 变量的匹配方式与 evaluate 相同：\theta 可以写作 \theta 或 theta，x_1 按整体名称匹配，
+// mc 这类多字母标识符按各字母的乘积处理。结果中的 0、1 系数和数值运算会被化简，
+// 不含求导变量的部分按常数处理；自定义函数 f(x) 的导数记作 f'(x)。
+pub fn differentiate(expr: &MathExpr, variable: &str) -> Result<MathExpr, DiffError> {
+    let expr = expr.unspanned();
+    if is_variable(expr, variable) {
+        return Ok(number(1.0));
+    }
+    // 等式、关系和矩阵即使不含求导变量也没有导数
+    let structural = matches!(
+        expr,
+        MathExpr::BinaryOp { operator: BinaryOperator::Equals, .. }
+            | MathExpr::Relation { .. }
+            | MathExpr::Matrix { .. }
+            | MathExpr::EquationSystem { .. }
+            | MathExpr::Empty
+            | MathExpr::Text { .. }
+            | MathExpr::CdArrow { .. }
+    ) || function_name(expr).is_some();
+    if !structural && !depends_on(expr, variable) {
+        return Ok(number(0.0));
+    }
+
+    // \sin x 解析为函数名与参数的隐式乘法，按函数调用求导；\sin 2x 等函数名后有多个因子的乘积无法确定参数的范围
+    if matches!(expr, MathExpr::BinaryOp { operator: BinaryOperator::Multiply, .. }) {
+        let mut factors = Vec::new();
+        collect_factors(expr, &mut factors);
+        let functions: Vec<(usize, &str)> =
+            factors.iter().enumerate().filter_map(|(i, factor)| Some((i, function_name(factor)?))).collect();
+        match functions[..] {
+            [] => {}
+            [(i, name)] if i + 2 == factors.len() => {
+                let product = factors[..i].iter().rfold(call(name, operand(factors[i + 1])), |right, factor| {
+                    binary(operand(factor), BinaryOperator::Multiply, right)
+                });
+                return differentiate(&product, variable);
+            }
+            _ => return Err(unsupported("function argument without parentheses")),
+        }
+    }
+
+    let d = |expr: &MathExpr| differentiate(expr, variable);
+    match expr {
+        MathExpr::Variable(_) if function_name(expr).is_some() => Err(unsupported("function without an argument")),
+        // 与求导变量相同的情况已在上面处理
+        MathExpr::Number(_) | MathExpr::Symbol(_) | MathExpr::Subscript { .. } => Ok(number(0.0)),
+        MathExpr::Variable(name) => {
+            let product = name
+                .chars()
+                .map(|c| MathExpr::Variable(c.to_string()))
+                .reduce(|left, right| binary(left, BinaryOperator::Multiply, right))
+                .unwrap_or_else(|| number(1.0));
+            d(&product)
+        }
+        MathExpr::BinaryOp { left, operator, right } => match operator {
+            BinaryOperator::Add => Ok(add(d(left)?, d(right)?)),
+            BinaryOperator::Subtract => Ok(sub(d(left)?, d(right)?)),
+            BinaryOperator::Multiply | BinaryOperator::DotProduct => {
+                Ok(add(mul(d(left)?, operand(right)), mul(operand(left), d(right)?)))
+            }
+            BinaryOperator::Divide => quotient(left, right, variable),
+            BinaryOperator::Power => power(left, right, variable),
+            BinaryOperator::Equals => Err(unsupported("equation")),
+        },
+        MathExpr::UnaryOp { operator, expr } => match operator {
+            UnaryOperator::Plus => d(expr),
+            UnaryOperator::Minus => Ok(neg(d(expr)?)),
+            UnaryOperator::Factorial => Err(unsupported("factorial")),
+        },
+        MathExpr::FunctionCall { name, args } => function(name, args, variable),
+        MathExpr::Superscript { base, superscript } => power(base, superscript, variable),
+        MathExpr::Fraction { numerator, denominator } => quotient(numerator, denominator, variable),
+        MathExpr::Root { radicand, index } => {
+            // \sqrt[n]{u} 的导数为 u' / (n (\sqrt[n]{u})^{n-1})
+            let n = match index {
+                Some(index) if depends_on(index, variable) => return Err(unsupported("root with a variable index")),
+                Some(index) => operand(index),
+                None => number(2.0),
+            };
+            let root = MathExpr::Root {
+                radicand: Box::new(operand(radicand)),
+                index: index.as_ref().map(|index| Box::new(operand(index))),
+            };
+            let exponent = sub(n.clone(), number(1.0));
+            Ok(div(d(radicand)?, mul(n, pow(root, exponent))))
+        }
+        MathExpr::Parenthesized(expr) => d(expr),
+        MathExpr::Matrix { .. } => Err(unsupported("matrix")),
+        MathExpr::Relation { .. } => Err(unsupported("relation")),
+        MathExpr::EquationSystem { .. } => Err(unsupported("equation system")),
+        MathExpr::Empty => Err(unsupported("empty cell")),
+        MathExpr::Text { .. } => Err(unsupported("text")),
+        MathExpr::CdArrow { .. } => Err(unsupported("diagram arrow")),
+        // 逐段求导，条件不变
+        MathExpr::Piecewise { branches } => {
+            let branches = branches
+                .iter()
+                .map(|(value, condition)| Ok((d(value)?, condition.clone())))
+                .collect::<Result<Vec<_>, DiffError>>()?;
+            Ok(MathExpr::Piecewise { branches })
+        }
+        // 导数节点先求出其本身，再对 variable 求导
+        MathExpr::Derivative { variable: inner, order, expression } => {
+            d(&nth_derivative(expression, inner, *order)?)
+        }
+        MathExpr::PartialDerivative { variables, expression } => {
+            let mut result = operand(expression);
+            for (inner, order) in variables {
+                result = nth_derivative(&result, inner, *order)?;
+            }
+            d(&result)
+        }
+        MathExpr::Prime { expr: inner, order } => match inner.unspanned() {
+            // 链式法则: (f^{(n)}(g))' = g' f^{(n+1)}(g)
+            MathExpr::FunctionCall { name, args } if is_custom_function(name) => {
+                let [arg] = args.as_slice() else {
+                    return Err(unsupported("function of several variables"));
+                };
+                let prime = MathExpr::Prime { expr: Box::new(operand(inner)), order: order.saturating_add(1) };
+                Ok(mul(d(arg)?, prime))
+            }
+            // 其他表达式上的撇号按对 variable 求导处理
+            _ => d(&nth_derivative(inner, variable, *order)?),
+        },
+        MathExpr::DifferentialOperator { .. } => Err(unsupported("differential operator")),
+        MathExpr::BigOperator { kind, index, lower, upper, body } => {
+            // 上下限与求导变量无关的求和逐项求导
+            let bounded = [lower, upper].into_iter().flatten().all(|bound| !depends_on(bound, variable));
+            if *kind != BigOperatorKind::Sum || index.as_deref() == Some(variable) || !bounded {
+                return Err(unsupported(match kind {
+                    BigOperatorKind::Sum => "sum",
+                    BigOperatorKind::Product => "product",
+                }));
+            }
+            Ok(MathExpr::BigOperator {
+                kind: kind.clone(),
+                index: index.clone(),
+                lower: lower.as_ref().map(|lower| Box::new(operand(lower))),
+                upper: upper.as_ref().map(|upper| Box::new(operand(upper))),
+                body: Box::new(d(body)?),
+            })
+        }
+        MathExpr::Limit { .. } => Err(unsupported("limit")),
+        MathExpr::Integral { kind: IntegralKind::Single, lower, upper, integrand, variables, .. } => {
+            match (lower, upper, variables.as_slice()) {
+                // 不定积分的导数为被积函数
+                (None, None, [t]) if t == variable => Ok(operand(integrand)),
+                // 积分变量与求导变量相同的定积分是常数
+                (Some(a), Some(b), [t]) if t == variable && !depends_on(a, variable) && !depends_on(b, variable) => {
+                    Ok(number(0.0))
+                }
+                // 变限积分: \frac{d}{dx} \int_{a(x)}^{b(x)} f(t) dt = f(b) b' - f(a) a'
+                (Some(a), Some(b), [t]) if t != variable && !depends_on(integrand, variable) => {
+                    let at = |bound: &MathExpr| substitute(integrand, t, &operand(bound));
+                    Ok(sub(mul(d(b)?, at(b)), mul(d(a)?, at(a))))
+                }
+                _ => Err(unsupported("integral")),
+            }
+        }
+        MathExpr::Integral { .. } => Err(unsupported("multiple integral")),
+        MathExpr::Spanned { expr, .. } => d(expr),
+    }
+}
+
+// 对 variable 求 order 阶导数
+fn nth_derivative(expr: &MathExpr, variable: &str, order: u32) -> Result<MathExpr, DiffError> {
+    let mut result = operand(expr);
+    for _ in 0..order {
+        if as_number(&result) == Some(0.0) {
+            break;
+        }
+        result = differentiate(&result, variable)?;
+    }
+    Ok(result)
+}
+
+// 商的导数: (u/v)' = (u' v - u v') / v^2
+fn quotient(u: &MathExpr, v: &MathExpr, variable: &str) -> Result<MathExpr, DiffError> {
+    let du = differentiate(u, variable)?;
+    if !depends_on(v, variable) {
+        return Ok(div(du, operand(v)));
+    }
+    let dv = differentiate(v, variable)?;
+    let numerator = sub(mul(du, operand(v)), mul(operand(u), dv));
+    Ok(div(numerator, pow(operand(v), number(2.0))))
+}
+
+// 幂的导数，分别处理指数为常数、底数为常数和两者都含有求导变量的情况
+fn power(u: &MathExpr, v: &MathExpr, variable: &str) -> Result<MathExpr, DiffError> {
+    let du = differentiate(u, variable)?;
+    let dv = differentiate(v, variable)?;
+    let (u_varies, v_varies) = (depends_on(u, variable), depends_on(v, variable));
+    let whole = || pow(operand(u), operand(v));
+    if !v_varies {
+        // (u^n)' = n u^{n-1} u'
+        let exponent = sub(operand(v), number(1.0));
+        return Ok(mul(mul(operand(v), pow(operand(u), exponent)), du));
+    }
+    if !u_varies {
+        // (a^v)' = v' a^v \ln(a)，底数为 e 时省略 \ln(e)
+        if matches!(u.unspanned(), MathExpr::Variable(name) if name == "e") {
+            return Ok(mul(dv, whole()));
+        }
+        return Ok(mul(dv, mul(whole(), call("\\ln", operand(u)))));
+    }
+    // (u^v)' = u^v (v' \ln(u) + v u' / u)
+    let inner = add(mul(dv, call("\\ln", operand(u))), div(mul(operand(v), du), operand(u)));
+    Ok(mul(whole(), inner))
+}
+
+// 初等函数的导数，结合链式法则
+fn function(name: &str, args: &[MathExpr], variable: &str) -> Result<MathExpr, DiffError> {
+    if matches!(name, "abs" | "norm") {
+        return Err(unsupported("absolute value"));
+    }
+    if is_custom_function(name) {
+        // 自定义函数: (f(g))' = g' f'(g)
+        let [arg] = args else {
+            return Err(unsupported("function of several variables"));
+        };
+        let prime = MathExpr::Prime {
+            expr: Box::new(MathExpr::FunctionCall { name: name.to_string(), args: vec![operand(arg)] }),
+            order: 1,
+        };
+        return Ok(mul(differentiate(arg, variable)?, prime));
+    }
+    if !matches!(name, "\\sin" | "\\cos" | "\\tan" | "\\exp" | "\\ln" | "\\log") {
+        return Err(DiffError::UnknownFunction { name: name.to_string() });
+    }
+    let [arg] = args else {
+        return Err(DiffError::WrongArgumentCount {
+            function: name.to_string(),
+            expected: 1,
+            found: args.len(),
+        });
+    };
+
+    let g = operand(arg);
+    let dg = differentiate(arg, variable)?;
+    Ok(match name {
+        "\\sin" => mul(dg, call("\\cos", g)),
+        "\\cos" => neg(mul(dg, call("\\sin", g))),
+        "\\tan" => div(dg, pow(call("\\cos", g), number(2.0))),
+        "\\exp" => mul(dg, call("\\exp", g)),
+        "\\ln" => div(dg, g),
+        // 与 evaluate 相同，\log 以 10 为底
+        _ => div(dg, mul(g, call("\\ln", number(10.0)))),
+    })
+}
+
+// 变量名为带反斜杠的函数名时返回该名称，如 \sin x 中的 \sin
+pub(crate) fn function_name(expr: &MathExpr) -> Option<&str> {
+    match expr.unspanned() {
+        MathExpr::Variable(name) if name.strip_prefix('\\').is_some_and(is_function_command) => Some(name),
+        _ => None,
+    }
+}
+
+// 按从左到右的顺序收集乘积的各个因子
+pub(crate) fn collect_factors<'a>(expr: &'a MathExpr, factors: &mut Vec<&'a MathExpr>) {
+    match expr.unspanned() {
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right } => {
+            collect_factors(left, factors);
+            collect_factors(right, factors);
+        }
+        expr => factors.push(expr),
+    }
+}
+
+// 不带反斜杠的函数名为自定义函数，如 f(x)、g(t)
+fn is_custom_function(name: &str) -> bool {
+    !name.starts_with('\\') && !matches!(name, "abs" | "norm")
+}
+
+// 表达式本身是否就是求导变量
+pub(crate) fn is_variable(expr: &MathExpr, variable: &str) -> bool {
+    let matches = |name: &str| name == variable || name.strip_prefix('\\') == Some(variable);
+    match expr.unspanned() {
+        // 函数名不是变量，\sin 不匹配变量 sin
+        MathExpr::Variable(name) => function_name(expr).is_none() && matches(name),
+        MathExpr::Symbol(symbol) => matches(&symbol.command()),
+        MathExpr::Subscript { base, subscript } => {
+            let (base, subscript) = (base.to_latex(), subscript.to_latex());
+            variable == format!("{}_{}", base, subscript) || variable == format!("{}_{{{}}}", base, subscript)
+        }
+        _ => false,
+    }
+}
+
+// 表达式是否含有求导变量
+pub(crate) fn depends_on(expr: &MathExpr, variable: &str) -> bool {
+    if is_variable(expr, variable) {
+        return true;
+    }
+    match expr.unspanned() {
+        // 多字母标识符按各字母的乘积处理
+        MathExpr::Variable(name) => {
+            variable.len() == 1 && name.chars().all(|c| c.is_ascii_alphabetic()) && name.contains(variable)
+        }
+        // 下标变量按整体名称匹配，不看其中的各个部分
+        MathExpr::Subscript { .. } => false,
+        expr => {
+            let mut found = false;
+            expr.for_each_child(&mut |child| found = found || depends_on(child, variable));
+            found
+        }
+    }
+}
+
+// 将 expr 中的变量 name 替换为 value
+pub(crate) fn substitute(expr: &MathExpr, name: &str, value: &MathExpr) -> MathExpr {
+    fn replace(expr: &mut MathExpr, name: &str, value: &MathExpr) {
+        if is_variable(expr, name) {
+            *expr = value.clone();
+        } else {
+            expr.for_each_child_mut(&mut |child| replace(child, name, value));
+        }
+    }
+    let mut result = operand(expr);
+    replace(&mut result, name, value);
+    result
+}
+
+// 原表达式中的子树放入结果时去掉位置信息和外层括号，需要时由输出重新加上括号
+pub(crate) fn operand(expr: &MathExpr) -> MathExpr {
+    match expr.unspanned() {
+        MathExpr::Parenthesized(inner) => operand(inner),
+        expr => expr.without_spans(),
+    }
+}
+
+fn unsupported(construct: &str) -> DiffError {
+    DiffError::Unsupported { construct: construct.to_string() }
+}
+
+fn number(n: f64) -> MathExpr {
+    MathExpr::Number(n)
+}
+
+fn as_number(expr: &MathExpr) -> Option<f64> {
+    match expr.unspanned() {
+        MathExpr::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn binary(left: MathExpr, operator: BinaryOperator, right: MathExpr) -> MathExpr {
+    MathExpr::BinaryOp { left: Box::new(left), operator, right: Box::new(right) }
+}
+
+fn call(name: &str, arg: MathExpr) -> MathExpr {
+    MathExpr::FunctionCall { name: name.to_string(), args: vec![arg] }
+}
+
+// 拆分出数值系数: 2 x → (2, x)，-x → (-1, x)，3 → (3, 无)
+fn coefficient(expr: MathExpr) -> (f64, Option<MathExpr>) {
+    match expr {
+        MathExpr::Number(n) => (n, None),
+        MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr } => {
+            let (c, rest) = coefficient(*expr);
+            (-c, rest)
+        }
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right } => match coefficient(*left) {
+            (c, Some(left)) => (c, Some(binary(left, BinaryOperator::Multiply, *right))),
+            (c, None) => (c, Some(*right)),
+        },
+        expr => (1.0, Some(expr)),
+    }
+}
+
+// 由系数和其余部分组成乘积，负的系数写成前缀负号
+fn with_coefficient(c: f64, rest: Option<MathExpr>) -> MathExpr {
+    let Some(rest) = rest else {
+        return number(c);
+    };
+    if c == 0.0 {
+        number(0.0)
+    } else if c == 1.0 {
+        rest
+    } else if c < 0.0 {
+        MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr: Box::new(with_coefficient(-c, Some(rest))) }
+    } else {
+        product(number(c), rest)
+    }
+}
+
+// 乘积保持左结合，与解析 2 x y 得到的结构相同
+fn product(a: MathExpr, b: MathExpr) -> MathExpr {
+    match b {
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right } => {
+            binary(product(a, *left), BinaryOperator::Multiply, *right)
+        }
+        b => binary(a, BinaryOperator::Multiply, b),
+    }
+}
+
+fn mul(a: MathExpr, b: MathExpr) -> MathExpr {
+    let (ca, a) = coefficient(a);
+    let (cb, b) = coefficient(b);
+    let rest = match (a, b) {
+        (Some(a), Some(b)) => Some(product(a, b)),
+        (rest, None) | (None, rest) => rest,
+    };
+    with_coefficient(ca * cb, rest)
+}
+
+fn neg(expr: MathExpr) -> MathExpr {
+    mul(number(-1.0), expr)
+}
+
+fn add(a: MathExpr, b: MathExpr) -> MathExpr {
+    match (as_number(&a), as_number(&b)) {
+        (Some(x), Some(y)) => number(x + y),
+        (Some(0.0), _) => b,
+        (_, Some(0.0)) => a,
+        _ => match coefficient(b) {
+            (c, rest) if c < 0.0 => binary(a, BinaryOperator::Subtract, with_coefficient(-c, rest)),
+            (c, rest) => binary(a, BinaryOperator::Add, with_coefficient(c, rest)),
+        },
+    }
+}
+
+fn sub(a: MathExpr, b: MathExpr) -> MathExpr {
+    add(a, neg(b))
+}
+
+// 分子的负号提到分数之外，分子与分母相同时为 1
+fn div(a: MathExpr, b: MathExpr) -> MathExpr {
+    if as_number(&b) == Some(1.0) || as_number(&a) == Some(0.0) {
+        return a;
+    }
+    if a == b {
+        return number(1.0);
+    }
+    match coefficient(a) {
+        (c, rest) if c < 0.0 => neg(div(with_coefficient(-c, rest), b)),
+        (c, rest) => MathExpr::Fraction { numerator: Box::new(with_coefficient(c, rest)), denominator: Box::new(b) },
+    }
+}
+
+fn pow(base: MathExpr, exponent: MathExpr) -> MathExpr {
+    match as_number(&exponent) {
+        Some(0.0) => number(1.0),
+        Some(1.0) => base,
+        _ => MathExpr::Superscript { base: Box::new(base), superscript: Box::new(exponent) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_inline_math;
+
+    fn derivative(source: &str) -> Result<String, DiffError> {
+        Ok(differentiate(&parse_inline_math(source).unwrap().expr, "x")?.to_latex())
+    }
+
+    // 省略括号的函数参数与带括号时结果相同
+    #[test]
+    fn function_without_parentheses() {
+        assert_eq!(derivative("\\sin x").unwrap(), derivative("\\sin(x)").unwrap());
+        assert_eq!(derivative("2 \\ln x").unwrap(), derivative("2 \\ln(x)").unwrap());
+        assert_eq!(derivative("\\cos x^2").unwrap(), derivative("\\cos(x^2)").unwrap());
+    }
+
+    #[test]
+    fn ambiguous_function_argument() {
+        for source in ["\\sin 2x", "\\sin x \\cos x", "\\sin"] {
+            assert!(matches!(derivative(source), Err(DiffError::Unsupported { .. })), "{}", source);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::ast::{BinaryOperator, MathBlock, MathExpr};
+use crate::eval::EvalError;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// 公式等价判断
+
+// 取样点的个数，每隔 INTEGER_EVERY 个取样点所有变量取小的非负整数，以便检查含阶乘等只在整数处有定义的公式
+const SAMPLES: usize = 48;
+const INTEGER_EVERY: usize = 3;
+
+// 两边都有定义的取样点少于这个数时无法判断
+const MIN_POINTS: usize = 8;
+
+// 数值比较的相对误差，积分和极限的数值近似也在这个范围内
+const TOLERANCE: f64 = 1e-8;
+
+// 判断结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Equivalence {
+    // 化简后相同，或在所有两边都有定义的取样点处取值相同
+    Equivalent,
+
+    // 在 counterexample 给出的变量取值处，两边的值分别为 left 和 right
+    NotEquivalent { counterexample: BTreeMap<String, f64>, left: f64, right: f64 },
+
+    // 含有无法求值的结构（等式、矩阵、导数等），或两边都有定义的取样点太少
+    Unknown { reason: String },
+}
+
+// 判断两个公式是否等价
+//
+// 先比较两边化简后的结果，相同或差化简为 0 时直接判定等价；否则对两边出现的全部自由变量随机取值，
+// 只比较两边都有定义的取样点，因此 \frac{x^2 - 1}{x - 1} 与 x + 1 视为等价。
+// 取样使用固定的种子，同样的输入总是得到同样的结果。
+pub fn check_equivalence(a: &MathBlock, b: &MathBlock) -> Equivalence {
+    let (a, b) = (a.expr.without_spans(), b.expr.without_spans());
+    if a.simplify() == b.simplify() {
+        return Equivalence::Equivalent;
+    }
+    let difference = MathExpr::BinaryOp {
+        left: Box::new(a.clone()),
+        operator: BinaryOperator::Subtract,
+        right: Box::new(MathExpr::Parenthesized(Box::new(b.clone()))),
+    };
+    if difference.simplify() == MathExpr::Number(0.0) {
+        return Equivalence::Equivalent;
+    }
+
+    let mut names = BTreeSet::new();
+    free_variables(&a, &mut names);
+    free_variables(&b, &mut names);
+
+    let mut random = XorShift(0x9E37_79B9_7F4A_7C15);
+    let mut points = 0;
+    for sample in 0..SAMPLES {
+        let vars: HashMap<String, f64> = names
+            .iter()
+            .map(|name| {
+                let value = if sample % INTEGER_EVERY == 0 {
+                    (random.next_f64() * 7.0).floor()
+                } else {
+                    // 保留三位小数，反例更易读
+                    ((random.next_f64() * 8000.0).round() - 4000.0) / 1000.0
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        let (left, right) = match (a.evaluate(&vars), b.evaluate(&vars)) {
+            (Ok(left), Ok(right)) if !left.is_nan() && !right.is_nan() => (left, right),
+            (Err(e), _) | (_, Err(e)) if !is_undefined(&e) => {
+                return Equivalence::Unknown { reason: e.to_string() };
+            }
+            _ => continue,
+        };
+        if !close(left, right) {
+            return Equivalence::NotEquivalent { counterexample: vars.into_iter().collect(), left, right };
+        }
+        points += 1;
+        // 没有自由变量时取一次值就足够了
+        if names.is_empty() {
+            return Equivalence::Equivalent;
+        }
+    }
+
+    if points < MIN_POINTS {
+        return Equivalence::Unknown {
+            reason: format!("Only {} of {} sample points are defined on both sides", points, SAMPLES),
+        };
+    }
+    Equivalence::Equivalent
+}
+
+// 取样点不在定义域内的错误，这样的取样点会被跳过
+fn is_undefined(error: &EvalError) -> bool {
+    matches!(error, EvalError::DivisionByZero | EvalError::Domain { .. } | EvalError::NoMatchingBranch)
+}
+
+fn close(left: f64, right: f64) -> bool {
+    left == right || (left - right).abs() <= TOLERANCE * left.abs().max(right.abs()).max(1.0)
+}
+
+// 收集表达式中的自由变量，名称与 evaluate 查找变量时使用的相同
+// 求和的索引、极限和积分的变量只在其内部绑定；e、\pi、\infty 按常量处理
+fn free_variables(expr: &MathExpr, names: &mut BTreeSet<String>) {
+    let bound = |body: &MathExpr, variables: &[String], names: &mut BTreeSet<String>| {
+        let mut inner = BTreeSet::new();
+        free_variables(body, &mut inner);
+        names.extend(inner.into_iter().filter(|name| !variables.contains(name)));
+    };
+    match expr {
+        MathExpr::Variable(name) if name == "e" => {}
+        // 多字母标识符按各字母的乘积处理
+        MathExpr::Variable(name) if name.len() > 1 && name.chars().all(|c| c.is_ascii_alphabetic()) => {
+            names.extend(name.chars().filter(|&c| c != 'e').map(String::from));
+        }
+        MathExpr::Variable(name) => {
+            names.insert(name.clone());
+        }
+        MathExpr::Symbol(symbol) => {
+            let command = symbol.command();
+            if command != "\\pi" && command != "\\infty" {
+                names.insert(command);
+            }
+        }
+        MathExpr::Subscript { base, subscript } => {
+            names.insert(format!("{}_{{{}}}", base.to_latex(), subscript.to_latex()));
+        }
+        MathExpr::BigOperator { index, lower, upper, body, .. } => {
+            for bound in [lower, upper].into_iter().flatten() {
+                free_variables(bound, names);
+            }
+            bound(body, index.as_slice(), names);
+        }
+        MathExpr::Limit { variable, target, body } => {
+            free_variables(target, names);
+            bound(body, std::slice::from_ref(variable), names);
+        }
+        MathExpr::Integral { lower, upper, integrand, variables, .. } => {
+            for bound in [lower, upper].into_iter().flatten() {
+                free_variables(bound, names);
+            }
+            bound(integrand, variables, names);
+        }
+        expr => expr.for_each_child(&mut |child| free_variables(child, names)),
+    }
+}
+
+// 简单的 xorshift 伪随机数生成器
+struct XorShift(u64);
+
+impl XorShift {
+    // [0, 1) 上的均匀分布
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_inline_math;
+
+    fn check(a: &str, b: &str) -> Equivalence {
+        check_equivalence(&parse_inline_math(a).unwrap(), &parse_inline_math(b).unwrap())
+    }
+
+    #[test]
+    fn equivalent() {
+        assert_eq!(check("2x + 2", "2(x+1)"), Equivalence::Equivalent);
+        assert_eq!(check("a b", "b a"), Equivalence::Equivalent);
+        assert_eq!(check("(x+1)^2", "x^2 + 2x + 1"), Equivalence::Equivalent);
+        // 只比较两边都有定义的取样点
+        assert_eq!(check("\\frac{x^2-1}{x-1}", "x+1"), Equivalence::Equivalent);
+    }
+
+    // 反例中两边的值确实不同
+    #[test]
+    fn not_equivalent() {
+        let Equivalence::NotEquivalent { counterexample, left, right } = check("x^2", "2x") else {
+            panic!("expected a counterexample");
+        };
+        let x = counterexample["x"];
+        assert_eq!((left, right), (x * x, 2.0 * x));
+        assert_ne!(left, right);
+    }
+
+    #[test]
+    fn unknown() {
+        assert!(matches!(check("\\begin{pmatrix}1\\end{pmatrix}", "x"), Equivalence::Unknown { .. }));
+        assert!(matches!(check("x = 1", "x"), Equivalence::Unknown { .. }));
+    }
+}
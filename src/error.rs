@@ -0,0 +1,185 @@
+use crate::lexer::{line_column, Token};
+use std::fmt;
+use std::ops::Range;
+
+// 解析错误，span 为出错位置在公式中的字节范围
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    // 遇到了不符合预期的标记，expected 为空表示此处期望一个表达式
+    UnexpectedToken {
+        expected: Vec<Token>,
+        found: Token,
+        span: Range<usize>,
+    },
+
+    // 输入提前结束
+    UnexpectedEof {
+        expected: Vec<Token>,
+        span: Range<usize>,
+    },
+
+    // \begin{...} 与 \end{...} 的环境名称不一致
+    MismatchedEnvironment {
+        expected: String,
+        found: String,
+        span: Range<usize>,
+    },
+
+    // 不支持的环境名称
+    UnknownEnvironment {
+        name: String,
+        span: Range<usize>,
+    },
+
+    // 词法分析无法识别的字符
+    InvalidCharacter {
+        text: String,
+        span: Range<usize>,
+    },
+
+    // 无法识别的命令
+    UnknownCommand {
+        name: String,
+        span: Range<usize>,
+    },
+}
+
+impl ParseError {
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedEof { span, .. }
+            | ParseError::MismatchedEnvironment { span, .. }
+            | ParseError::UnknownEnvironment { span, .. }
+            | ParseError::InvalidCharacter { span, .. }
+            | ParseError::UnknownCommand { span, .. } => span.clone(),
+        }
+    }
+
+    // 将位置整体偏移 offset 个字节，例如从公式内的位置换算为在整个文档中的位置
+    pub fn shift_span(&mut self, offset: usize) {
+        let span = match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedEof { span, .. }
+            | ParseError::MismatchedEnvironment { span, .. }
+            | ParseError::UnknownEnvironment { span, .. }
+            | ParseError::InvalidCharacter { span, .. }
+            | ParseError::UnknownCommand { span, .. } => span,
+        };
+        *span = span.start + offset..span.end + offset;
+    }
+
+    // 出错位置在源文本中的行号和列号
+    pub fn line_column(&self, source: &str) -> (usize, usize) {
+        line_column(source, self.span().start)
+    }
+}
+
+fn format_expected(expected: &[Token]) -> String {
+    match expected {
+        [] => "expression".to_string(),
+        [token] => format!("{:?}", token),
+        tokens => format!(
+            "one of {}",
+            tokens.iter().map(|t| format!("{:?}", t)).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken { expected, found, span } => {
+                if expected.is_empty() {
+                    write!(f, "Unexpected token: {:?}", found)?;
+                } else {
+                    write!(f, "Expected {}, found {:?}", format_expected(expected), found)?;
+                }
+                write!(f, " at {}..{}", span.start, span.end)
+            }
+            ParseError::UnexpectedEof { expected, span } => {
+                write!(
+                    f,
+                    "Unexpected end of input, expected {} at {}",
+                    format_expected(expected),
+                    span.start
+                )
+            }
+            ParseError::MismatchedEnvironment { expected, found, span } => {
+                write!(
+                    f,
+                    "Mismatched environment: expected {}, found {} at {}..{}",
+                    expected, found, span.start, span.end
+                )
+            }
+            ParseError::UnknownEnvironment { name, span } => {
+                write!(f, "Unknown environment: {} at {}..{}", name, span.start, span.end)
+            }
+            ParseError::InvalidCharacter { text, span } => {
+                write!(f, "Invalid character: {:?} at {}..{}", text, span.start, span.end)
+            }
+            ParseError::UnknownCommand { name, span } => {
+                write!(f, "Unknown command: \\{} at {}..{}", name, span.start, span.end)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_inline_math, parse_math_block_strict};
+
+    fn error(source: &str) -> ParseError {
+        parse_inline_math(source).unwrap_err()
+    }
+
+    #[test]
+    fn unexpected_token() {
+        let err = error("\\frac{1,}{2}");
+        assert_eq!(err, ParseError::UnexpectedToken { expected: vec![Token::RCurly], found: Token::Comma, span: 7..8 });
+        assert_eq!(err.to_string(), "Expected RCurly, found Comma at 7..8");
+        let err = parse_math_block_strict("x ) y", false).unwrap_err();
+        assert_eq!(err, ParseError::UnexpectedToken { expected: vec![], found: Token::RParen, span: 2..3 });
+        assert_eq!(err.to_string(), "Unexpected token: RParen at 2..3");
+    }
+
+    // 输入结束处的错误，位置为空范围
+    #[test]
+    fn unexpected_eof() {
+        assert_eq!(error("(x + 1"), ParseError::UnexpectedEof { expected: vec![Token::RParen], span: 6..6 });
+        let err = error("x +");
+        assert_eq!(err.to_string(), "Unexpected end of input, expected expression at 3");
+    }
+
+    #[test]
+    fn environments() {
+        let err = error("\\begin{pmatrix} 1 \\end{bmatrix}");
+        assert_eq!(
+            err,
+            ParseError::MismatchedEnvironment { expected: "pmatrix".to_string(), found: "bmatrix".to_string(), span: 23..30 }
+        );
+        assert_eq!(error("\\begin{foo} 1 \\end{foo}"), ParseError::UnknownEnvironment { name: "foo".to_string(), span: 7..10 });
+    }
+
+    // 未知命令只在严格模式下报错
+    #[test]
+    fn unknown_command() {
+        assert!(parse_inline_math("x + \\foo").is_ok());
+        let err = parse_math_block_strict("x + \\foo", false).unwrap_err();
+        assert_eq!(err, ParseError::UnknownCommand { name: "foo".to_string(), span: 4..8 });
+        assert_eq!(err.to_string(), "Unknown command: \\foo at 4..8");
+    }
+
+    #[test]
+    fn position() {
+        let source = "a\n+ \\frac{1}{";
+        let mut err = error(source);
+        assert_eq!(err.span(), 13..13);
+        assert_eq!(err.line_column(source), (2, 12));
+        err.shift_span(100);
+        assert_eq!(err.span(), 113..113);
+    }
+}
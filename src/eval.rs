@@ -0,0 +1,592 @@
+use crate::ast::{BigOperatorKind, BinaryOperator, IntegralKind, MathExpr, RelationOperator, UnaryOperator};
+use crate::diff::{collect_factors, function_name};
+use std::collections::HashMap;
+use std::fmt;
+
+// 求和、求积最多计算的项数
+const MAX_TERMS: i64 = 1_000_000;
+
+// 数值求值
+
+// 求值错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    // 变量没有给出取值，也不是已知常量
+    UnboundVariable { name: String },
+
+    // 除数为零
+    DivisionByZero,
+
+    // 超出运算的定义域，例如 \ln(-1)、(-1)!、负数开平方
+    Domain { operation: String, value: f64 },
+
+    // 不支持的函数
+    UnknownFunction { name: String },
+
+    // 函数参数个数不正确
+    WrongArgumentCount { function: String, expected: usize, found: usize },
+
+    // 无法求出数值的结构，例如等式、矩阵、导数
+    Unsupported { construct: String },
+
+    // 分段函数的所有条件都不成立
+    NoMatchingBranch,
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::UnboundVariable { name } => write!(f, "Unbound variable: {}", name),
+            EvalError::DivisionByZero => write!(f, "Division by zero"),
+            EvalError::Domain { operation, value } => {
+                write!(f, "Value {} is outside the domain of {}", value, operation)
+            }
+            EvalError::UnknownFunction { name } => write!(f, "Unknown function: {}", name),
+            EvalError::WrongArgumentCount { function, expected, found } => write!(
+                f,
+                "Function {} expects {} argument(s), found {}",
+                function, expected, found
+            ),
+            EvalError::Unsupported { construct } => write!(f, "Cannot evaluate {}", construct),
+            EvalError::NoMatchingBranch => write!(f, "No branch of the piecewise function applies"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+// 计算表达式的值，vars 为变量的取值
+//
+// 变量先在 vars 中查找，\alpha 这类命令也可以用不带反斜杠的名称 alpha 绑定；
+// 未绑定的 \pi、e、\infty 按常量处理，mc 这类多字母标识符按各字母的乘积处理。
+pub fn evaluate(expr: &MathExpr, vars: &HashMap<String, f64>) -> Result<f64, EvalError> {
+    match expr {
+        MathExpr::Number(n) => Ok(*n),
+        MathExpr::Variable(name) if function_name(expr).is_some() => {
+            Err(EvalError::WrongArgumentCount { function: name.clone(), expected: 1, found: 0 })
+        }
+        MathExpr::Variable(name) => lookup(name, vars),
+        MathExpr::Symbol(symbol) => lookup(&symbol.command(), vars),
+        MathExpr::BinaryOp { operator: BinaryOperator::Multiply, .. } if contains_function(expr) => {
+            let mut factors = Vec::new();
+            collect_factors(expr, &mut factors);
+            product(&factors, vars)
+        }
+        MathExpr::BinaryOp { left, operator, right } => {
+            let apply: fn(f64, f64) -> Result<f64, EvalError> = match operator {
+                BinaryOperator::Add => |l, r| Ok(l + r),
+                BinaryOperator::Subtract => |l, r| Ok(l - r),
+                BinaryOperator::Multiply | BinaryOperator::DotProduct => |l, r| Ok(l * r),
+                BinaryOperator::Divide => divide,
+                BinaryOperator::Power => power,
+                BinaryOperator::Equals => {
+                    return Err(EvalError::Unsupported { construct: "equation".to_string() });
+                }
+            };
+            apply(evaluate(left, vars)?, evaluate(right, vars)?)
+        }
+        MathExpr::UnaryOp { operator, expr } => {
+            let value = evaluate(expr, vars)?;
+            match operator {
+                UnaryOperator::Plus => Ok(value),
+                UnaryOperator::Minus => Ok(-value),
+                UnaryOperator::Factorial => factorial(value),
+            }
+        }
+        MathExpr::FunctionCall { name, args } => {
+            let args = args
+                .iter()
+                .map(|arg| evaluate(arg, vars))
+                .collect::<Result<Vec<_>, _>>()?;
+            call(name, &args)
+        }
+        MathExpr::Subscript { base, subscript } => {
+            // x_1 作为一个整体的变量名查找，x_1 和 x_{1} 两种写法均可
+            let base = base.to_latex();
+            let subscript = subscript.to_latex();
+            let name = format!("{}_{}", base, subscript);
+            let braced = format!("{}_{{{}}}", base, subscript);
+            vars.get(&name)
+                .or_else(|| vars.get(&braced))
+                .copied()
+                .ok_or(EvalError::UnboundVariable { name: braced })
+        }
+        MathExpr::Superscript { base, superscript } => {
+            power(evaluate(base, vars)?, evaluate(superscript, vars)?)
+        }
+        MathExpr::Fraction { numerator, denominator } => {
+            divide(evaluate(numerator, vars)?, evaluate(denominator, vars)?)
+        }
+        MathExpr::Root { radicand, index } => {
+            let radicand = evaluate(radicand, vars)?;
+            let index = match index {
+                Some(index) => evaluate(index, vars)?,
+                None => 2.0,
+            };
+            root(radicand, index)
+        }
+        MathExpr::Parenthesized(expr) => evaluate(expr, vars),
+        MathExpr::Matrix { .. } => Err(EvalError::Unsupported { construct: "matrix".to_string() }),
+        MathExpr::Relation { .. } => Err(EvalError::Unsupported { construct: "relation".to_string() }),
+        MathExpr::Empty => Err(EvalError::Unsupported { construct: "empty cell".to_string() }),
+        MathExpr::Text { .. } => Err(EvalError::Unsupported { construct: "text".to_string() }),
+        MathExpr::CdArrow { .. } => Err(EvalError::Unsupported { construct: "diagram arrow".to_string() }),
+        MathExpr::EquationSystem { .. } => {
+            Err(EvalError::Unsupported { construct: "equation system".to_string() })
+        }
+        MathExpr::Piecewise { branches } => {
+            // 依次检查各分支的条件，取第一个成立的分支
+            for (value, condition) in branches {
+                let holds = match condition {
+                    Some(condition) => holds(condition, vars)?,
+                    None => true,
+                };
+                if holds {
+                    return evaluate(value, vars);
+                }
+            }
+            Err(EvalError::NoMatchingBranch)
+        }
+        MathExpr::Derivative { .. } | MathExpr::Prime { .. } => {
+            Err(EvalError::Unsupported { construct: "derivative".to_string() })
+        }
+        MathExpr::PartialDerivative { .. } => {
+            Err(EvalError::Unsupported { construct: "partial derivative".to_string() })
+        }
+        MathExpr::DifferentialOperator { .. } => {
+            Err(EvalError::Unsupported { construct: "differential operator".to_string() })
+        }
+        MathExpr::BigOperator { kind, index, lower, upper, body } => {
+            big_operator(kind, index.as_deref(), lower.as_deref(), upper.as_deref(), body, vars)
+        }
+        MathExpr::Limit { variable, target, body } => {
+            limit(variable, evaluate(target, vars)?, body, vars)
+        }
+        // 被积函数与微分都是数值，点积按乘法计算
+        MathExpr::Integral { kind, lower, upper, integrand, variables, .. } => {
+            let (IntegralKind::Single, Some(lower), Some(upper), [variable]) =
+                (kind, lower, upper, variables.as_slice())
+            else {
+                return Err(EvalError::Unsupported {
+                    construct: "indefinite or multiple integral".to_string(),
+                });
+            };
+            integral(variable, evaluate(lower, vars)?, evaluate(upper, vars)?, integrand, vars)
+        }
+        MathExpr::Spanned { expr, .. } => evaluate(expr, vars),
+    }
+}
+
+// 乘积的因子中是否有不带括号的函数名，如 \sin x
+fn contains_function(expr: &MathExpr) -> bool {
+    let mut factors = Vec::new();
+    collect_factors(expr, &mut factors);
+    factors.iter().any(|factor| function_power(factor).is_some())
+}
+
+// 不带括号作用的函数名及其可选的幂次，如 \sin 或 \sin^2
+fn function_power(factor: &MathExpr) -> Option<(&str, Option<&MathExpr>)> {
+    match factor.unspanned() {
+        MathExpr::Superscript { base, superscript } => Some((function_name(base)?, Some(superscript))),
+        _ => Some((function_name(factor)?, None)),
+    }
+}
+
+// \sin x、\cos 2x 解析为函数名与参数的隐式乘法：函数名作用于其后直到下一个函数名的所有因子，
+// 如 2 \sin x \cos x 为 2 \sin(x) \cos(x)；函数名之后紧跟函数名时为嵌套调用，如 \sin \cos x；
+// \sin^2 x 为 (\sin x)^2，\sin^{-1} x 表示反函数而非倒数，不予求值
+fn product(factors: &[&MathExpr], vars: &HashMap<String, f64>) -> Result<f64, EvalError> {
+    let Some(i) = factors.iter().position(|factor| function_power(factor).is_some()) else {
+        return factors.iter().try_fold(1.0, |result, factor| Ok(result * evaluate(factor, vars)?));
+    };
+    let (name, exponent) = function_power(factors[i]).unwrap_or_default();
+    let rest = &factors[i + 1..];
+    if rest.is_empty() {
+        return Err(EvalError::WrongArgumentCount { function: name.to_string(), expected: 1, found: 0 });
+    }
+    let end = match rest.iter().position(|factor| function_power(factor).is_some()) {
+        Some(0) | None => rest.len(),
+        Some(end) => end,
+    };
+    let argument = product(&rest[..end], vars)?;
+    let mut value = call(name, &[argument])?;
+    if let Some(exponent) = exponent {
+        let exponent = evaluate(exponent, vars)?;
+        if exponent == -1.0 {
+            return Err(EvalError::Unsupported { construct: format!("{}^{{-1}}", name) });
+        }
+        value = power(value, exponent)?;
+    }
+    Ok(product(&factors[..i], vars)? * value * product(&rest[end..], vars)?)
+}
+
+// 对整数范围 lower..=upper 逐项求和或求积，索引变量在每一项中绑定为当前值
+fn big_operator(
+    kind: &BigOperatorKind,
+    index: Option<&str>,
+    lower: Option<&MathExpr>,
+    upper: Option<&MathExpr>,
+    body: &MathExpr,
+    vars: &HashMap<String, f64>,
+) -> Result<f64, EvalError> {
+    let (Some(index), Some(lower), Some(upper)) = (index, lower, upper) else {
+        return Err(EvalError::Unsupported { construct: "sum or product without bounds".to_string() });
+    };
+    let bound = |expr: &MathExpr| -> Result<i64, EvalError> {
+        let value = evaluate(expr, vars)?;
+        if value.is_infinite() {
+            return Err(EvalError::Unsupported { construct: "infinite series".to_string() });
+        }
+        if value.fract() != 0.0 {
+            return Err(EvalError::Domain { operation: "summation bound".to_string(), value });
+        }
+        // 超出 i64 范围的上下限必然超过项数上限
+        if value.abs() >= i64::MAX as f64 {
+            return Err(EvalError::Unsupported { construct: format!("sum or product with more than {} terms", MAX_TERMS) });
+        }
+        Ok(value as i64)
+    };
+    let (lower, upper) = (bound(lower)?, bound(upper)?);
+    if upper.saturating_sub(lower) >= MAX_TERMS {
+        return Err(EvalError::Unsupported { construct: format!("sum or product with more than {} terms", MAX_TERMS) });
+    }
+
+    let mut vars = vars.clone();
+    let mut result = match kind {
+        BigOperatorKind::Sum => 0.0,
+        BigOperatorKind::Product => 1.0,
+    };
+    for k in lower..=upper {
+        vars.insert(index.to_string(), k as f64);
+        let term = evaluate(body, &vars)?;
+        match kind {
+            BigOperatorKind::Sum => result += term,
+            BigOperatorKind::Product => result *= term,
+        }
+    }
+    Ok(result)
+}
+
+// 先直接代入；代入无定义（如 \frac{\sin x}{x} 在 0 处）时从两侧逼近，两侧一致才取其值
+fn limit(variable: &str, target: f64, body: &MathExpr, vars: &HashMap<String, f64>) -> Result<f64, EvalError> {
+    if target.is_infinite() {
+        return Err(EvalError::Unsupported { construct: "limit at infinity".to_string() });
+    }
+
+    let mut vars = vars.clone();
+    let mut at = |x: f64| {
+        vars.insert(variable.to_string(), x);
+        evaluate(body, &vars)
+    };
+    let direct = at(target);
+    if let Ok(value) = direct
+        && value.is_finite()
+    {
+        return Ok(value);
+    }
+
+    let h = 1e-7 * target.abs().max(1.0);
+    let (Ok(left), Ok(right)) = (at(target - h), at(target + h)) else {
+        return direct;
+    };
+    if (left - right).abs() <= 1e-5 * left.abs().max(right.abs()).max(1.0) {
+        Ok((left + right) / 2.0)
+    } else {
+        Err(EvalError::Domain { operation: "limit".to_string(), value: target })
+    }
+}
+
+// 定积分的数值近似，使用复合 Simpson 公式
+fn integral(
+    variable: &str,
+    lower: f64,
+    upper: f64,
+    integrand: &MathExpr,
+    vars: &HashMap<String, f64>,
+) -> Result<f64, EvalError> {
+    const INTERVALS: usize = 1000;
+
+    if lower.is_infinite() || upper.is_infinite() {
+        return Err(EvalError::Unsupported { construct: "improper integral".to_string() });
+    }
+
+    let mut vars = vars.clone();
+    let mut at = |x: f64| {
+        vars.insert(variable.to_string(), x);
+        evaluate(integrand, &vars)
+    };
+    let h = (upper - lower) / INTERVALS as f64;
+    let mut sum = at(lower)? + at(upper)?;
+    for i in 1..INTERVALS {
+        let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+        sum += weight * at(lower + i as f64 * h)?;
+    }
+    Ok(sum * h / 3.0)
+}
+
+// 分段函数的条件是否成立，条件为等式或（连续的）比较
+fn holds(condition: &MathExpr, vars: &HashMap<String, f64>) -> Result<bool, EvalError> {
+    if let Some(condition) = without_prose(condition) {
+        return holds(&condition, vars);
+    }
+    match condition.unspanned() {
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Equals, right } => {
+            Ok(evaluate(left, vars)? == evaluate(right, vars)?)
+        }
+        MathExpr::Relation { operands, operators } => {
+            let values = operands
+                .iter()
+                .map(|operand| evaluate(operand, vars))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut result = true;
+            for (operator, pair) in operators.iter().zip(values.windows(2)) {
+                let (left, right) = (pair[0], pair[1]);
+                result &= match operator {
+                    RelationOperator::Equals => left == right,
+                    RelationOperator::NotEquals => left != right,
+                    RelationOperator::Less => left < right,
+                    RelationOperator::LessEqual => left <= right,
+                    RelationOperator::Greater => left > right,
+                    RelationOperator::GreaterEqual => left >= right,
+                    _ => {
+                        return Err(EvalError::Unsupported { construct: format!("condition {}", operator.to_latex()) });
+                    }
+                };
+            }
+            Ok(result)
+        }
+        MathExpr::Parenthesized(condition) => holds(condition, vars),
+        _ => Err(EvalError::Unsupported { construct: "condition".to_string() }),
+    }
+}
+
+// 条件开头的正文只用于排版，如 \text{if } x > 0 中的 if，求值时去掉；没有这样的正文时返回 None
+fn without_prose(condition: &MathExpr) -> Option<MathExpr> {
+    match condition.unspanned() {
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right }
+            if matches!(left.unspanned(), MathExpr::Text { .. }) =>
+        {
+            Some((**right).clone())
+        }
+        MathExpr::BinaryOp { left, operator: operator @ (BinaryOperator::Multiply | BinaryOperator::Equals), right } => {
+            Some(MathExpr::BinaryOp { left: Box::new(without_prose(left)?), operator: operator.clone(), right: right.clone() })
+        }
+        MathExpr::Relation { operands, operators } => {
+            let mut operands = operands.clone();
+            operands[0] = without_prose(operands.first()?)?;
+            Some(MathExpr::Relation { operands, operators: operators.clone() })
+        }
+        _ => None,
+    }
+}
+
+fn lookup(name: &str, vars: &HashMap<String, f64>) -> Result<f64, EvalError> {
+    if let Some(value) = vars.get(name) {
+        return Ok(*value);
+    }
+    if let Some(command) = name.strip_prefix('\\')
+        && let Some(value) = vars.get(command)
+    {
+        return Ok(*value);
+    }
+
+    match name {
+        "\\pi" => return Ok(std::f64::consts::PI),
+        "e" => return Ok(std::f64::consts::E),
+        "\\infty" => return Ok(f64::INFINITY),
+        _ => {}
+    }
+
+    if name.len() > 1 && name.chars().all(|c| c.is_ascii_alphabetic()) {
+        let mut product = 1.0;
+        for c in name.chars() {
+            product *= lookup(&c.to_string(), vars)
+                .map_err(|_| EvalError::UnboundVariable { name: name.to_string() })?;
+        }
+        return Ok(product);
+    }
+
+    Err(EvalError::UnboundVariable { name: name.to_string() })
+}
+
+fn divide(numerator: f64, denominator: f64) -> Result<f64, EvalError> {
+    if denominator == 0.0 {
+        Err(EvalError::DivisionByZero)
+    } else {
+        Ok(numerator / denominator)
+    }
+}
+
+fn power(base: f64, exponent: f64) -> Result<f64, EvalError> {
+    if base == 0.0 && exponent < 0.0 {
+        return Err(EvalError::DivisionByZero);
+    }
+    let value = base.powf(exponent);
+    if value.is_nan() && !base.is_nan() && !exponent.is_nan() {
+        // 负数的非整数次幂
+        return Err(EvalError::Domain { operation: "power".to_string(), value: base });
+    }
+    Ok(value)
+}
+
+fn root(radicand: f64, index: f64) -> Result<f64, EvalError> {
+    if index == 0.0 {
+        return Err(EvalError::DivisionByZero);
+    }
+    if radicand < 0.0 {
+        // 负数只有奇数次方根
+        if index.fract() == 0.0 && index % 2.0 != 0.0 {
+            return Ok(-(-radicand).powf(1.0 / index));
+        }
+        return Err(EvalError::Domain { operation: "root".to_string(), value: radicand });
+    }
+    Ok(radicand.powf(1.0 / index))
+}
+
+fn factorial(value: f64) -> Result<f64, EvalError> {
+    if value < 0.0 || value.fract() != 0.0 {
+        return Err(EvalError::Domain { operation: "factorial".to_string(), value });
+    }
+    // 171! 已超出 f64 的范围
+    if value > 170.0 {
+        return Ok(f64::INFINITY);
+    }
+    Ok((1..=value as u64).map(|k| k as f64).product())
+}
+
+fn call(name: &str, args: &[f64]) -> Result<f64, EvalError> {
+    let unary = |f: fn(f64) -> f64| -> Result<f64, EvalError> {
+        match args {
+            [x] => Ok(f(*x)),
+            _ => Err(EvalError::WrongArgumentCount {
+                function: name.to_string(),
+                expected: 1,
+                found: args.len(),
+            }),
+        }
+    };
+    // valid 判断参数是否在函数的定义域内
+    let checked = |f: fn(f64) -> f64, valid: fn(f64) -> bool| -> Result<f64, EvalError> {
+        if let [x] = args
+            && !valid(*x)
+        {
+            return Err(EvalError::Domain { operation: name.to_string(), value: *x });
+        }
+        unary(f)
+    };
+    // 形如 numerator(x) / denominator(x) 的函数，分母在舍入误差范围内为零时为极点，如 \cot 0、\sec \frac{\pi}{2}
+    let reciprocal = |numerator: fn(f64) -> f64, denominator: fn(f64) -> f64| -> Result<f64, EvalError> {
+        match args {
+            [x] if denominator(*x).abs() <= f64::EPSILON * x.abs() => Err(EvalError::DivisionByZero),
+            [x] => Ok(numerator(*x) / denominator(*x)),
+            _ => unary(numerator),
+        }
+    };
+
+    match name {
+        // 对实数而言，范数与绝对值相同
+        "abs" | "norm" => unary(f64::abs),
+        "\\sin" => unary(f64::sin),
+        "\\cos" => unary(f64::cos),
+        "\\tan" => reciprocal(f64::sin, f64::cos),
+        "\\cot" => reciprocal(f64::cos, f64::sin),
+        "\\sec" => reciprocal(|_| 1.0, f64::cos),
+        "\\csc" => reciprocal(|_| 1.0, f64::sin),
+        "\\arcsin" => checked(f64::asin, |x| (-1.0..=1.0).contains(&x)),
+        "\\arccos" => checked(f64::acos, |x| (-1.0..=1.0).contains(&x)),
+        "\\arctan" => unary(f64::atan),
+        "\\sinh" => unary(f64::sinh),
+        "\\cosh" => unary(f64::cosh),
+        "\\tanh" => unary(f64::tanh),
+        "\\exp" => unary(f64::exp),
+        "\\ln" => checked(f64::ln, |x| x > 0.0),
+        "\\log" | "\\lg" => checked(f64::log10, |x| x > 0.0),
+        "\\max" | "\\min" => {
+            let fold = if name == "\\max" { f64::max } else { f64::min };
+            args.iter().copied().reduce(fold).ok_or(EvalError::WrongArgumentCount {
+                function: name.to_string(),
+                expected: 1,
+                found: 0,
+            })
+        }
+        _ => Err(EvalError::UnknownFunction { name: name.to_string() }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_inline_math;
+
+    fn eval(source: &str) -> Result<f64, EvalError> {
+        evaluate(&parse_inline_math(source).unwrap().expr, &HashMap::new())
+    }
+
+    #[test]
+    fn big_operator() {
+        assert_eq!(eval("\\sum_{i=1}^{100} i").unwrap(), 5050.0);
+        assert_eq!(eval("\\prod_{i=1}^{5} i").unwrap(), 120.0);
+        assert_eq!(eval("\\sum_{i=3}^{1} i").unwrap(), 0.0);
+    }
+
+    // 分段函数取第一个条件成立的分支，没有条件的分支总是成立
+    #[test]
+    fn piecewise() {
+        let expr = parse_inline_math("\\begin{cases} x & x \\ge 0 \\\\ -x & x < 0 \\end{cases}").unwrap().expr;
+        let at = |x: f64| evaluate(&expr, &HashMap::from([("x".to_string(), x)]));
+        assert_eq!(at(2.0), Ok(2.0));
+        assert_eq!(at(-3.0), Ok(3.0));
+        assert_eq!(eval("\\begin{cases} 1 & 2 < 1 \\\\ 5 & \\text{otherwise} \\end{cases}"), Ok(5.0));
+        assert_eq!(eval("\\begin{cases} 1 & 2 < 1 \\end{cases}"), Err(EvalError::NoMatchingBranch));
+    }
+
+    // 条件开头的正文不参与求值，只有正文的条件无法求值
+    #[test]
+    fn piecewise_prose() {
+        let source = "\\begin{cases} 1 & \\text{for } x>0, \\\\ 2 & \\text{if } 2x = -2 \\\\ 0 & \\text{otherwise.} \\end{cases}";
+        let expr = parse_inline_math(source).unwrap().expr;
+        let at = |x: f64| evaluate(&expr, &HashMap::from([("x".to_string(), x)]));
+        assert_eq!(at(3.0), Ok(1.0));
+        assert_eq!(at(-1.0), Ok(2.0));
+        assert_eq!(at(-3.0), Ok(0.0));
+        assert!(matches!(eval("\\begin{cases} 1 & \\text{sometimes} \\end{cases}"), Err(EvalError::Unsupported { .. })));
+    }
+
+    // 不带括号的函数参数
+    #[test]
+    fn function_without_parentheses() {
+        let close = |source: &str, expected: f64| {
+            let value = eval(source).unwrap();
+            assert!((value - expected).abs() < 1e-9, "{} = {}", source, value);
+        };
+        close("\\sin 1", 1f64.sin());
+        close("\\ln 2", 2f64.ln());
+        close("\\cos 2 \\pi", 1.0);
+        close("2 \\sin 1 \\cos 1", 2.0 * 1f64.sin() * 1f64.cos());
+        close("\\sin \\cos 1", 1f64.cos().sin());
+        close("\\sin^2 1 + \\cos^2 1", 1.0);
+        close("3 \\tan^{2} 1", 3.0 * 1f64.tan().powi(2));
+        assert!(matches!(eval("\\sin^{-1} 1"), Err(EvalError::Unsupported { .. })));
+        close("\\lim_{x \\to 0} \\frac{\\sin x}{x}", 1.0);
+        assert!(matches!(eval("\\sin"), Err(EvalError::WrongArgumentCount { found: 0, .. })));
+        assert!(matches!(eval("2 \\sin"), Err(EvalError::WrongArgumentCount { found: 0, .. })));
+    }
+
+    // 极点处报除零错误，而不是返回无穷大
+    #[test]
+    fn trigonometric_poles() {
+        for source in ["\\cot 0", "\\csc(\\pi)", "\\sec \\frac{\\pi}{2}", "\\tan(\\frac{\\pi}{2})", "\\cot(-2\\pi)"] {
+            assert_eq!(eval(source), Err(EvalError::DivisionByZero), "{}", source);
+        }
+        assert!((eval("\\cot(1)").unwrap() - 1.0 / 1f64.tan()).abs() < 1e-12);
+        assert!((eval("\\sec(0)").unwrap() - 1.0).abs() < 1e-12);
+    }
+
+    // 项数过多时报错而不是逐项计算
+    #[test]
+    fn big_operator_term_limit() {
+        for source in ["\\sum_{i=1}^{10^{16}} i", "\\sum_{i=1}^{10^{12}} i", "\\prod_{i=-10^{30}}^{10^{30}} i"] {
+            assert!(matches!(eval(source), Err(EvalError::Unsupported { .. })), "{}", source);
+        }
+    }
+}
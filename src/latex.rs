@@ -0,0 +1,640 @@
+use crate::ast::{BinaryOperator, Environment, MathBlock, MathExpr, SymbolKind, UnaryOperator};
+use crate::lexer::{Lexer, Token};
+use crate::parser::{is_compact_differential, is_derivative_operator};
+
+// LaTeX 输出
+//
+// 输出的文本能被本解析器重新解析为相同的 AST。为此每个节点打印后都记录它在语法上
+// 属于哪一层（对应解析器的递归下降层级），父节点只在子节点无法在当前位置被正确
+// 解析时才加上 \left( ... \right)。
+
+// 打印结果在语法上的类别，顺序由松到紧
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Equality,       // a = b
+    Additive,       // a + b
+    Multiplicative, // a \times b, a \cdot b
+    Power,          // |x|^2，由 parse_power 处理的幂运算
+    Minus,          // -x、+x，以及 parse_unary 中的前缀 !x
+    Implicit,       // 隐式乘法 2 x
+    Operator,       // \sum_{i = 1}^{n} x，其后的乘除法和幂运算都会被吸收进主体
+    Environment,    // \begin{...} ... \end{...}
+    Delimited,      // |x|, ||x||
+    Postfix,        // n!
+    Atom,           // 其后还可以接下标、上标的基本元素
+}
+
+struct Printed {
+    text: String,
+    kind: Kind,
+    // 紧随其后的 ^ 是否会被最后一个基本元素当作上标吸收
+    absorbs_caret: bool,
+    // 紧随其后的 ! 是否会被最后一个元素当作阶乘吸收
+    absorbs_bang: bool,
+    // 末尾是否为求和、求积或极限，此时其后的 \times、\cdot 和 ^ 都会被吸收进主体
+    absorbs_product: bool,
+    // 末尾为没有主体的 \sum、\prod、\int 或 \lim_{x \to a} 时，其后的元素会被当作主体；
+    // 值为 (已有下标, 已有上标)，上下限各只能有一个，再出现时不再被解析为运算符
+    bare_operator: Option<(bool, bool)>,
+}
+
+impl Printed {
+    fn new(text: String, kind: Kind) -> Self {
+        Printed {
+            text,
+            kind,
+            absorbs_caret: kind == Kind::Atom,
+            absorbs_bang: matches!(kind, Kind::Atom | Kind::Postfix),
+            absorbs_product: false,
+            bare_operator: None,
+        }
+    }
+
+    // 用 \left( ... \right) 包裹，结果总是一个基本元素
+    fn wrap(self) -> Printed {
+        Printed::new(format!("\\left({}\\right)", self.text), Kind::Atom)
+    }
+
+    // 可以作为 parse_factor 的结果
+    fn is_factor(&self) -> bool {
+        self.kind >= Kind::Minus
+    }
+
+    // 其后的基本元素会被吸收进它自身（例如 -a 后面接 b 会被解析为 -(a b)）
+    fn is_right_open(&self) -> bool {
+        matches!(self.kind, Kind::Power | Kind::Minus | Kind::Operator)
+    }
+
+    // 可以作为隐式乘法的右操作数
+    fn is_implicit_operand(&self) -> bool {
+        matches!(self.kind, Kind::Atom | Kind::Postfix | Kind::Delimited | Kind::Operator)
+    }
+
+    // 可以作为 parse_power 的结果
+    fn is_power(&self) -> bool {
+        self.kind == Kind::Power || self.is_factor()
+    }
+
+    // 可以作为 \times、\cdot 的左操作数
+    fn is_multiplicative(&self) -> bool {
+        self.kind >= Kind::Multiplicative && !self.absorbs_product
+    }
+
+    fn fits(self, ok: bool) -> Printed {
+        if ok { self } else { self.wrap() }
+    }
+}
+
+pub fn to_latex(expr: &MathExpr) -> String {
+    print(expr).text
+}
+
+// 按提取时的分隔符输出，没有分隔符时按显示模式使用 $$ 或 $
+pub fn block_to_latex(block: &MathBlock) -> String {
+    if let Some(delimiter) = block.delimiter {
+        format!("{}{}{}", delimiter.open(), to_latex(&block.expr), delimiter.close())
+    } else if block.display_style {
+        format!("$${}$$", to_latex(&block.expr))
+    } else {
+        format!("${}$", to_latex(&block.expr))
+    }
+}
+
+// 导数符号写在函数名与参数之间，如 f'(x)；高阶导数在函数名之后写成 f^{(4)}，
+// 其他表达式只能用连续的导数符号，且须是能接受后缀的基本元素
+fn print_prime(expr: &MathExpr, order: u32) -> Printed {
+    let marks = |named: bool| match order {
+        4.. if named => format!("^{{({})}}", order),
+        _ => "'".repeat(order as usize),
+    };
+    let is_name = |name: &str| {
+        let name = name.strip_prefix('\\').unwrap_or(name);
+        name.starts_with(|c: char| c.is_ascii_alphabetic()) && name.chars().all(|c| c.is_ascii_alphanumeric())
+    };
+    let text = match expr.unspanned() {
+        MathExpr::FunctionCall { name, args } if is_name(name) && !(matches!(name.as_str(), "abs" | "norm") && args.len() == 1) => {
+            let args = args.iter().map(to_latex).collect::<Vec<_>>().join(", ");
+            format!("{}{}({})", name, marks(true), args)
+        }
+        MathExpr::Variable(name) if is_name(name) && !name.starts_with('\\') => format!("{}{}", name, marks(true)),
+        MathExpr::Symbol(symbol) => format!("{}{}", symbol.command(), marks(true)),
+        _ => {
+            let p = print(expr);
+            let ok = p.kind == Kind::Atom && p.absorbs_bang;
+            format!("{}{}", p.fits(ok).text, marks(false))
+        }
+    };
+    Printed::new(text, Kind::Atom)
+}
+
+fn print(expr: &MathExpr) -> Printed {
+    match expr {
+        MathExpr::Number(n) => print_number(*n),
+        // 单独出现的 \right) 和 \right] 被解析为名为 ")"、"]" 的变量
+        MathExpr::Variable(name) if name == ")" || name == "]" => {
+            Printed::new(format!("\\right{}", name), Kind::Atom)
+        }
+        MathExpr::Variable(name) => {
+            let bare_operator = match name.as_str() {
+                "\\sum" | "\\prod" | "\\int" | "\\iint" | "\\iiint" | "\\oint" => Some((false, false)),
+                _ => None,
+            };
+            Printed {
+                bare_operator,
+                ..Printed::new(name.clone(), Kind::Atom)
+            }
+        }
+        MathExpr::Symbol(symbol) => {
+            // \nabla 和 \Delta 之后的元素会被当作微分算子的作用对象；
+            // \nabla 只在带有 ^{2} 时仍是算子，\Delta 带有任何上下标后都不再是算子
+            let bare_operator = match symbol.name.as_str() {
+                "nabla" => Some((true, false)),
+                "Delta" => Some((true, true)),
+                _ => None,
+            };
+            Printed {
+                bare_operator,
+                ..Printed::new(symbol.command(), Kind::Atom)
+            }
+        }
+        MathExpr::BinaryOp { left, operator, right } => print_binary(print(left), operator, print(right)),
+        MathExpr::Relation { operands, operators } => {
+            let operand = |expr: &MathExpr| {
+                let p = print(expr);
+                let ok = p.kind >= Kind::Additive;
+                p.fits(ok).text
+            };
+            let mut text = operand(&operands[0]);
+            for (relation, expr) in operators.iter().zip(&operands[1..]) {
+                text.push_str(&format!(" {} {}", relation.to_latex(), operand(expr)));
+            }
+            Printed::new(text, Kind::Equality)
+        }
+        MathExpr::UnaryOp { operator, expr } => {
+            let operand = print(expr);
+            let postfix = matches!(operand.kind, Kind::Atom | Kind::Postfix) && operand.absorbs_bang;
+            match operator {
+                UnaryOperator::Factorial if postfix => {
+                    Printed::new(format!("{}!", operand.text), Kind::Postfix)
+                }
+                UnaryOperator::Factorial => prefix("!", operand),
+                UnaryOperator::Plus => prefix("+", operand),
+                UnaryOperator::Minus => prefix("-", operand),
+            }
+        }
+        MathExpr::FunctionCall { name, args } => {
+            if name == "abs" && args.len() == 1 {
+                Printed::new(format!("|{}|", to_latex(&args[0])), Kind::Delimited)
+            } else if name == "norm" && args.len() == 1 {
+                Printed::new(format!("||{}||", to_latex(&args[0])), Kind::Delimited)
+            } else {
+                let args_str = args.iter().map(to_latex).collect::<Vec<_>>().join(", ");
+                Printed::new(format!("{}({})", name, args_str), Kind::Atom)
+            }
+        }
+        MathExpr::Prime { expr, order } => print_prime(expr, *order),
+        MathExpr::Subscript { base, subscript } => script(base, "_", subscript),
+        MathExpr::Superscript { base, superscript } => script(base, "^", superscript),
+        MathExpr::Fraction { numerator, denominator } => {
+            // \frac{d}{dx} 之后紧跟的元素会被当作被求导的表达式
+            let bare_operator = is_derivative_operator(numerator, denominator).then_some((true, true));
+            Printed {
+                bare_operator,
+                ..Printed::new(
+                    format!("\\frac{{{}}}{{{}}}", to_latex(numerator), to_latex(denominator)),
+                    Kind::Atom,
+                )
+            }
+        }
+        MathExpr::Root { radicand, index } => {
+            let text = match index {
+                Some(index) => format!("\\sqrt[{}]{{{}}}", to_latex(index), to_latex(radicand)),
+                None => format!("\\sqrt{{{}}}", to_latex(radicand)),
+            };
+            Printed::new(text, Kind::Atom)
+        }
+        MathExpr::Parenthesized(expr) => print(expr).wrap(),
+        MathExpr::Matrix { rows, environment } => {
+            // 交换图中对象和箭头之间没有 &
+            let separator = if *environment == Environment::CD { " " } else { " & " };
+            let rows_str = rows
+                .iter()
+                .map(|row| row.iter().map(|cell| to_latex(cell).trim().to_string()).collect::<Vec<_>>().join(separator))
+                .map(|row| row.trim().to_string())
+                .collect::<Vec<_>>()
+                .join(" \\\\ ");
+            Printed::new(
+                format!("{} {} \\end{{{}}}", environment.begin(), rows_str, environment.name()),
+                Kind::Environment,
+            )
+        }
+        MathExpr::Piecewise { branches } => {
+            let branches = branches
+                .iter()
+                .map(|(value, condition)| {
+                    let condition = match condition {
+                        Some(condition) => to_latex(condition),
+                        None => "\\text{otherwise}".to_string(),
+                    };
+                    format!("{} & {}", to_latex(value), condition)
+                })
+                .collect::<Vec<_>>()
+                .join(" \\\\ ");
+            Printed::new(format!("\\begin{{cases}} {} \\end{{cases}}", branches), Kind::Environment)
+        }
+        MathExpr::EquationSystem { environment, rows } => {
+            let rows = rows
+                .iter()
+                .map(|row| {
+                    let cells = row.cells.iter().map(|cell| to_latex(cell).trim().to_string()).collect::<Vec<_>>();
+                    format!("{}{}", cells.join(" & ").trim(), row.commands())
+                })
+                .collect::<Vec<_>>()
+                .join(" \\\\ ");
+            Printed::new(
+                format!("\\begin{{{0}}} {1} \\end{{{0}}}", environment.name(), rows),
+                Kind::Environment,
+            )
+        }
+        MathExpr::Empty => Printed::new(String::new(), Kind::Atom),
+        MathExpr::Text { command, text } => Printed::new(format!("{}{{{}}}", command.command(), text), Kind::Atom),
+        MathExpr::CdArrow { direction, first, second } => {
+            let label = |label: &Option<Box<MathExpr>>| label.as_deref().map(|label| format!("{{{}}}", to_latex(label))).unwrap_or_default();
+            Printed::new(direction.to_latex(&label(first), &label(second)), Kind::Atom)
+        }
+        MathExpr::Derivative { variable, order, expression } => {
+            let denominator = format!("{}{}", differential(variable), power(u64::from(*order)));
+            print_derivative("d", u64::from(*order), denominator, expression)
+        }
+        MathExpr::PartialDerivative { variables, expression } => {
+            let order = variables.iter().map(|(_, order)| u64::from(*order)).sum();
+            let denominator = variables
+                .iter()
+                .map(|(variable, order)| format!("\\partial {}{}", variable, power(u64::from(*order))))
+                .collect::<Vec<_>>()
+                .join(" ");
+            print_derivative("\\partial", order, denominator, expression)
+        }
+        MathExpr::DifferentialOperator { kind, operand } => {
+            // 作用对象按幂运算表达式解析，外层的一对括号仅用于分组，需要再加一层
+            let operand = {
+                let p = print(operand);
+                let ok = p.is_power()
+                    && !p.text.starts_with('-')
+                    && !matches!(operand.unspanned(), MathExpr::Parenthesized(_));
+                p.fits(ok)
+            };
+            // 其后的隐式乘法、^ 和 ! 会被吸收进作用对象，\times 和 \cdot 则不会
+            Printed {
+                text: format!("{} {}", kind.command(), operand.text),
+                kind: Kind::Operator,
+                absorbs_caret: true,
+                absorbs_bang: true,
+                absorbs_product: operand.absorbs_product,
+                bare_operator: operand.bare_operator,
+            }
+        }
+        MathExpr::BigOperator { kind, index, lower, upper, body } => {
+            let mut text = kind.command().to_string();
+            match (index, lower) {
+                (Some(index), Some(lower)) => text.push_str(&format!("_{{{} = {}}}", index, to_latex(lower))),
+                (Some(index), None) => text.push_str(&format!("_{{{}}}", index)),
+                (None, Some(lower)) => text.push_str(&format!("_{{{}}}", to_latex(lower))),
+                (None, None) => {}
+            }
+            if let Some(upper) = upper {
+                text.push_str(&format!("^{{{}}}", to_latex(upper)));
+            }
+            operator(text, print(body))
+        }
+        MathExpr::Limit { variable, target, body } => {
+            operator(format!("\\lim_{{{} \\to {}}}", variable, to_latex(target)), print(body))
+        }
+        MathExpr::Integral { kind, lower, upper, integrand, variables, dot_product } => {
+            let mut text = kind.command().to_string();
+            if let Some(lower) = lower {
+                text.push_str(&format!("_{{{}}}", to_latex(lower)));
+            }
+            if let Some(upper) = upper {
+                text.push_str(&format!("^{{{}}}", to_latex(upper)));
+            }
+            // 微分按隐式乘法接在被积函数之后，点积时第一个微分之前为 \cdot
+            let mut body = {
+                let integrand = print(integrand);
+                let ok = is_body(&integrand);
+                integrand.fits(ok)
+            };
+            for (i, variable) in variables.iter().enumerate() {
+                let differential = print(&MathExpr::Variable(format!("d{}", variable)));
+                let operator = if *dot_product && i == 0 { BinaryOperator::DotProduct } else { BinaryOperator::Multiply };
+                body = print_binary(body, &operator, differential);
+            }
+            operator(text, body)
+        }
+        MathExpr::Spanned { expr, .. } => print(expr),
+    }
+}
+
+// 求和、求积、极限与积分，主体按乘除法表达式解析
+fn operator(head: String, body: Printed) -> Printed {
+    let body = {
+        let ok = is_body(&body);
+        body.fits(ok)
+    };
+    Printed {
+        text: format!("{} {}", head, body.text),
+        kind: Kind::Operator,
+        absorbs_caret: false,
+        absorbs_bang: false,
+        absorbs_product: true,
+        bare_operator: None,
+    }
+}
+
+// 可以作为运算符的主体：乘除法表达式，且不以 -、+、! 开头
+fn is_body(body: &Printed) -> bool {
+    body.kind >= Kind::Multiplicative && !body.text.starts_with(['-', '+', '!'])
+}
+
+fn print_number(n: f64) -> Printed {
+    if n.is_infinite() {
+        let text = if n > 0.0 { "\\infty" } else { "-\\infty" };
+        return Printed::new(text.to_string(), if n > 0.0 { Kind::Atom } else { Kind::Minus });
+    }
+
+    // 负数会被解析为一元负号，需要按负号的规则处理
+    let kind = if n.is_sign_negative() && n != 0.0 { Kind::Minus } else { Kind::Atom };
+    Printed::new(format!("{}", n), kind)
+}
+
+fn prefix(op: &str, operand: Printed) -> Printed {
+    let operand = {
+        let ok = operand.is_factor();
+        operand.fits(ok)
+    };
+    Printed {
+        text: format!("{}{}", op, operand.text),
+        kind: Kind::Minus,
+        absorbs_caret: operand.absorbs_caret,
+        absorbs_bang: operand.absorbs_bang,
+        absorbs_product: operand.absorbs_product,
+        bare_operator: operand.bare_operator,
+    }
+}
+
+// 下标和上标的底数必须是能继续接收 _ 和 ^ 的基本元素
+fn script(base: &MathExpr, op: &str, script: &MathExpr) -> Printed {
+    let base = {
+        let base = print(base);
+        let ok = base.kind == Kind::Atom && base.absorbs_caret;
+        base.fits(ok)
+    };
+    let script = print(script);
+    let bare_operator = match (base.bare_operator, op) {
+        (Some((false, sup)), "_") => Some((true, sup)),
+        // \nabla 只在上标为 2 时仍是算子
+        (Some((sub, false)), "^") if base.text != "\\nabla" || script.text == "2" => Some((sub, true)),
+        // \lim 只在下标为 x \to a 的形式时作为极限，之后不能再有上下标
+        _ if base.text == "\\lim" && op == "_" && is_limit_head(&script.text) => Some((true, true)),
+        _ => None,
+    };
+    Printed {
+        bare_operator,
+        ..Printed::new(format!("{}{}{{{}}}", base.text, op, script.text), Kind::Atom)
+    }
+}
+
+fn print_binary(l: Printed, operator: &BinaryOperator, r: Printed) -> Printed {
+    match operator {
+        BinaryOperator::Equals => {
+            // 两侧都不能再是等式或关系，否则重新解析时会合并为一条关系链
+            let l = {
+                let ok = l.kind >= Kind::Additive;
+                l.fits(ok)
+            };
+            let r = {
+                let ok = r.kind >= Kind::Additive;
+                r.fits(ok)
+            };
+            Printed::new(format!("{} = {}", l.text, r.text), Kind::Equality)
+        }
+        BinaryOperator::Add | BinaryOperator::Subtract => {
+            let l = {
+                let ok = l.kind >= Kind::Additive;
+                l.fits(ok)
+            };
+            let r = {
+                let ok = r.kind >= Kind::Multiplicative;
+                r.fits(ok)
+            };
+            Printed::new(
+                format!("{} {} {}", l.text, operator.to_string(), r.text),
+                Kind::Additive,
+            )
+        }
+        BinaryOperator::Multiply => {
+            let implicit_left = l.is_factor() && !l.is_right_open() && !l.absorbs_product && l.bare_operator.is_none();
+            let explicit_left = l.is_multiplicative();
+            if implicit_left && r.is_implicit_operand() {
+                implicit(l, r)
+            } else if explicit_left && r.is_power() {
+                // 紧跟在 \nabla 之后的 \times 会被解析为旋度
+                let op = if bare_nabla(&l) { "*" } else { "\\times" };
+                explicit(l, op, r)
+            } else {
+                let l = l.fits(implicit_left);
+                let r = {
+                    let ok = r.is_implicit_operand();
+                    r.fits(ok)
+                };
+                implicit(l, r)
+            }
+        }
+        BinaryOperator::DotProduct => {
+            let l = {
+                // 紧跟在 \nabla 之后的 \cdot 会被解析为散度，除非右侧以 -、+、! 开头
+                let ok = l.is_multiplicative() && (!bare_nabla(&l) || r.text.starts_with(['-', '+', '!']));
+                l.fits(ok)
+            };
+            let r = {
+                let ok = r.is_power();
+                r.fits(ok)
+            };
+            explicit(l, "\\cdot", r)
+        }
+        BinaryOperator::Divide => Printed::new(
+            format!("\\frac{{{}}}{{{}}}", l.text, r.text),
+            Kind::Atom,
+        ),
+        BinaryOperator::Power => {
+            // 底数的最后一个基本元素若会吸收 ^，则会被解析为上标，需要加括号
+            let l = {
+                let ok = l.is_power() && !l.absorbs_caret && !l.absorbs_product;
+                l.fits(ok)
+            };
+            if l.absorbs_caret {
+                // 加括号后底数变为基本元素，只能写成上标的形式
+                return Printed::new(format!("{}^{{{}}}", l.text, r.text), Kind::Atom);
+            }
+            let r = {
+                let ok = r.is_factor();
+                r.fits(ok)
+            };
+            Printed {
+                text: format!("{}^{}", l.text, r.text),
+                kind: Kind::Power,
+                absorbs_caret: r.absorbs_caret,
+                absorbs_bang: r.absorbs_bang,
+                absorbs_product: r.absorbs_product,
+                bare_operator: r.bare_operator,
+            }
+        }
+    }
+}
+
+// 导数和偏导数，d 为 d 或 \\partial，denominator 为已经写好的分母
+fn print_derivative(d: &str, order: u64, denominator: String, expression: &MathExpr) -> Printed {
+    match leibniz_operand(expression) {
+        // 单个变量写在分子中，如 \frac{dy}{dx}、\frac{d^{2} y}{dx^{2}}、\frac{\partial f}{\partial x}；
+        // 与其他导数相同，之后的 ^ 和 ! 不会作为它的上标和阶乘解析
+        Some(operand) => {
+            let numerator = if order == 1 && d == "d" {
+                differential(&operand)
+            } else {
+                format!("{}{} {}", d, power(order), operand)
+            };
+            Printed {
+                absorbs_caret: false,
+                absorbs_bang: false,
+                ..Printed::new(format!("\\frac{{{}}}{{{}}}", numerator, denominator), Kind::Atom)
+            }
+        }
+        // 其他表达式写在方括号中，作为算子的主体
+        None => operator(
+            format!("\\frac{{{}{}}}{{{}}}", d, power(order), denominator),
+            Printed::new(format!("[{}]", to_latex(expression)), Kind::Atom),
+        ),
+    }
+}
+
+// dx、d\theta 这样的微分，多字母的变量与 d 之间加空格，如 d ab，否则会被解析为标识符 dab
+fn differential(variable: &str) -> String {
+    if is_compact_differential(variable) || variable.starts_with('\\') {
+        format!("d{}", variable)
+    } else {
+        format!("d {}", variable)
+    }
+}
+
+// 导数的阶数，一阶时省略
+fn power(order: u64) -> String {
+    if order == 1 { String::new() } else { format!("^{{{}}}", order) }
+}
+
+// 可以直接写在导数分子中的变量：标识符或希腊字母
+fn leibniz_operand(expr: &MathExpr) -> Option<String> {
+    match expr.unspanned() {
+        MathExpr::Variable(name) if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            Some(name.clone())
+        }
+        MathExpr::Symbol(symbol) if symbol.kind == SymbolKind::Letter => Some(symbol.command()),
+        _ => None,
+    }
+}
+
+// 下标以 x \to 或 \alpha \to 开头，与 Parser 识别极限的规则相同
+fn is_limit_head(text: &str) -> bool {
+    let tokens: Vec<Token> = Lexer::new(text).take(4).collect();
+    match tokens.as_slice() {
+        [Token::Identifier(_), Token::Backslash, Token::Identifier(to), ..]
+        | [Token::Backslash, Token::Identifier(_), Token::Backslash, Token::Identifier(to)] => to == "to",
+        _ => false,
+    }
+}
+
+fn implicit(l: Printed, r: Printed) -> Printed {
+    // 右侧为运算符时，其后的元素同样会被吸收，与负号的情形相同
+    let kind = if r.kind == Kind::Operator { Kind::Minus } else { Kind::Implicit };
+    Printed {
+        text: format!("{} {}", l.text, r.text),
+        kind,
+        absorbs_caret: r.absorbs_caret,
+        absorbs_bang: r.absorbs_bang,
+        absorbs_product: r.absorbs_product,
+        bare_operator: r.bare_operator,
+    }
+}
+
+// 末尾为没有作用对象的 \nabla
+fn bare_nabla(p: &Printed) -> bool {
+    p.bare_operator.is_some() && p.text.ends_with("\\nabla")
+}
+
+fn explicit(l: Printed, op: &str, r: Printed) -> Printed {
+    Printed {
+        absorbs_product: r.absorbs_product,
+        bare_operator: r.bare_operator,
+        ..Printed::new(format!("{} {} {}", l.text, op, r.text), Kind::Multiplicative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn var(name: &str) -> MathExpr {
+        MathExpr::Variable(name.to_string())
+    }
+
+    fn binary(left: MathExpr, operator: BinaryOperator, right: MathExpr) -> MathExpr {
+        MathExpr::BinaryOp { left: Box::new(left), operator, right: Box::new(right) }
+    }
+
+    // 解析输出的 LaTeX 得到相同的语法树
+    #[test]
+    fn roundtrip() {
+        let sources = [
+            "x_1 + y_{ij}",
+            "(a+b)c",
+            "a - (b - c)",
+            "x^{y^z}",
+            "(x^2)^3",
+            "-(-x)",
+            "a/b/c",
+            "a/(b/c)",
+            "\\sqrt[3]{x+1}",
+            "\\begin{pmatrix} 1 & 2 \\\\ 3 & 4 \\end{pmatrix}",
+            "|x - 1| + (n+1)!",
+            "f'(x) = \\frac{dy}{dx}",
+            "\\sum_{i=1}^{n} i^2",
+            "\\lim_{x \\to 0} \\frac{\\sin x}{x}",
+            "\\int_0^1 x^2 \\, dx",
+            "0 \\le x < 1",
+            "x^+y z",
+            "\\nabla^\\theta f",
+        ];
+        for source in sources {
+            let expr = Parser::new(source).parse().unwrap();
+            let printed = to_latex(&expr);
+            assert_eq!(Parser::new(&printed).parse().unwrap(), expr, "{} → {}", source, printed);
+        }
+    }
+
+    // 只在优先级需要时加括号
+    #[test]
+    fn minimal_parentheses() {
+        let sum = || binary(var("b"), BinaryOperator::Add, var("c"));
+        assert_eq!(to_latex(&binary(var("a"), BinaryOperator::Subtract, sum())), "a - \\left(b + c\\right)");
+        assert_eq!(to_latex(&binary(sum(), BinaryOperator::Add, var("a"))), "b + c + a");
+        // 右侧的加法需要括号，否则重新解析时按左结合得到不同的语法树
+        assert_eq!(to_latex(&binary(var("a"), BinaryOperator::Add, sum())), "a + \\left(b + c\\right)");
+        assert_eq!(to_latex(&binary(sum(), BinaryOperator::Multiply, var("a"))), "\\left(b + c\\right) a");
+        assert_eq!(to_latex(&binary(var("a"), BinaryOperator::Multiply, var("b"))), "a b");
+        assert_eq!(to_latex(&binary(var("a"), BinaryOperator::DotProduct, var("b"))), "a \\cdot b");
+        assert_eq!(to_latex(&binary(var("a"), BinaryOperator::Divide, var("b"))), "\\frac{a}{b}");
+        assert_eq!(to_latex(&binary(sum(), BinaryOperator::Power, MathExpr::Number(2.0))), "\\left(b + c\\right)^{2}");
+        let subscript = MathExpr::Subscript { base: Box::new(var("x")), subscript: Box::new(MathExpr::Number(1.0)) };
+        assert_eq!(to_latex(&subscript), "x_{1}");
+    }
+}
@@ -1,4 +1,5 @@
 use logos::Logos;
+use std::ops::Range;
 
 #[derive(Logos, Debug, Clone, PartialEq)]
 pub enum Token {
@@ -25,8 +26,14 @@ pub enum Token {
     Exclamation,
     #[token("=")]
     Equals,
+    #[token("<")]
+    Less,
+    #[token(">")]
+    Greater,
     #[token("\\cdot")]
     CDot,
+    #[token("\\times")]
+    Times,
     #[token("'")]
     Prime, // 导数符号
     
@@ -53,6 +60,8 @@ pub enum Token {
     Semicolon,
     #[token("\\\\")]
     DoubleBackslash,
+    #[token("@")]
+    At, // 交换图中的箭头，如 @>>>
     
     // 环境命令
     #[token("\\begin")]
@@ -83,6 +92,22 @@ pub enum Token {
     // 空白（跳过）
     #[regex(r"[ \t\n\r]+", logos::skip)]
     Whitespace,
+
+    // 间距命令 \, \: \; \! \  \quad \qquad（跳过），如 f(x) \, dx
+    #[regex(r"\\[,:;! ]|\\q?quad", logos::skip)]
+    Spacing,
+
+    // 无法识别的字符，由 Lexer 在词法错误处产生，不会中断标记流
+    Unknown(String),
+}
+
+// 带位置信息的标记
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Range<usize>, // 在输入中的字节范围
+    pub line: usize,        // 行号，从1开始
+    pub column: usize,      // 列号，从1开始，按字符计数
 }
 
 #[derive(Clone)]
@@ -96,12 +121,118 @@ impl<'a> Lexer<'a> {
             inner: Token::lexer(input),
         }
     }
+
+    // 最近一次返回的标记在输入中的字节范围
+    pub fn span(&self) -> Range<usize> {
+        self.inner.span()
+    }
+
+    // 转换为带位置信息的标记流
+    pub fn spanned(self) -> SpannedLexer<'a> {
+        SpannedLexer {
+            lexer: self,
+            line: 1,
+            line_start: 0,
+            scanned: 0,
+        }
+    }
 }
 
 impl<'a> Iterator for Lexer<'a> {
     type Item = Token;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next().and_then(|result| result.ok())
+        match self.inner.next()? {
+            Ok(token) => Some(token),
+            Err(()) => Some(Token::Unknown(self.inner.slice().to_string())),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SpannedLexer<'a> {
+    lexer: Lexer<'a>,
+    line: usize,
+    line_start: usize, // 当前行起始的字节偏移
+    scanned: usize,    // 已统计过换行的字节偏移
+}
+
+impl<'a> Iterator for SpannedLexer<'a> {
+    type Item = SpannedToken;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.lexer.next()?;
+        let span = self.lexer.span();
+        let source = self.lexer.inner.source();
+
+        // 标记按顺序产生，只需统计上一次位置之后的换行
+        for (offset, ch) in source[self.scanned..span.start].char_indices() {
+            if ch == '\n' {
+                self.line += 1;
+                self.line_start = self.scanned + offset + 1;
+            }
+        }
+        self.scanned = span.start;
+
+        let column = source[self.line_start..span.start].chars().count() + 1;
+        Some(SpannedToken {
+            token,
+            span,
+            line: self.line,
+            column,
+        })
     }
-}
\ No newline at end of file
+}
+
+// 计算字节偏移对应的行号和列号（均从1开始，列按字符计数）
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spanned(source: &str) -> Vec<(Token, Range<usize>, usize, usize)> {
+        Lexer::new(source).spanned().map(|t| (t.token, t.span, t.line, t.column)).collect()
+    }
+
+    #[test]
+    fn spans() {
+        assert_eq!(
+            spanned("x^{2}"),
+            [
+                (Token::Identifier("x".to_string()), 0..1, 1, 1),
+                (Token::Caret, 1..2, 1, 2),
+                (Token::LCurly, 2..3, 1, 3),
+                (Token::Number(2.0), 3..4, 1, 4),
+                (Token::RCurly, 4..5, 1, 5),
+            ]
+        );
+        let mut lexer = Lexer::new("a + bc");
+        lexer.next();
+        lexer.next();
+        assert_eq!(lexer.next(), Some(Token::Identifier("bc".to_string())));
+        assert_eq!(lexer.span(), 4..6);
+    }
+
+    // 行号按换行计算，列号按字符而不是字节计算；跳过的空白和间距命令不产生标记
+    #[test]
+    fn line_and_column() {
+        let tokens = spanned("x\n+ é\\, y");
+        assert_eq!(tokens[1], (Token::Plus, 2..3, 2, 1));
+        assert_eq!(tokens[2], (Token::Unknown("é".to_string()), 4..6, 2, 3));
+        assert_eq!(tokens[3], (Token::Identifier("y".to_string()), 9..10, 2, 7));
+        assert_eq!(line_column("x\n+ é\\, y", 9), (2, 7));
+    }
+
+    // 无法识别的字符产生 Unknown 标记，之后的标记照常产生
+    #[test]
+    fn unknown_character() {
+        let tokens: Vec<Token> = Lexer::new("a % 3.5").collect();
+        assert_eq!(tokens, [Token::Identifier("a".to_string()), Token::Unknown("%".to_string()), Token::Number(3.5)]);
+    }
+}
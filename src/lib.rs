@@ -1,86 +1,206 @@
 pub mod ast;
+pub mod diff;
+pub mod equiv;
+pub mod error;
+pub mod eval;
+pub mod latex;
 pub mod lexer;
+pub mod markdown;
+pub mod mathml;
 pub mod parser;
+pub mod simplify;
+pub mod solve;
 
 use ast::MathBlock;
+use lexer::line_column;
+use markdown::{find_math_with, Delimiters, MarkdownFormula};
+pub use error::ParseError;
+pub use diff::DiffError;
+pub use eval::EvalError;
+pub use solve::SolveError;
 use parser::Parser;
 
-pub fn parse_math_block(input: &str, display_style: bool) -> Result<MathBlock, String> {
-    let mut parser = Parser::new(input);
-    let expr = parser.parse_expression()?;
-    
+pub fn parse_math_block(input: &str, display_style: bool) -> Result<MathBlock, ParseError> {
+    parse_with(Parser::new(input), display_style)
+}
+
+// 与 parse_math_block 相同，但每个节点都带有其在 input 中的字节范围
+pub fn parse_math_block_with_spans(input: &str, display_style: bool) -> Result<MathBlock, ParseError> {
+    parse_with(Parser::new(input).with_spans(true), display_style)
+}
+
+// 严格模式：输入中有未知命令或多余内容时报错，而不是只返回能解析的前缀；
+// 无法识别的字符在两种模式下都报告为 InvalidCharacter
+pub fn parse_math_block_strict(input: &str, display_style: bool) -> Result<MathBlock, ParseError> {
+    parse_with(Parser::new(input).strict(true), display_style)
+}
+
+fn parse_with(mut parser: Parser, display_style: bool) -> Result<MathBlock, ParseError> {
+    let expr = parser.parse()?;
+
     Ok(MathBlock {
         expr,
         display_style,
+        delimiter: None,
     })
 }
 
-pub fn parse_inline_math(input: &str) -> Result<MathBlock, String> {
+pub fn parse_inline_math(input: &str) -> Result<MathBlock, ParseError> {
     parse_math_block(input, false)
 }
 
-pub fn parse_display_math(input: &str) -> Result<MathBlock, String> {
+pub fn parse_display_math(input: &str) -> Result<MathBlock, ParseError> {
     parse_math_block(input, true)
 }
 
-// 从Markdown文本中提取并解析数学公式
+// 从Markdown文本中提取并解析数学公式，节点位置为在整个文档中的字节范围
+// 代码块、行内代码、HTML 和转义的 \$ 中的 $ 不会被当作公式，见 markdown::find_math
+// 解析失败的公式会被忽略，需要错误信息时使用 parse_markdown_math_report
 pub fn parse_markdown_math(markdown: &str) -> Vec<MathBlock> {
-    let mut math_blocks = Vec::new();
-    let markdown_chars: Vec<char> = markdown.chars().collect();
-    let mut i = 0;
-    
-    while i < markdown_chars.len() {
-        if markdown_chars[i] == '$' {
-            // 检查是否是显示模式($$)
-            let display_style = i + 1 < markdown_chars.len() && markdown_chars[i + 1] == '$';
-            
-            // 确定结束分隔符和偏移量
-            let (start_offset, end_delimiter) = if display_style {
-                (2, "$$")
-            } else {
-                (1, "$")
-            };
-            
-            // 查找结束分隔符
-            let start_index = i + start_offset;
-            let mut end_index = None;
-            
-            for j in start_index..markdown_chars.len() {
-                if markdown_chars[j] == end_delimiter.chars().next().unwrap() {
-                    if display_style {
-                        // 对于显示模式，需要检查下一个字符是否也是$
-                        if j + 1 < markdown_chars.len() && markdown_chars[j + 1] == '$' {
-                            end_index = Some(j);
-                            break;
-                        }
-                    } else {
-                        // 对于内联模式，找到$即可
-                        end_index = Some(j);
-                        break;
-                    }
+    parse_markdown_math_with(markdown, Delimiters::default())
+}
+
+// 与 parse_markdown_math 相同，但识别 delimiters 中的分隔符，如 \(...\)、\[...\]
+pub fn parse_markdown_math_with(markdown: &str, delimiters: Delimiters) -> Vec<MathBlock> {
+    parse_markdown_math_report_with(markdown, false, delimiters)
+        .into_iter()
+        .filter_map(|formula| formula.result.ok())
+        .collect()
+}
+
+// 与 parse_markdown_math 相同，但返回所有公式（包括解析失败的）及其位置和原始文本
+// strict 为 true 时按严格模式解析，公式中有未知命令或多余内容也视为失败
+pub fn parse_markdown_math_report(markdown: &str, strict: bool) -> Vec<MarkdownFormula> {
+    parse_markdown_math_report_with(markdown, strict, Delimiters::default())
+}
+
+// 与 parse_markdown_math_report 相同，但识别 delimiters 中的分隔符
+pub fn parse_markdown_math_report_with(markdown: &str, strict: bool, delimiters: Delimiters) -> Vec<MarkdownFormula> {
+    find_math_with(markdown, delimiters)
+        .into_iter()
+        .map(|segment| {
+            let source = segment.source(markdown);
+            let offset = segment.content.start;
+            let parser = Parser::new(&source).with_spans(true).strict(strict);
+            let result = match parse_with(parser, segment.display_style) {
+                Ok(mut math_block) => {
+                    math_block.expr.shift_spans(offset);
+                    math_block.delimiter = Some(segment.delimiter);
+                    Ok(math_block)
                 }
-            }
-            
-            // 如果找到了结束分隔符，则提取内容
-            if let Some(end_idx) = end_index {
-                let math_content: String = markdown_chars[start_index..end_idx].iter().collect();
-                if let Ok(math_block) = parse_math_block(&math_content, display_style) {
-                    math_blocks.push(math_block);
+                Err(mut error) => {
+                    error.shift_span(offset);
+                    Err(error)
                 }
-                
-                // 更新索引位置
-                i = if display_style {
-                    end_idx + 2  // 跳过$$
-                } else {
-                    end_idx + 1  // 跳过$
-                };
-            } else {
-                i += 1;
+            };
+            let (line, column) = line_column(markdown, segment.span.start);
+            MarkdownFormula {
+                span: segment.span,
+                line,
+                column,
+                source,
+                display_style: segment.display_style,
+                delimiter: segment.delimiter,
+                result,
             }
-        } else {
-            i += 1;
+        })
+        .collect()
+}
+
+// 替换文档中的公式，公式之外的文本保持不变
+// f 的参数为解析结果和包括分隔符在内的原始文本，返回 None 时保留原文；解析失败的公式也保留原文
+pub fn rewrite_markdown_math<F>(markdown: &str, f: F) -> String
+where
+    F: Fn(&MathBlock, &str) -> Option<String>,
+{
+    rewrite_markdown_math_with(markdown, Delimiters::default(), f)
+}
+
+// 与 rewrite_markdown_math 相同，但识别 delimiters 中的分隔符
+pub fn rewrite_markdown_math_with<F>(markdown: &str, delimiters: Delimiters, f: F) -> String
+where
+    F: Fn(&MathBlock, &str) -> Option<String>,
+{
+    let mut output = String::with_capacity(markdown.len());
+    let mut last = 0;
+    for formula in parse_markdown_math_report_with(markdown, false, delimiters) {
+        let Ok(block) = &formula.result else {
+            continue;
+        };
+        if let Some(replacement) = f(block, &markdown[formula.span.clone()]) {
+            output.push_str(&markdown[last..formula.span.start]);
+            output.push_str(&replacement);
+            last = formula.span.end;
         }
     }
-    
-    math_blocks
-}
\ No newline at end of file
+    output.push_str(&markdown[last..]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = "ok $x$\nbad $\\frac{1}{$ and\n  $y + \\foo$";
+
+    // 解析失败的公式也会返回，位置为在整个文档中的位置
+    #[test]
+    fn report_failures() {
+        let report = parse_markdown_math_report(DOCUMENT, false);
+        assert_eq!(report.len(), 3);
+        assert!(report[0].result.is_ok());
+
+        let failed = &report[1];
+        assert_eq!((failed.line, failed.column), (2, 5));
+        assert_eq!(failed.span, 11..22);
+        assert_eq!(failed.source, "\\frac{1}{");
+        assert_eq!(failed.result, Err(ParseError::UnexpectedEof { expected: vec![], span: 21..21 }));
+
+        assert_eq!((report[2].line, report[2].column), (3, 3));
+        assert!(report[2].result.is_ok());
+        // 只返回解析成功的公式
+        assert_eq!(parse_markdown_math(DOCUMENT).len(), 2);
+    }
+
+    // 严格模式下未知命令也视为失败
+    #[test]
+    fn report_strict() {
+        let report = parse_markdown_math_report(DOCUMENT, true);
+        let Err(ParseError::UnknownCommand { name, span }) = &report[2].result else {
+            panic!("expected an unknown command, found {:?}", report[2].result);
+        };
+        assert_eq!(name, "foo");
+        assert_eq!(&DOCUMENT[span.clone()], "\\foo");
+    }
+
+    // 提取的公式记录分隔符，输出 LaTeX 时使用相同的分隔符
+    #[test]
+    fn markdown_delimiters() {
+        let blocks = parse_markdown_math_with("\\(x+1\\) and\n\n```math\ny\n```", Delimiters::all());
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].delimiter, Some(markdown::Delimiter::Parenthesis));
+        assert!(!blocks[0].display_style);
+        assert_eq!(latex::block_to_latex(&blocks[0]), "\\(x + 1\\)");
+        assert_eq!(blocks[1].delimiter, Some(markdown::Delimiter::MathFence));
+        assert_eq!(latex::block_to_latex(&blocks[1]), "```math\ny\n```");
+        assert_eq!(parse_inline_math("x").unwrap().delimiter, None);
+    }
+
+    // 公式之外的文本保持不变；返回 None 的公式和解析失败的公式保留原文
+    #[test]
+    fn rewrite() {
+        let markdown = "# Title\n\nA $x^2$, `$code$` and $$\\frac{1}{$$ and $y$ — ok.\n";
+        let rewritten = rewrite_markdown_math(markdown, |block, source| {
+            assert!(source.starts_with('$'));
+            match block.expr.to_latex().as_str() {
+                "y" => None,
+                latex => Some(format!("[{}]", latex)),
+            }
+        });
+        assert_eq!(rewritten, "# Title\n\nA [x^{2}], `$code$` and $$\\frac{1}{$$ and $y$ — ok.\n");
+        assert_eq!(rewrite_markdown_math(markdown, |_, _| None), markdown);
+
+        let rewritten = rewrite_markdown_math_with("\\(a\\) $b$", Delimiters::all(), |block, _| Some(block.to_mathml()));
+        assert!(rewritten.starts_with("<math") && rewritten.contains("</math> <math"));
+    }
+}
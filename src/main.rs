@@ -9,7 +9,7 @@ fn main() {
         Ok(ast) => {
             println!("Expression: {}", expr1);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("Error parsing '{}': {}\n", expr1, e);
@@ -22,7 +22,7 @@ fn main() {
         Ok(ast) => {
             println!("Expression: {}", expr2);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("Error parsing '{}': {}\n", expr2, e);
@@ -35,7 +35,7 @@ fn main() {
         Ok(ast) => {
             println!("Expression: {}", expr3);
             println!("AST: {:#?}", ast.expr);
-            println!("LaTeX: {}\n", ast.to_string());
+            println!("LaTeX: {}\n", ast.to_latex());
         }
         Err(e) => {
             println!("Error parsing '{}': {}\n", expr3, e);
@@ -59,7 +59,7 @@ $$
     println!("Parsing Markdown text:");
     let math_blocks = parse_markdown_math(markdown);
     for (i, block) in math_blocks.iter().enumerate() {
-        println!("Formula {}: {}", i + 1, block.to_string());
+        println!("Formula {}: {}", i + 1, block);
         println!("Display style: {}", block.display_style);
         println!("---");
     }
@@ -0,0 +1,748 @@
+use crate::ast::MathBlock;
+use crate::error::ParseError;
+use std::ops::Range;
+
+// Markdown 中数学公式的定位
+//
+// 按 CommonMark 的块结构和行内结构扫描文档，跳过代码块（围栏式和缩进式）、HTML 块、
+// 链接引用定义，以及行内的代码、HTML 标签、自动链接、链接地址和反斜杠转义，
+// 只有其余正文中的分隔符才被当作公式。默认只识别 $...$ 和 $$...$$，其他分隔符见 Delimiter。
+
+// 公式的分隔符
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    // $...$
+    Dollar,
+    // $$...$$
+    DoubleDollar,
+    // \(...\)，Pandoc 和 Jupyter 导出的文档中常用
+    Parenthesis,
+    // \[...\]
+    Bracket,
+    // GitLab 的 $`...`$
+    BacktickDollar,
+    // GitLab 的 ```math 围栏代码块
+    MathFence,
+}
+
+impl Delimiter {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+
+    // 是否为块级公式的分隔符
+    pub fn display_style(self) -> bool {
+        matches!(self, Delimiter::DoubleDollar | Delimiter::Bracket | Delimiter::MathFence)
+    }
+
+    pub fn open(self) -> &'static str {
+        match self {
+            Delimiter::Dollar => "$",
+            Delimiter::DoubleDollar => "$$",
+            Delimiter::Parenthesis => "\\(",
+            Delimiter::Bracket => "\\[",
+            Delimiter::BacktickDollar => "$`",
+            Delimiter::MathFence => "```math\n",
+        }
+    }
+
+    pub fn close(self) -> &'static str {
+        match self {
+            Delimiter::Dollar => "$",
+            Delimiter::DoubleDollar => "$$",
+            Delimiter::Parenthesis => "\\)",
+            Delimiter::Bracket => "\\]",
+            Delimiter::BacktickDollar => "`$",
+            Delimiter::MathFence => "\n```",
+        }
+    }
+}
+
+// 识别的分隔符，默认只识别 $ 和 $$
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Delimiters {
+    bits: u8,
+}
+
+impl Delimiters {
+    pub fn all() -> Self {
+        Delimiters { bits: u8::MAX }
+    }
+
+    pub fn none() -> Self {
+        Delimiters { bits: 0 }
+    }
+
+    pub fn with(mut self, delimiter: Delimiter) -> Self {
+        self.bits |= delimiter.bit();
+        self
+    }
+
+    pub fn without(mut self, delimiter: Delimiter) -> Self {
+        self.bits &= !delimiter.bit();
+        self
+    }
+
+    pub fn contains(&self, delimiter: Delimiter) -> bool {
+        self.bits & delimiter.bit() != 0
+    }
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Delimiters::none().with(Delimiter::Dollar).with(Delimiter::DoubleDollar)
+    }
+}
+
+// 文档中的一个公式，位置均为在整个文档中的字节范围
+#[derive(Debug, Clone, PartialEq)]
+pub struct MathSegment {
+    // 包括分隔符在内的范围
+    pub span: Range<usize>,
+    // 分隔符之间的公式内容
+    pub content: Range<usize>,
+    pub display_style: bool,
+    pub delimiter: Delimiter,
+}
+
+impl MathSegment {
+    // 从 start 处的开始分隔符到 close 处的结束分隔符
+    fn new(delimiter: Delimiter, start: usize, close: usize) -> Self {
+        MathSegment {
+            span: start..close + delimiter.close().len(),
+            content: start + delimiter.open().len()..close,
+            display_style: delimiter.display_style(),
+            delimiter,
+        }
+    }
+
+    // 分隔符之间的公式文本。引用块中跨行的公式，各行开头的 > 标记替换为空格，
+    // 文本中的字节位置与 content 一致
+    pub fn source(&self, markdown: &str) -> String {
+        let bytes = markdown.as_bytes();
+        let line_start = markdown[..self.span.start].rfind('\n').map_or(0, |n| n + 1);
+        let (_, depth) = skip_quote_markers(bytes, line_start, self.span.start, usize::MAX);
+        let mut source = String::with_capacity(self.content.len());
+        let mut pos = self.content.start;
+        while pos < self.content.end {
+            if depth > 0 && (pos == 0 || bytes[pos - 1] == b'\n') {
+                let (text_start, _) = skip_quote_markers(bytes, pos, self.content.end, depth);
+                source.extend(std::iter::repeat_n(' ', text_start - pos));
+                pos = text_start;
+            }
+            let end = markdown[pos..self.content.end].find('\n').map_or(self.content.end, |n| pos + n + 1);
+            source.push_str(&markdown[pos..end]);
+            pos = end;
+        }
+        source
+    }
+}
+
+// 文档中一个公式的解析结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkdownFormula {
+    // 包括分隔符在内的范围
+    pub span: Range<usize>,
+    // 开始分隔符所在的行号和列号（从 1 开始，列按字符计算）
+    pub line: usize,
+    pub column: usize,
+    // 分隔符之间的原始文本，引用块中各行开头的 > 标记替换为空格，见 MathSegment::source
+    pub source: String,
+    pub display_style: bool,
+    pub delimiter: Delimiter,
+    // 解析结果，节点和错误的位置均为在整个文档中的字节范围
+    pub result: Result<MathBlock, ParseError>,
+}
+
+// 找出文档中所有以 $ 或 $$ 分隔的公式
+pub fn find_math(markdown: &str) -> Vec<MathSegment> {
+    find_math_with(markdown, Delimiters::default())
+}
+
+// 找出文档中所有以 delimiters 中的分隔符分隔的公式
+pub fn find_math_with(markdown: &str, delimiters: Delimiters) -> Vec<MathSegment> {
+    let lines = split_lines(markdown);
+    let bytes = markdown.as_bytes();
+    let mut segments = Vec::new();
+    // 当前段落的范围，段落内的行内公式可以跨行
+    let mut paragraph: Option<Range<usize>> = None;
+    // 当前列表项内容的缩进，列表项内的缩进不构成代码块
+    let mut list_indent: Option<usize> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = Line::new(markdown, lines[i].clone());
+
+        if line.is_blank() {
+            flush_paragraph(markdown, &mut paragraph, &mut segments, delimiters);
+            i += 1;
+            continue;
+        }
+
+        // 相对于所在列表项的缩进
+        let indent = match list_indent {
+            Some(base) if line.indent >= base => line.indent - base,
+            Some(_) if paragraph.is_none() && list_item_indent(&line).is_none() => {
+                list_indent = None;
+                line.indent
+            }
+            _ => line.indent,
+        };
+
+        // 缩进代码块，不能打断段落
+        if indent >= 4 && paragraph.is_none() {
+            i += 1;
+            continue;
+        }
+
+        if indent <= 3 {
+            if let Some((fence, len)) = code_fence(&line) {
+                flush_paragraph(markdown, &mut paragraph, &mut segments, delimiters);
+                let is_math = line.rest(len).split_whitespace().next() == Some("math");
+                let start = line.text_start;
+                i += 1;
+                let content_start = lines.get(i).map_or(line.range.end, |next| next.start);
+                let mut content_end = content_start;
+                let mut span_end = None;
+                // 跳到对应的结束围栏，未闭合时代码块延续到文档末尾
+                while i < lines.len() {
+                    let closing = Line::new(markdown, lines[i].clone());
+                    i += 1;
+                    if closing.indent <= 3
+                        && let Some((c, n)) = code_fence(&closing)
+                        && c == fence
+                        && n >= len
+                        && closing.rest(n).trim().is_empty()
+                    {
+                        span_end = Some(closing.range.end);
+                        break;
+                    }
+                    content_end = closing.range.end;
+                }
+                // 信息字符串为 math 的围栏代码块是块级公式
+                if is_math && delimiters.contains(Delimiter::MathFence) {
+                    segments.push(MathSegment {
+                        span: start..span_end.unwrap_or(content_end),
+                        content: content_start..content_end,
+                        display_style: true,
+                        delimiter: Delimiter::MathFence,
+                    });
+                }
+                continue;
+            }
+
+            // 独占一行开头的 $$ 或 \[ 开始块级公式，公式内可以有空行
+            let display = [Delimiter::DoubleDollar, Delimiter::Bracket]
+                .into_iter()
+                .find(|&delimiter| delimiters.contains(delimiter) && line.text().starts_with(delimiter.open()));
+            if let Some(delimiter) = display
+                && let Some(close) = find_closing(bytes, line.text_start + delimiter.open().len(), bytes.len(), delimiter)
+            {
+                flush_paragraph(markdown, &mut paragraph, &mut segments, delimiters);
+                let segment = MathSegment::new(delimiter, line.text_start, close);
+                let end = segment.span.end;
+                segments.push(segment);
+                // 结束分隔符所在行的剩余部分仍按正文处理
+                while i < lines.len() && lines[i].end < end {
+                    i += 1;
+                }
+                if i < lines.len() {
+                    if !markdown[end..lines[i].end].trim().is_empty() {
+                        paragraph = Some(end..lines[i].end);
+                    }
+                    i += 1;
+                }
+                continue;
+            }
+
+            if let Some(end) = html_block_end(&line, paragraph.is_some()) {
+                flush_paragraph(markdown, &mut paragraph, &mut segments, delimiters);
+                i = skip_html_block(markdown, &lines, i, end);
+                continue;
+            }
+
+            if paragraph.is_none() && is_link_reference_definition(line.text()) {
+                i += 1;
+                continue;
+            }
+
+            if let Some(content_indent) = list_item_indent(&line) {
+                flush_paragraph(markdown, &mut paragraph, &mut segments, delimiters);
+                list_indent = Some(line.indent + content_indent);
+            }
+        }
+
+        paragraph = match paragraph {
+            Some(range) => Some(range.start..line.range.end),
+            None => Some(line.text_start..line.range.end),
+        };
+        i += 1;
+    }
+
+    flush_paragraph(markdown, &mut paragraph, &mut segments, delimiters);
+    segments
+}
+
+// 按行切分，每行的范围不包括换行符
+fn split_lines(markdown: &str) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, b) in markdown.bytes().enumerate() {
+        if b == b'\n' {
+            let end = if i > start && markdown.as_bytes()[i - 1] == b'\r' { i - 1 } else { i };
+            lines.push(start..end);
+            start = i + 1;
+        }
+    }
+    if start < markdown.len() {
+        lines.push(start..markdown.len());
+    }
+    lines
+}
+
+struct Line<'a> {
+    source: &'a str,
+    range: Range<usize>,
+    // 去掉引用标记 > 和缩进后正文的起始位置
+    text_start: usize,
+    // 缩进的列数，制表符按 4 列计算
+    indent: usize,
+}
+
+impl<'a> Line<'a> {
+    fn new(source: &'a str, range: Range<usize>) -> Self {
+        let bytes = source.as_bytes();
+        let (mut pos, _) = skip_quote_markers(bytes, range.start, range.end, usize::MAX);
+
+        let mut indent = 0;
+        while pos < range.end {
+            match bytes[pos] {
+                b' ' => indent += 1,
+                b'\t' => indent += 4 - indent % 4,
+                _ => break,
+            }
+            pos += 1;
+        }
+
+        Line {
+            source,
+            range,
+            text_start: pos,
+            indent,
+        }
+    }
+
+    fn text(&self) -> &'a str {
+        &self.source[self.text_start..self.range.end]
+    }
+
+    // 正文中前 n 个字节之后的部分
+    fn rest(&self, n: usize) -> &'a str {
+        &self.text()[n..]
+    }
+
+    fn is_blank(&self) -> bool {
+        self.text().trim().is_empty()
+    }
+}
+
+// 跳过行首最多 max 层引用块的标记 >，返回标记之后的位置和跳过的层数
+fn skip_quote_markers(bytes: &[u8], start: usize, end: usize, max: usize) -> (usize, usize) {
+    let mut pos = start;
+    let mut depth = 0;
+    while depth < max {
+        let mut p = pos;
+        while p < end && p - pos < 3 && bytes[p] == b' ' {
+            p += 1;
+        }
+        if p < end && bytes[p] == b'>' {
+            pos = p + 1;
+            if pos < end && bytes[pos] == b' ' {
+                pos += 1;
+            }
+            depth += 1;
+        } else {
+            break;
+        }
+    }
+    (pos, depth)
+}
+
+fn flush_paragraph(
+    markdown: &str,
+    paragraph: &mut Option<Range<usize>>,
+    segments: &mut Vec<MathSegment>,
+    delimiters: Delimiters,
+) {
+    if let Some(range) = paragraph.take() {
+        scan_inline(markdown.as_bytes(), range, segments, delimiters);
+    }
+}
+
+// 围栏式代码块的开始：至少三个 ` 或 ~，返回围栏字符和长度
+fn code_fence(line: &Line) -> Option<(u8, usize)> {
+    let text = line.text().as_bytes();
+    let fence = *text.first()?;
+    if fence != b'`' && fence != b'~' {
+        return None;
+    }
+    let len = text.iter().take_while(|&&b| b == fence).count();
+    // 反引号围栏的信息字符串中不能再有反引号
+    if len < 3 || (fence == b'`' && line.rest(len).contains('`')) {
+        return None;
+    }
+    Some((fence, len))
+}
+
+// 列表项标记（- * + 或 1. 1)）之后内容的缩进
+fn list_item_indent(line: &Line) -> Option<usize> {
+    let text = line.text().as_bytes();
+    let marker = match text.first()? {
+        b'-' | b'*' | b'+' => 1,
+        b'0'..=b'9' => {
+            let digits = text.iter().take_while(|b| b.is_ascii_digit()).count();
+            match text.get(digits) {
+                Some(b'.') | Some(b')') if digits <= 9 => digits + 1,
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    let spaces = text[marker..].iter().take_while(|&&b| b == b' ').count();
+    match spaces {
+        0 if marker < text.len() => None,
+        0 => Some(marker + 1),
+        1..=4 => Some(marker + spaces),
+        // 标记后超过四个空格时，内容本身是缩进代码
+        _ => Some(marker + 1),
+    }
+}
+
+// 链接引用定义：[label]: destination
+fn is_link_reference_definition(text: &str) -> bool {
+    match text.strip_prefix('[').and_then(|rest| rest.split_once("]:")) {
+        Some((label, _)) => !label.is_empty() && !label.contains(['[', ']']),
+        None => false,
+    }
+}
+
+// HTML 块的结束条件
+enum HtmlBlockEnd {
+    // 遇到包含该字符串的行时结束（含该行）
+    Marker(&'static str),
+    // 遇到空行时结束
+    BlankLine,
+}
+
+const RAW_HTML_TAGS: [&str; 4] = ["script", "pre", "style", "textarea"];
+
+const BLOCK_HTML_TAGS: [&str; 40] = [
+    "address", "article", "aside", "blockquote", "body", "caption", "center", "dd",
+    "details", "dialog", "div", "dl", "dt", "fieldset", "figcaption", "figure", "footer",
+    "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "html", "iframe", "li",
+    "main", "nav", "ol", "p", "section", "summary", "table", "tbody", "td", "tr", "ul",
+];
+
+fn html_block_end(line: &Line, in_paragraph: bool) -> Option<HtmlBlockEnd> {
+    let text = line.text();
+    if !text.starts_with('<') {
+        return None;
+    }
+    let lower = text.to_ascii_lowercase();
+
+    let tag_name = |s: &str| -> String {
+        s.chars().take_while(|c| c.is_ascii_alphanumeric()).collect()
+    };
+
+    let name = tag_name(&lower[1..]);
+    if RAW_HTML_TAGS.contains(&name.as_str()) {
+        let end = match name.as_str() {
+            "script" => "</script>",
+            "pre" => "</pre>",
+            "style" => "</style>",
+            _ => "</textarea>",
+        };
+        return Some(HtmlBlockEnd::Marker(end));
+    }
+    if lower.starts_with("<!--") {
+        return Some(HtmlBlockEnd::Marker("-->"));
+    }
+    if lower.starts_with("<?") {
+        return Some(HtmlBlockEnd::Marker("?>"));
+    }
+    if lower.starts_with("<![cdata[") {
+        return Some(HtmlBlockEnd::Marker("]]>"));
+    }
+    if lower.starts_with("<!") && lower[2..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Some(HtmlBlockEnd::Marker(">"));
+    }
+
+    let closing = lower.starts_with("</");
+    let name = if closing { tag_name(&lower[2..]) } else { name };
+    if BLOCK_HTML_TAGS.contains(&name.as_str()) {
+        return Some(HtmlBlockEnd::BlankLine);
+    }
+
+    // 其他单独占一行的完整标签，不能打断段落
+    let trimmed = lower.trim_end();
+    if !in_paragraph && !name.is_empty() && trimmed.ends_with('>') && !trimmed[1..].contains('<') {
+        return Some(HtmlBlockEnd::BlankLine);
+    }
+    None
+}
+
+// 跳过从第 start 行开始的 HTML 块，返回其后的第一行
+fn skip_html_block(markdown: &str, lines: &[Range<usize>], start: usize, end: HtmlBlockEnd) -> usize {
+    let mut i = start;
+    while i < lines.len() {
+        let text = &markdown[lines[i].clone()];
+        match end {
+            HtmlBlockEnd::Marker(marker) => {
+                i += 1;
+                if text.to_ascii_lowercase().contains(marker) {
+                    break;
+                }
+            }
+            HtmlBlockEnd::BlankLine => {
+                if text.trim().is_empty() {
+                    break;
+                }
+                i += 1;
+            }
+        }
+    }
+    i
+}
+
+// 扫描段落中的行内公式
+fn scan_inline(bytes: &[u8], range: Range<usize>, segments: &mut Vec<MathSegment>, delimiters: Delimiters) {
+    let end = range.end;
+    let mut i = range.start;
+
+    while i < end {
+        let delimiter = match bytes[i] {
+            b'\\' if i + 1 < end && bytes[i + 1] == b'(' => Some(Delimiter::Parenthesis),
+            b'\\' if i + 1 < end && bytes[i + 1] == b'[' => Some(Delimiter::Bracket),
+            b'$' if i + 1 < end && bytes[i + 1] == b'`' && delimiters.contains(Delimiter::BacktickDollar) => {
+                Some(Delimiter::BacktickDollar)
+            }
+            b'$' if i + 1 < end && bytes[i + 1] == b'$' => Some(Delimiter::DoubleDollar),
+            // 与 Pandoc 相同，开始的 $ 之后不能是空白，如 costs $5 and $10 中的 $10 不是公式
+            b'$' if i + 1 < end && !bytes[i + 1].is_ascii_whitespace() => Some(Delimiter::Dollar),
+            _ => None,
+        };
+        if let Some(delimiter) = delimiter {
+            let open = delimiter.open().len();
+            match find_closing(bytes, i + open, end, delimiter) {
+                Some(close) if delimiters.contains(delimiter) => {
+                    segments.push(MathSegment::new(delimiter, i, close));
+                    i = close + delimiter.close().len();
+                }
+                // 未闭合时从下一个字符继续，$$ 中的第二个 $ 仍可以开始行内公式
+                None if bytes[i] == b'$' => i += 1,
+                // 未启用的分隔符按普通字符处理，\( 和 \[ 为转义
+                _ => i += open,
+            }
+            continue;
+        }
+
+        match bytes[i] {
+            // 转义的 ASCII 标点（如 \$）按普通字符处理
+            b'\\' if i + 1 < end && bytes[i + 1].is_ascii_punctuation() => i += 2,
+            b'`' => {
+                let len = run_length(bytes, i, end, b'`');
+                i = match find_backtick_run(bytes, i + len, end, len) {
+                    Some(close) => close + len,
+                    None => i + len,
+                };
+            }
+            b'<' => i = inline_html_end(bytes, i, end).unwrap_or(i + 1),
+            b']' if i + 1 < end && bytes[i + 1] == b'(' => {
+                i = link_destination_end(bytes, i + 1, end).unwrap_or(i + 1);
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+// 查找结束分隔符的位置，公式内的 \ 与其后的字符一起跳过
+// 与 Pandoc 相同，结束的 $ 之前不能是空白，之后不能是数字
+fn find_closing(bytes: &[u8], start: usize, end: usize, delimiter: Delimiter) -> Option<usize> {
+    let close = delimiter.close().as_bytes();
+    let mut i = start;
+    while i < end {
+        let valid = delimiter != Delimiter::Dollar
+            || (i > start && !bytes[i - 1].is_ascii_whitespace() && !(i + 1 < end && bytes[i + 1].is_ascii_digit()));
+        if valid && bytes[i..end].starts_with(close) {
+            return Some(i);
+        }
+        i += if bytes[i] == b'\\' { 2 } else { 1 };
+    }
+    None
+}
+
+fn run_length(bytes: &[u8], start: usize, end: usize, b: u8) -> usize {
+    bytes[start..end].iter().take_while(|&&c| c == b).count()
+}
+
+// 查找长度恰好为 len 的反引号串，作为行内代码的结束
+fn find_backtick_run(bytes: &[u8], start: usize, end: usize, len: usize) -> Option<usize> {
+    let mut i = start;
+    while i < end {
+        if bytes[i] == b'`' {
+            let run = run_length(bytes, i, end, b'`');
+            if run == len {
+                return Some(i);
+            }
+            i += run;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+// 行内 HTML 标签、注释或自动链接 <...> 的结束位置
+fn inline_html_end(bytes: &[u8], start: usize, end: usize) -> Option<usize> {
+    let next = *bytes.get(start + 1)?;
+    if !(next.is_ascii_alphabetic() || next == b'/' || next == b'!' || next == b'?') {
+        return None;
+    }
+    if bytes[start..end].starts_with(b"<!--") {
+        let close = find_subslice(bytes, start + 4, end, b"-->")?;
+        return Some(close + 3);
+    }
+
+    // 引号内的 > 不结束标签
+    let mut quote = None;
+    let mut i = start + 1;
+    while i < end {
+        match (quote, bytes[i]) {
+            (None, b'"') | (None, b'\'') => quote = Some(bytes[i]),
+            (Some(q), c) if c == q => quote = None,
+            (None, b'>') => return Some(i + 1),
+            (None, b'<') => return None,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+// 链接地址 (...) 的结束位置，start 指向左括号
+fn link_destination_end(bytes: &[u8], start: usize, end: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = start;
+    while i < end {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn find_subslice(bytes: &[u8], start: usize, end: usize, needle: &[u8]) -> Option<usize> {
+    bytes[start..end]
+        .windows(needle.len())
+        .position(|w| w == needle)
+        .map(|p| start + p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(markdown: &str, delimiters: Delimiters) -> Vec<String> {
+        find_math_with(markdown, delimiters).iter().map(|segment| segment.source(markdown)).collect()
+    }
+
+    // 代码、HTML、链接地址和转义中的 $ 不是分隔符
+    #[test]
+    fn skips_markdown_structure() {
+        let cases = [
+            "a `$x$` b $y$",
+            "```\n$x$\n```\n$y$",
+            "~~~~\n$x$\n~~~\n~~~~\n$y$",
+            "    $x$\n\n$y$",
+            "<div>\n$x$\n</div>\n\n$y$",
+            "<span title=\"$x$\">$y$</span>",
+            "[link](http://a/$x$) $y$",
+            "[l]: http://$x$\n$y$",
+            "price \\$5 and $y$",
+            "- item\n\n      $x$\n\n$y$",
+        ];
+        for markdown in cases {
+            assert_eq!(sources(markdown, Delimiters::default()), ["y"], "{:?}", markdown);
+        }
+    }
+
+    // 段落内的行内公式可以跨行，块级公式内可以有空行
+    #[test]
+    fn formula_ranges() {
+        let markdown = "text $a +\nb$\n\n$$\nx\n\ny\n$$ after";
+        let segments = find_math(markdown);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].span, 5..12);
+        assert_eq!(segments[0].content, 6..11);
+        assert!(!segments[0].display_style);
+        assert_eq!(&markdown[segments[1].content.clone()], "\nx\n\ny\n");
+        assert!(segments[1].display_style);
+    }
+
+    // 开始的 $ 之后和结束的 $ 之前不能是空白，结束的 $ 之后不能是数字
+    #[test]
+    fn dollar_amounts() {
+        let inline = |markdown: &str| sources(markdown, Delimiters::default());
+        assert!(inline("costs $5 and $10").is_empty());
+        assert!(inline("between $ 1 $ and $2").is_empty());
+        assert_eq!(inline("from $x$ to $y$, not $20"), ["x", "y"]);
+        assert_eq!(inline("$a$5 and $b$."), ["a$5 and $b"]);
+        assert_eq!(inline("$\\$$"), ["\\$"]);
+    }
+
+    // 引用块中的块级公式不包括每行开头的 > 标记，位置与原文一致
+    #[test]
+    fn blockquote_math() {
+        let markdown = "> $$\n> x +\n> y\n> $$\n\n> ```math\n> a^2\n> ```\n> > $a +\n> > b$";
+        let segments = find_math_with(markdown, Delimiters::all());
+        let quoted: Vec<String> = segments.iter().map(|segment| segment.source(markdown)).collect();
+        assert_eq!(quoted, ["\n  x +\n  y\n  ", "  a^2", "a +\n    b"]);
+        for (segment, source) in segments.iter().zip(&quoted) {
+            assert_eq!(source.len(), segment.content.len());
+        }
+        // 不在引用块中时，行首的 > 是公式的一部分
+        assert_eq!(sources("$$\na\n> b\n$$", Delimiters::default()), ["\na\n> b\n"]);
+    }
+
+    // 各种分隔符只在被启用时识别，并记录在结果中
+    #[test]
+    fn delimiter_sets() {
+        let markdown = "\\(a\\) $b$ $`c`$ \\[d\\]\n\n```math\ne\n```";
+        // 未启用 $`...`$ 时按 $...$ 处理，反引号是公式的一部分
+        assert_eq!(sources(markdown, Delimiters::default()), ["b", "`c`"]);
+        assert_eq!(sources(markdown, Delimiters::all()), ["a", "b", "c", "d", "e"]);
+        assert_eq!(sources(markdown, Delimiters::all().without(Delimiter::Dollar)), ["a", "c", "d", "e"]);
+        assert_eq!(sources(markdown, Delimiters::none().with(Delimiter::Bracket)), ["d"]);
+
+        let delimiters: Vec<(Delimiter, bool)> = find_math_with(markdown, Delimiters::all())
+            .iter()
+            .map(|segment| (segment.delimiter, segment.display_style))
+            .collect();
+        assert_eq!(delimiters, [
+            (Delimiter::Parenthesis, false),
+            (Delimiter::Dollar, false),
+            (Delimiter::BacktickDollar, false),
+            (Delimiter::Bracket, true),
+            (Delimiter::MathFence, true),
+        ]);
+    }
+}
@@ -0,0 +1,674 @@
+use crate::ast::{BinaryOperator, CdArrowDirection, DifferentialOperatorKind, Environment, MathBlock, MathExpr, RelationOperator, Symbol, SymbolKind, TextCommand, UnaryOperator};
+use crate::parser::Parser;
+
+// Presentation MathML 输出
+
+const MATHML_NS: &str = "http://www.w3.org/1998/Math/MathML";
+
+// 以 <mo> 输出的大型运算符
+const LARGE_OPERATORS: [(&str, &str); 6] = [
+    ("sum", "∑"), ("prod", "∏"), ("int", "∫"), ("iint", "∬"), ("iiint", "∭"), ("oint", "∮"),
+];
+
+pub fn to_mathml(expr: &MathExpr) -> String {
+    let mut out = String::new();
+    write_expr(&mut out, expr);
+    out
+}
+
+pub fn block_to_mathml(block: &MathBlock) -> String {
+    let display = if block.display_style { "block" } else { "inline" };
+    format!(
+        "<math xmlns=\"{}\" display=\"{}\">{}</math>",
+        MATHML_NS,
+        display,
+        to_mathml(&block.expr)
+    )
+}
+
+// 运算符的优先级，用于判断子表达式是否需要加括号
+fn binary_precedence(operator: &BinaryOperator) -> u8 {
+    match operator {
+        BinaryOperator::Equals => 0,
+        BinaryOperator::Add | BinaryOperator::Subtract => 1,
+        BinaryOperator::Multiply | BinaryOperator::DotProduct => 2,
+        // 以 <mfrac> 和 <msup> 输出，本身不需要括号
+        BinaryOperator::Divide | BinaryOperator::Power => 4,
+    }
+}
+
+fn precedence(expr: &MathExpr) -> u8 {
+    match expr.unspanned() {
+        MathExpr::BinaryOp { operator, .. } => binary_precedence(operator),
+        MathExpr::Relation { .. } => 0,
+        MathExpr::UnaryOp { operator: UnaryOperator::Factorial, .. } => 4,
+        MathExpr::UnaryOp { .. } => 3,
+        MathExpr::Number(n) if *n < 0.0 => 3,
+        // 主体向右延伸，与乘法同级
+        MathExpr::BigOperator { .. } | MathExpr::Limit { .. } | MathExpr::Integral { .. } => 2,
+        MathExpr::DifferentialOperator { .. } => 2,
+        _ => 4,
+    }
+}
+
+fn write_expr(out: &mut String, expr: &MathExpr) {
+    match expr {
+        MathExpr::Number(n) => write_number(out, *n),
+        MathExpr::Variable(name) => write_variable(out, name),
+        MathExpr::Symbol(symbol) => write_symbol(out, symbol),
+        MathExpr::BinaryOp { left, operator, right } => write_binary(out, left, operator, right),
+        MathExpr::Relation { operands, operators } => {
+            out.push_str("<mrow>");
+            write_operand(out, &operands[0], 1);
+            for (operator, operand) in operators.iter().zip(&operands[1..]) {
+                write_mo(out, relation_symbol(operator));
+                write_operand(out, operand, 1);
+            }
+            out.push_str("</mrow>");
+        }
+        MathExpr::UnaryOp { operator, expr } => {
+            out.push_str("<mrow>");
+            match operator {
+                UnaryOperator::Factorial => {
+                    write_operand(out, expr, 4);
+                    write_mo(out, "!");
+                }
+                UnaryOperator::Plus => {
+                    write_mo(out, "+");
+                    write_operand(out, expr, 3);
+                }
+                UnaryOperator::Minus => {
+                    write_mo(out, "−");
+                    write_operand(out, expr, 3);
+                }
+            }
+            out.push_str("</mrow>");
+        }
+        MathExpr::FunctionCall { name, args } => {
+            if (name == "abs" || name == "norm") && args.len() == 1 {
+                let bar = if name == "abs" { "|" } else { "‖" };
+                write_fenced(out, bar, bar, |out| write_expr(out, &args[0]));
+                return;
+            }
+            out.push_str("<mrow>");
+            write_variable(out, name);
+            write_arguments(out, args);
+            out.push_str("</mrow>");
+        }
+        MathExpr::Subscript { base, subscript } => {
+            out.push_str("<msub>");
+            write_operand(out, base, 4);
+            write_expr(out, subscript);
+            out.push_str("</msub>");
+        }
+        MathExpr::Superscript { base, superscript } => {
+            // x_i^2 合并为 <msubsup>
+            if let MathExpr::Subscript { base, subscript } = base.unspanned() {
+                out.push_str("<msubsup>");
+                write_operand(out, base, 4);
+                write_expr(out, subscript);
+                write_expr(out, superscript);
+                out.push_str("</msubsup>");
+            } else {
+                out.push_str("<msup>");
+                write_operand(out, base, 4);
+                write_expr(out, superscript);
+                out.push_str("</msup>");
+            }
+        }
+        MathExpr::Fraction { numerator, denominator } => write_fraction(out, numerator, denominator),
+        MathExpr::Root { radicand, index } => match index {
+            Some(index) => {
+                out.push_str("<mroot>");
+                write_expr(out, radicand);
+                write_expr(out, index);
+                out.push_str("</mroot>");
+            }
+            None => {
+                out.push_str("<msqrt>");
+                write_expr(out, radicand);
+                out.push_str("</msqrt>");
+            }
+        },
+        MathExpr::Parenthesized(expr) => write_fenced(out, "(", ")", |out| write_expr(out, expr)),
+        MathExpr::Matrix { rows, environment } => {
+            let (open, close) = match environment {
+                Environment::PMatrix => ("(", ")"),
+                Environment::BMatrix => ("[", "]"),
+                Environment::BMatrixCurly => ("{", "}"),
+                Environment::VMatrix => ("|", "|"),
+                Environment::VMatrixDouble => ("‖", "‖"),
+                _ => ("", ""),
+            };
+            let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+            let write_table = |out: &mut String| {
+                out.push_str("<mtable");
+                write_column_attributes(out, environment, columns);
+                out.push('>');
+                for row in rows {
+                    out.push_str("<mtr>");
+                    for cell in row {
+                        out.push_str("<mtd>");
+                        write_expr(out, cell);
+                        out.push_str("</mtd>");
+                    }
+                    out.push_str("</mtr>");
+                }
+                out.push_str("</mtable>");
+            };
+            match environment {
+                // cases 只有左侧的花括号
+                Environment::Cases => {
+                    out.push_str("<mrow>");
+                    write_mo(out, "{");
+                    write_table(out);
+                    out.push_str("</mrow>");
+                }
+                Environment::SmallMatrix => {
+                    out.push_str("<mstyle scriptlevel=\"1\">");
+                    write_table(out);
+                    out.push_str("</mstyle>");
+                }
+                _ if open.is_empty() => write_table(out),
+                _ => write_fenced(out, open, close, write_table),
+            }
+        }
+        MathExpr::Piecewise { branches } => {
+            out.push_str("<mrow>");
+            write_mo(out, "{");
+            out.push_str("<mtable columnalign=\"left left\">");
+            for (value, condition) in branches {
+                out.push_str("<mtr><mtd>");
+                write_expr(out, value);
+                out.push_str("</mtd><mtd>");
+                match condition {
+                    Some(condition) => write_expr(out, condition),
+                    None => out.push_str("<mtext>otherwise</mtext>"),
+                }
+                out.push_str("</mtd></mtr>");
+            }
+            out.push_str("</mtable></mrow>");
+        }
+        MathExpr::EquationSystem { environment, rows } => {
+            let columns = rows.iter().map(|row| row.cells.len()).max().unwrap_or(0);
+            out.push_str("<mtable");
+            write_column_attributes(out, environment, columns);
+            out.push('>');
+            for row in rows {
+                // 有 \tag 的行用 mlabeledtr，第一个单元格为编号
+                match &row.tag {
+                    Some(tag) => out.push_str(&format!("<mlabeledtr><mtd><mtext>({})</mtext></mtd>", escape(tag))),
+                    None => out.push_str("<mtr>"),
+                }
+                for cell in &row.cells {
+                    out.push_str("<mtd>");
+                    write_expr(out, cell);
+                    out.push_str("</mtd>");
+                }
+                out.push_str(if row.tag.is_some() { "</mlabeledtr>" } else { "</mtr>" });
+            }
+            out.push_str("</mtable>");
+        }
+        MathExpr::Empty => out.push_str("<mrow></mrow>"),
+        MathExpr::Text { command, text } => {
+            let element = match command {
+                TextCommand::Text => format!("<mtext>{}</mtext>", escape(text)),
+                TextCommand::Mathrm => format!("<mi mathvariant=\"normal\">{}</mi>", escape(text)),
+                TextCommand::Operatorname => format!("<mi>{}</mi>", escape(text)),
+            };
+            out.push_str(&element);
+        }
+        MathExpr::CdArrow { direction, first, second } => {
+            let first = first.as_deref().map(to_mathml);
+            let second = second.as_deref().map(to_mathml);
+            let arrow = match direction {
+                CdArrowDirection::Right => "→",
+                CdArrowDirection::Left => "←",
+                CdArrowDirection::Down => "↓",
+                CdArrowDirection::Up => "↑",
+                CdArrowDirection::Equals => "=",
+                CdArrowDirection::VerticalEquals => "‖",
+                CdArrowDirection::Blank => return out.push_str("<mrow></mrow>"),
+            };
+            match direction {
+                // 水平箭头的标注写在箭头上方和下方，竖直箭头的写在左侧和右侧
+                CdArrowDirection::Right | CdArrowDirection::Left => {
+                    write_operator(out, &format!("<mo>{}</mo>", arrow), second, first, true, |_| {});
+                }
+                _ => {
+                    out.push_str("<mrow>");
+                    out.push_str(&first.unwrap_or_default());
+                    write_mo(out, arrow);
+                    out.push_str(&second.unwrap_or_default());
+                    out.push_str("</mrow>");
+                }
+            }
+        }
+        MathExpr::Derivative { variable, order, expression } => {
+            write_derivative(out, "<mi>d</mi>", &[(variable.clone(), *order)], expression);
+        }
+        MathExpr::PartialDerivative { variables, expression } => {
+            write_derivative(out, "<mo>∂</mo>", variables, expression);
+        }
+        MathExpr::Prime { expr, order } => match expr.unspanned() {
+            // 函数调用的导数写作 f′(x)
+            MathExpr::FunctionCall { name, args } if !(matches!(name.as_str(), "abs" | "norm") && args.len() == 1) => {
+                out.push_str("<mrow>");
+                write_prime(out, *order, |out| write_variable(out, name));
+                write_arguments(out, args);
+                out.push_str("</mrow>");
+            }
+            _ => write_prime(out, *order, |out| write_operand(out, expr, 4)),
+        },
+        MathExpr::DifferentialOperator { kind, operand } => {
+            out.push_str("<mrow>");
+            match kind {
+                DifferentialOperatorKind::Gradient => write_mo(out, "∇"),
+                DifferentialOperatorKind::Divergence => {
+                    write_mo(out, "∇");
+                    write_mo(out, "⋅");
+                }
+                DifferentialOperatorKind::Curl => {
+                    write_mo(out, "∇");
+                    write_mo(out, "×");
+                }
+                DifferentialOperatorKind::Laplacian => out.push_str("<msup><mo>∇</mo><mn>2</mn></msup>"),
+            }
+            write_operand(out, operand, 3);
+            out.push_str("</mrow>");
+        }
+        MathExpr::BigOperator { kind, index, lower, upper, body } => {
+            let under = match (index, lower) {
+                (Some(index), Some(lower)) => {
+                    let mut under = String::from("<mrow>");
+                    write_variable(&mut under, index);
+                    write_mo(&mut under, "=");
+                    write_expr(&mut under, lower);
+                    under.push_str("</mrow>");
+                    Some(under)
+                }
+                (Some(index), None) => {
+                    let mut under = String::new();
+                    write_variable(&mut under, index);
+                    Some(under)
+                }
+                (None, Some(lower)) => Some(to_mathml(lower)),
+                (None, None) => None,
+            };
+            let over = upper.as_deref().map(to_mathml);
+            let mut op = String::new();
+            write_command(&mut op, &kind.command()[1..]);
+            write_operator(out, &op, under, over, true, |out| write_operand(out, body, 2));
+        }
+        MathExpr::Limit { variable, target, body } => {
+            let mut under = String::from("<mrow>");
+            write_variable(&mut under, variable);
+            write_mo(&mut under, "→");
+            write_expr(&mut under, target);
+            under.push_str("</mrow>");
+            write_operator(out, "<mo>lim</mo>", Some(under), None, true, |out| write_operand(out, body, 2));
+        }
+        MathExpr::Integral { kind, lower, upper, integrand, variables, dot_product } => {
+            let mut op = String::new();
+            write_command(&mut op, &kind.command()[1..]);
+            let lower = lower.as_deref().map(to_mathml);
+            let upper = upper.as_deref().map(to_mathml);
+            write_operator(out, &op, lower, upper, false, |out| {
+                write_operand(out, integrand, 2);
+                if *dot_product {
+                    write_mo(out, "⋅");
+                }
+                for variable in variables {
+                    out.push_str("<mrow><mi>d</mi>");
+                    write_variable(out, variable);
+                    out.push_str("</mrow>");
+                }
+            });
+        }
+        MathExpr::Spanned { expr, .. } => write_expr(out, expr),
+    }
+}
+
+// 求和、求积、极限与积分，其后为主体
+// limits 为 true 时上下限写在运算符的上方和下方，否则与积分号一样写在右侧
+fn write_operator(
+    out: &mut String,
+    op: &str,
+    under: Option<String>,
+    over: Option<String>,
+    limits: bool,
+    body: impl FnOnce(&mut String),
+) {
+    let (both, lower, upper) = if limits {
+        ("munderover", "munder", "mover")
+    } else {
+        ("msubsup", "msub", "msup")
+    };
+    out.push_str("<mrow>");
+    match (under, over) {
+        (Some(under), Some(over)) => out.push_str(&format!("<{0}>{1}{2}{3}</{0}>", both, op, under, over)),
+        (Some(under), None) => out.push_str(&format!("<{0}>{1}{2}</{0}>", lower, op, under)),
+        (None, Some(over)) => out.push_str(&format!("<{0}>{1}{2}</{0}>", upper, op, over)),
+        (None, None) => out.push_str(op),
+    }
+    body(out);
+    out.push_str("</mrow>");
+}
+
+// 表格的列对齐方式：对齐环境交替右对齐和左对齐，eqnarray 为右、中、左，cases 左对齐，
+// array 按列格式，竖线用 columnlines 表示
+fn write_column_attributes(out: &mut String, environment: &Environment, columns: usize) {
+    let (align, lines) = match environment {
+        Environment::Aligned | Environment::Align { .. } | Environment::Split => {
+            let align = (0..columns).map(|i| if i % 2 == 0 { "right" } else { "left" }).collect::<Vec<_>>();
+            (align, Vec::new())
+        }
+        Environment::EqnArray { .. } => (vec!["right", "center", "left"], Vec::new()),
+        Environment::Cases => (vec!["left"; columns.max(1)], Vec::new()),
+        Environment::Array { columns } => {
+            let mut align = Vec::new();
+            let mut lines = Vec::new();
+            let mut line = false;
+            for c in columns.chars() {
+                match c {
+                    '|' => line = true,
+                    c => {
+                        if !align.is_empty() {
+                            lines.push(if line { "solid" } else { "none" });
+                        }
+                        line = false;
+                        align.push(match c {
+                            'l' => "left",
+                            'r' => "right",
+                            _ => "center",
+                        });
+                    }
+                }
+            }
+            if !lines.contains(&"solid") {
+                lines.clear();
+            }
+            (align, lines)
+        }
+        _ => return,
+    };
+    if !align.is_empty() {
+        out.push_str(&format!(" columnalign=\"{}\"", align.join(" ")));
+    }
+    if !lines.is_empty() {
+        out.push_str(&format!(" columnlines=\"{}\"", lines.join(" ")));
+    }
+}
+
+fn write_number(out: &mut String, n: f64) {
+    if n.is_infinite() {
+        if n < 0.0 {
+            out.push_str("<mrow>");
+            write_mo(out, "−");
+            out.push_str("<mi>∞</mi></mrow>");
+        } else {
+            out.push_str("<mi>∞</mi>");
+        }
+    } else if n < 0.0 {
+        out.push_str("<mrow>");
+        write_mo(out, "−");
+        out.push_str(&format!("<mn>{}</mn></mrow>", -n));
+    } else {
+        out.push_str(&format!("<mn>{}</mn>", n));
+    }
+}
+
+fn write_variable(out: &mut String, name: &str) {
+    if let Some(command) = name.strip_prefix('\\') {
+        write_command(out, command);
+        return;
+    }
+
+    // 多个字母的标识符按 LaTeX 的习惯逐个字母输出，如 dx、mc
+    if name.len() > 1 && name.chars().all(|c| c.is_ascii_alphabetic()) {
+        out.push_str("<mrow>");
+        for c in name.chars() {
+            out.push_str(&format!("<mi>{}</mi>", c));
+        }
+        out.push_str("</mrow>");
+    } else {
+        out.push_str(&format!("<mi>{}</mi>", escape(name)));
+    }
+}
+
+fn write_command(out: &mut String, command: &str) {
+    if let Some(inner) = command.strip_prefix("vec{").and_then(|s| s.strip_suffix('}')) {
+        out.push_str("<mover accent=\"true\">");
+        write_latex_fragment(out, inner);
+        write_mo(out, "→");
+        out.push_str("</mover>");
+    } else if let Some(inner) = command.strip_prefix("mathbf{").and_then(|s| s.strip_suffix('}')) {
+        out.push_str("<mstyle mathvariant=\"bold\">");
+        write_latex_fragment(out, inner);
+        out.push_str("</mstyle>");
+    } else if let Some(symbol) = Symbol::from_command(command) {
+        write_symbol(out, &symbol);
+    } else if let Some((_, symbol)) = LARGE_OPERATORS.iter().find(|(name, _)| *name == command) {
+        out.push_str(&format!("<mo largeop=\"true\">{}</mo>", symbol));
+    } else {
+        // \sin、\log 等函数名及其他命令以正体输出
+        out.push_str(&format!("<mi>{}</mi>", escape(command)));
+    }
+}
+
+// 字母和常量以 <mi> 输出，\partial、\nabla 等以 <mo> 输出
+fn write_symbol(out: &mut String, symbol: &Symbol) {
+    match symbol.kind {
+        SymbolKind::Letter | SymbolKind::Constant => out.push_str(&format!("<mi>{}</mi>", symbol.unicode)),
+        SymbolKind::Operator => write_mo(out, &symbol.unicode.to_string()),
+    }
+}
+
+// 变量名中保存的 LaTeX 片段（如 \vec{...} 的内容），能解析时按表达式输出
+fn write_latex_fragment(out: &mut String, latex: &str) {
+    match Parser::new(latex).strict(true).parse() {
+        Ok(expr) => write_expr(out, &expr),
+        Err(_) => out.push_str(&format!("<mi>{}</mi>", escape(latex))),
+    }
+}
+
+// 导数和偏导数，d 为 <mi>d</mi> 或 <mo>∂</mo>；
+// 单个变量写在分子中，如 dy/dx，其他表达式写在分数之后的方括号中
+fn write_derivative(out: &mut String, d: &str, variables: &[(String, u32)], expression: &MathExpr) {
+    if matches!(expression.unspanned(), MathExpr::Variable(_) | MathExpr::Symbol(_)) {
+        write_derivative_fraction(out, d, variables, Some(expression));
+    } else {
+        out.push_str("<mrow>");
+        write_derivative_fraction(out, d, variables, None);
+        write_fenced(out, "[", "]", |out| write_expr(out, expression));
+        out.push_str("</mrow>");
+    }
+}
+
+fn write_derivative_fraction(out: &mut String, d: &str, variables: &[(String, u32)], operand: Option<&MathExpr>) {
+    // 高阶导数的 d 和求导变量带上阶数
+    let with_order = |out: &mut String, order: u64, base: &dyn Fn(&mut String)| {
+        if order == 1 {
+            base(out);
+        } else {
+            out.push_str("<msup>");
+            base(out);
+            write_number(out, order as f64);
+            out.push_str("</msup>");
+        }
+    };
+    let order = variables.iter().map(|(_, order)| u64::from(*order)).sum();
+    out.push_str("<mfrac>");
+    match operand {
+        Some(operand) => {
+            out.push_str("<mrow>");
+            with_order(out, order, &|out| out.push_str(d));
+            write_expr(out, operand);
+            out.push_str("</mrow>");
+        }
+        None => with_order(out, order, &|out| out.push_str(d)),
+    }
+    out.push_str("<mrow>");
+    for (variable, order) in variables {
+        out.push_str(d);
+        with_order(out, u64::from(*order), &|out| write_variable(out, variable));
+    }
+    out.push_str("</mrow></mfrac>");
+}
+
+fn write_fraction(out: &mut String, numerator: &MathExpr, denominator: &MathExpr) {
+    out.push_str("<mfrac>");
+    write_expr(out, numerator);
+    write_expr(out, denominator);
+    out.push_str("</mfrac>");
+}
+
+fn relation_symbol(operator: &RelationOperator) -> &'static str {
+    match operator {
+        RelationOperator::Equals => "=",
+        RelationOperator::NotEquals => "≠",
+        RelationOperator::Less => "<",
+        RelationOperator::LessEqual => "≤",
+        RelationOperator::Greater => ">",
+        RelationOperator::GreaterEqual => "≥",
+        RelationOperator::Approx => "≈",
+        RelationOperator::Equiv => "≡",
+        RelationOperator::Sim => "∼",
+        RelationOperator::Propto => "∝",
+    }
+}
+
+fn write_binary(out: &mut String, left: &MathExpr, operator: &BinaryOperator, right: &MathExpr) {
+    let op = match operator {
+        BinaryOperator::Divide => return write_fraction(out, left, right),
+        BinaryOperator::Power => {
+            out.push_str("<msup>");
+            write_operand(out, left, 4);
+            write_expr(out, right);
+            out.push_str("</msup>");
+            return;
+        }
+        BinaryOperator::Equals => "=",
+        BinaryOperator::Add => "+",
+        BinaryOperator::Subtract => "−",
+        BinaryOperator::DotProduct => "⋅",
+        // 数字相乘时显示乘号，其余情况使用不可见乘号 U+2062；正文之后的数字不是乘法，如 \text{if } 0 \le x
+        BinaryOperator::Multiply if starts_with_number(right) && !matches!(left.unspanned(), MathExpr::Text { .. }) => "×",
+        BinaryOperator::Multiply => "\u{2062}",
+    };
+
+    let prec = binary_precedence(operator);
+    // 求和等运算符的主体向右延伸，作为乘法的左操作数时需要加括号
+    let left_min = match left.unspanned() {
+        MathExpr::BigOperator { .. } | MathExpr::Limit { .. } | MathExpr::Integral { .. } if prec == 2 => prec + 1,
+        _ => prec,
+    };
+    out.push_str("<mrow>");
+    write_operand(out, left, left_min);
+    write_mo(out, op);
+    // 左结合运算，右侧同级的子表达式需要加括号
+    write_operand(out, right, prec + 1);
+    out.push_str("</mrow>");
+}
+
+fn starts_with_number(expr: &MathExpr) -> bool {
+    match expr.unspanned() {
+        MathExpr::Number(_) => true,
+        MathExpr::BinaryOp { left, operator, .. } => {
+            *operator != BinaryOperator::Divide && starts_with_number(left)
+        }
+        MathExpr::Subscript { base, .. } | MathExpr::Superscript { base, .. } => {
+            starts_with_number(base)
+        }
+        MathExpr::UnaryOp { operator: UnaryOperator::Factorial, expr } => starts_with_number(expr),
+        _ => false,
+    }
+}
+
+// 优先级低于 min 的子表达式加括号输出
+// 函数调用的参数列表
+fn write_arguments(out: &mut String, args: &[MathExpr]) {
+    // U+2061 FUNCTION APPLICATION
+    write_mo(out, "\u{2061}");
+    write_fenced(out, "(", ")", |out| {
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                write_mo(out, ",");
+            }
+            write_expr(out, arg);
+        }
+    });
+}
+
+// 导数符号作为上标：三阶以内写作 ′、″ 形式的撇号，更高阶写作带括号的阶数
+fn write_prime(out: &mut String, order: u32, base: impl FnOnce(&mut String)) {
+    out.push_str("<msup>");
+    base(out);
+    match order {
+        1..=3 => write_mo(out, &"′".repeat(order as usize)),
+        _ => write_fenced(out, "(", ")", |out| write_number(out, f64::from(order))),
+    }
+    out.push_str("</msup>");
+}
+
+fn write_operand(out: &mut String, expr: &MathExpr, min: u8) {
+    if precedence(expr) < min {
+        write_fenced(out, "(", ")", |out| write_expr(out, expr));
+    } else {
+        write_expr(out, expr);
+    }
+}
+
+fn write_fenced(out: &mut String, open: &str, close: &str, body: impl FnOnce(&mut String)) {
+    out.push_str("<mrow>");
+    write_mo(out, open);
+    body(out);
+    write_mo(out, close);
+    out.push_str("</mrow>");
+}
+
+fn write_mo(out: &mut String, op: &str) {
+    out.push_str(&format!("<mo>{}</mo>", escape(op)));
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_display_math, parse_inline_math};
+
+    fn mathml(source: &str) -> String {
+        to_mathml(&parse_inline_math(source).unwrap().expr)
+    }
+
+    #[test]
+    fn elements() {
+        assert_eq!(mathml("\\frac{a}{b}"), "<mfrac><mi>a</mi><mi>b</mi></mfrac>");
+        assert_eq!(mathml("\\sqrt{x}"), "<msqrt><mi>x</mi></msqrt>");
+        assert_eq!(mathml("\\sqrt[3]{x}"), "<mroot><mi>x</mi><mn>3</mn></mroot>");
+        assert_eq!(mathml("x_1"), "<msub><mi>x</mi><mn>1</mn></msub>");
+        assert_eq!(mathml("x^2"), "<msup><mi>x</mi><mn>2</mn></msup>");
+        assert_eq!(
+            mathml("\\begin{pmatrix} 1 & 2 \\end{pmatrix}"),
+            "<mrow><mo>(</mo><mtable><mtr><mtd><mn>1</mn></mtd><mtd><mn>2</mn></mtd></mtr></mtable><mo>)</mo></mrow>"
+        );
+    }
+
+    // 隐式乘法使用不可见乘号，运算符中的 < 需要转义
+    #[test]
+    fn operators() {
+        assert_eq!(mathml("2x + 1"), "<mrow><mrow><mn>2</mn><mo>\u{2062}</mo><mi>x</mi></mrow><mo>+</mo><mn>1</mn></mrow>");
+        assert_eq!(mathml("a < b"), "<mrow><mi>a</mi><mo>&lt;</mo><mi>b</mi></mrow>");
+        assert_eq!(mathml("\\alpha"), "<mi>α</mi>");
+    }
+
+    #[test]
+    fn display_attribute() {
+        let math = "<math xmlns=\"http://www.w3.org/1998/Math/MathML\"";
+        assert_eq!(parse_inline_math("x").unwrap().to_mathml(), format!("{} display=\"inline\"><mi>x</mi></math>", math));
+        assert_eq!(parse_display_math("x").unwrap().to_mathml(), format!("{} display=\"block\"><mi>x</mi></math>", math));
+    }
+}
@@ -1,61 +1,130 @@
-use crate::ast::{MathExpr, BinaryOperator, UnaryOperator};
-use crate::lexer::{Token, Lexer};
-use std::iter::Peekable;
-
-pub struct Parser<'a> {
-    tokens: Peekable<Lexer<'a>>,
+use crate::ast::{BigOperatorKind, BinaryOperator, CdArrowDirection, DifferentialOperatorKind, Environment, EquationRow, IntegralKind, MathExpr, RelationOperator, Symbol, SymbolKind, TextCommand, UnaryOperator};
+use crate::error::ParseError;
+use crate::lexer::{Lexer, SpannedToken, Token};
+use std::ops::Range;
+
+// 符号表之外的已知命令：函数名，以及没有主体时按普通命令处理的大型运算符和 \to
+const FUNCTION_COMMANDS: [&str; 20] = [
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan",
+    "sinh", "cosh", "tanh", "exp", "ln", "log", "lg", "max", "min", "det", "gcd",
+];
+const OPERATOR_COMMANDS: [&str; 8] = ["sum", "prod", "int", "iint", "iiint", "oint", "lim", "to"];
+
+// 公式组中每行的编号命令，不参与表达式的解析
+const ROW_COMMANDS: [&str; 4] = ["label", "tag", "nonumber", "notag"];
+
+pub struct Parser {
+    tokens: Vec<SpannedToken>,
+    pos: usize,
+    input: String, // \label{...} 和 \tag{...} 的内容按原文保存
+    spans: bool,  // 是否为每个节点记录位置
+    strict: bool, // 严格模式：不允许未知命令和未解析完的输入
 }
 
-impl<'a> Parser<'a> {
-    pub fn new(input: &'a str) -> Self {
+impl Parser {
+    pub fn new(input: &str) -> Self {
         Self {
-            tokens: Lexer::new(input).peekable(),
+            tokens: Lexer::new(input).spanned().collect(),
+            pos: 0,
+            input: input.to_string(),
+            spans: false,
+            strict: false,
         }
     }
 
-    pub fn parse_expression(&mut self) -> Result<MathExpr, String> {
-        self.parse_equality()
+    // 启用后，每个节点都会被 MathExpr::Spanned 包裹，记录其在输入中的字节范围
+    pub fn with_spans(mut self, enabled: bool) -> Self {
+        self.spans = enabled;
+        self
     }
 
-    // 等式: expression = expression
-    fn parse_equality(&mut self) -> Result<MathExpr, String> {
-        let mut expr = self.parse_additive()?;
+    // 严格模式下，遇到未知命令或表达式之后还有剩余标记时报错，而不是截断
+    pub fn strict(mut self, enabled: bool) -> Self {
+        self.strict = enabled;
+        self
+    }
 
-        while self.consume(&Token::Equals) {
-            let right = self.parse_additive()?;
-            expr = MathExpr::BinaryOp {
-                left: Box::new(expr),
-                operator: BinaryOperator::Equals,
-                right: Box::new(right),
-            };
+    // 解析整个输入；无论是否为严格模式，无法识别的字符都会报告为 InvalidCharacter，不会被截断丢弃
+    pub fn parse(&mut self) -> Result<MathExpr, ParseError> {
+        // \label{eq:1}、\text{x's} 这样的内容按原文保存，其中可以出现任意字符；交换图中的 @. 为空箭头，
+        // 单元格末尾的句号为标点，如分段函数中的 x < 0.
+        let mut depth = 0;
+        for (i, t) in self.tokens.iter().enumerate().skip(self.pos) {
+            match &t.token {
+                Token::LCurly if depth > 0 => depth += 1,
+                Token::LCurly
+                    if i >= 2
+                        && self.tokens[i - 2].token == Token::Backslash
+                        && matches!(&self.tokens[i - 1].token, Token::Identifier(name) if is_raw_text_command(name)) =>
+                {
+                    depth = 1;
+                }
+                Token::RCurly if depth > 0 => depth -= 1,
+                Token::Unknown(text) if text == "." && i >= 1 && self.tokens[i - 1].token == Token::At => {}
+                Token::Unknown(text)
+                    if text == "."
+                        && matches!(self.tokens.get(i + 1).map(|t| &t.token), Some(Token::Ampersand | Token::DoubleBackslash | Token::End)) => {}
+                Token::Unknown(text) if depth == 0 => {
+                    return Err(ParseError::InvalidCharacter {
+                        text: text.clone(),
+                        span: t.span.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        let expr = self.parse_expression()?;
+
+        if self.strict && self.peek().is_some() {
+            return Err(self.error(&[]));
         }
 
         Ok(expr)
     }
 
+    pub fn parse_expression(&mut self) -> Result<MathExpr, ParseError> {
+        self.parse_equality()
+    }
+
+    // 等式与关系: additive ( (= | < | \le | ...) additive )*
+    fn parse_equality(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
+        let mut operands = vec![self.parse_additive()?];
+        let mut operators = Vec::new();
+
+        while let Some(operator) = self.consume_relation() {
+            operators.push(operator);
+            operands.push(self.parse_additive()?);
+        }
+
+        Ok(self.relation(start, operands, operators))
+    }
+
     // 加减法: additive ( (+ | -) additive )*
-    fn parse_additive(&mut self) -> Result<MathExpr, String> {
+    fn parse_additive(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
         let mut expr = self.parse_multiplicative()?;
 
-        while let Some(token) = self.tokens.peek() {
+        while let Some(token) = self.peek() {
             match token {
                 Token::Plus => {
-                    self.tokens.next();
+                    self.advance();
                     let right = self.parse_multiplicative()?;
-                    expr = MathExpr::BinaryOp {
+                    expr = self.spanned(start, MathExpr::BinaryOp {
                         left: Box::new(expr),
                         operator: BinaryOperator::Add,
                         right: Box::new(right),
-                    };
+                    });
                 }
                 Token::Minus => {
-                    self.tokens.next();
+                    self.advance();
                     let right = self.parse_multiplicative()?;
-                    expr = MathExpr::BinaryOp {
+                    expr = self.spanned(start, MathExpr::BinaryOp {
                         left: Box::new(expr),
                         operator: BinaryOperator::Subtract,
                         right: Box::new(right),
-                    };
+                    });
                 }
                 _ => break,
             }
@@ -64,48 +133,40 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    // 乘除法: multiplicative ( (* | / | \cdot) multiplicative )*
-    fn parse_multiplicative(&mut self) -> Result<MathExpr, String> {
+    // 乘除法: multiplicative ( (* | \times | / | \cdot) multiplicative )*
+    fn parse_multiplicative(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
         let mut expr = self.parse_power()?;
 
-        while let Some(token) = self.tokens.peek() {
+        while let Some(token) = self.peek() {
             match token {
-                Token::Asterisk => {
-                    self.tokens.next();
+                Token::Asterisk | Token::Times => {
+                    self.advance();
                     let right = self.parse_power()?;
-                    expr = MathExpr::BinaryOp {
+                    expr = self.spanned(start, MathExpr::BinaryOp {
                         left: Box::new(expr),
                         operator: BinaryOperator::Multiply,
                         right: Box::new(right),
-                    };
+                    });
                 }
                 Token::Slash => {
-                    self.tokens.next();
-                    let right = self.parse_power()?;
-                    // 特殊处理导数运算符 \frac{d}{dx}
-                    if let MathExpr::Variable(ref left) = expr {
-                        if left == "d" {
-                            if let MathExpr::Variable(ref right_var) = right {
-                                if right_var.starts_with("d") && right_var.len() > 1 {
-                                    let variable = &right_var[1..];
-                                    return Ok(MathExpr::Variable(format!("\\frac{{d}}{{d{}}}", variable)));
-                                }
-                            }
-                        }
+                    self.advance();
+                    // 算子形式的导数 d/dx f
+                    if let Some(derivative) = self.parse_slash_derivative(&expr, start, false)? {
+                        expr = derivative;
+                        continue;
                     }
-                    expr = MathExpr::Fraction {
-                        numerator: Box::new(expr),
-                        denominator: Box::new(right),
-                    };
+                    let right = self.parse_power()?;
+                    expr = self.slash_fraction(start, expr, right);
                 }
                 Token::CDot => {
-                    self.tokens.next();
+                    self.advance();
                     let right = self.parse_power()?;
-                    expr = MathExpr::BinaryOp {
+                    expr = self.spanned(start, MathExpr::BinaryOp {
                         left: Box::new(expr),
                         operator: BinaryOperator::DotProduct,
                         right: Box::new(right),
-                    };
+                    });
                 }
                 _ => break,
             }
@@ -115,48 +176,50 @@ impl<'a> Parser<'a> {
     }
 
     // 幂运算: power (^ power)*
-    fn parse_power(&mut self) -> Result<MathExpr, String> {
+    fn parse_power(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
         let mut expr = self.parse_factor()?;
 
         while self.consume(&Token::Caret) {
             let right = self.parse_unary()?;  // 使用parse_unary而不是parse_power来避免无限递归
-            expr = MathExpr::BinaryOp {
+            expr = self.spanned(start, MathExpr::BinaryOp {
                 left: Box::new(expr),
                 operator: BinaryOperator::Power,
                 right: Box::new(right),
-            };
+            });
         }
 
         Ok(expr)
     }
 
     // 一元运算: (+ | - | !) unary | factor
-    fn parse_unary(&mut self) -> Result<MathExpr, String> {
-        if let Some(token) = self.tokens.peek() {
+    fn parse_unary(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
+        if let Some(token) = self.peek() {
             match token {
                 Token::Plus => {
-                    self.tokens.next();
+                    self.advance();
                     let expr = self.parse_unary()?;
-                    return Ok(MathExpr::UnaryOp {
+                    return Ok(self.spanned(start, MathExpr::UnaryOp {
                         operator: UnaryOperator::Plus,
                         expr: Box::new(expr),
-                    });
+                    }));
                 }
                 Token::Minus => {
-                    self.tokens.next();
+                    self.advance();
                     let expr = self.parse_unary()?;
-                    return Ok(MathExpr::UnaryOp {
+                    return Ok(self.spanned(start, MathExpr::UnaryOp {
                         operator: UnaryOperator::Minus,
                         expr: Box::new(expr),
-                    });
+                    }));
                 }
                 Token::Exclamation => {
-                    self.tokens.next();
+                    self.advance();
                     let expr = self.parse_unary()?;
-                    return Ok(MathExpr::UnaryOp {
+                    return Ok(self.spanned(start, MathExpr::UnaryOp {
                         operator: UnaryOperator::Factorial,
                         expr: Box::new(expr),
-                    });
+                    }));
                 }
                 _ => {}
             }
@@ -166,15 +229,12 @@ impl<'a> Parser<'a> {
     }
 
     // 因子: primary (primary)* 用于处理连续表达式，如隐式乘法
-    fn parse_factor(&mut self) -> Result<MathExpr, String> {
+    fn parse_factor(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
         let mut expr = self.parse_primary()?;
 
         // 处理连续的表达式（隐式乘法）
-        loop {
-            let peeked = match self.tokens.peek() {
-                Some(t) => t,
-                None => break,
-            };
+        while let Some(peeked) = self.peek() {
 
             // 只有当接下来是明确可构成乘法的元素时才继续
             // 注意：Pipe符号可以触发隐式乘法，因为它可能是另一个绝对值的开始
@@ -182,48 +242,52 @@ impl<'a> Parser<'a> {
                 Token::Number(_) | Token::Identifier(_) | Token::LParen | Token::Backslash | Token::LBracket | Token::Pipe => {}
                 _ => break,
             }
+            if self.at_right_delimiter() || self.at_row_command() || self.peek_relation().is_some() {
+                break;
+            }
 
             // 特别注意：如果当前expr已经是Superscript或Subscript，需要防止错误连接
             // 但我们仍然允许如 sin^2 x 这样的隐式乘法
 
             let right = self.parse_primary()?;
-            expr = MathExpr::BinaryOp {
+            expr = self.spanned(start, MathExpr::BinaryOp {
                 left: Box::new(expr),
                 operator: BinaryOperator::Multiply,
                 right: Box::new(right),
-            };
+            });
         }
 
         Ok(expr)
     }
 
     // 基本元素: number | identifier | function | subscript | superscript | parentheses | absolute value
-    fn parse_primary(&mut self) -> Result<MathExpr, String> {
+    fn parse_primary(&mut self) -> Result<MathExpr, ParseError> {
+        let (token, span) = self.next_token(&[])?;
+        let start = span.start;
         let mut expr = {
-            let token = self.tokens.next().ok_or("Unexpected end of input")?;
 
             match token {
-                Token::Number(n) => MathExpr::Number(n),
+                Token::Number(n) => self.spanned(start, MathExpr::Number(n)),
                 Token::Identifier(name) => {
-                    // 检查是否是函数调用或者带导数符号的变量
-                    if self.consume(&Token::LParen) {
+                    // 检查是否带导数符号 f'、f^{(4)}，以及是否是函数调用 f(x)、f'(x)
+                    let order = self.consume_derivative_order();
+                    let expr = if self.consume(&Token::LParen) {
                         self.parse_function_call(name)?
-                    } else if self.consume(&Token::Prime) {
-                        // 处理导数符号 f'
-                        MathExpr::Variable(format!("{}'", name))
                     } else {
                         MathExpr::Variable(name)
-                    }
+                    };
+                    let expr = self.spanned(start, expr);
+                    self.primed(start, expr, order)
                 }
                 Token::LParen => {
                     let expr = self.parse_expression()?;
                     self.expect(&Token::RParen)?;
-                    MathExpr::Parenthesized(Box::new(expr))
+                    self.spanned(start, MathExpr::Parenthesized(Box::new(expr)))
                 }
                 Token::LBracket => {
                     let expr = self.parse_expression()?;
                     self.expect(&Token::RBracket)?;
-                    MathExpr::Parenthesized(Box::new(expr))
+                    self.spanned(start, MathExpr::Parenthesized(Box::new(expr)))
                 }
                 Token::Pipe => {
                     // 处理绝对值 |...|
@@ -234,45 +298,62 @@ impl<'a> Parser<'a> {
                     return self.parse_matrix_environment();
                 }
                 Token::Backslash => {
-                    self.parse_command()?
+                    let expr = self.parse_command()?;
+                    // 求和、求积、极限、积分、导数与微分算子的主体已延伸到右侧，之后不再接上下标和后缀
+                    if matches!(
+                        expr.unspanned(),
+                        MathExpr::BigOperator { .. }
+                            | MathExpr::Limit { .. }
+                            | MathExpr::Integral { .. }
+                            | MathExpr::Derivative { .. }
+                            | MathExpr::PartialDerivative { .. }
+                            | MathExpr::DifferentialOperator { .. }
+                    ) {
+                        return Ok(expr);
+                    }
+                    expr
                 }
-                Token::Minus => {
-                    // 处理负号
+                Token::Minus | Token::Plus => {
+                    // 处理负号，以及 x^{+y} 中的正号
+                    let operator = if token == Token::Minus { UnaryOperator::Minus } else { UnaryOperator::Plus };
                     let expr = self.parse_unary()?;
-                    return Ok(MathExpr::UnaryOp {
-                        operator: UnaryOperator::Minus,
-                        expr: Box::new(expr),
-                    });
+                    return Ok(self.spanned(start, MathExpr::UnaryOp { operator, expr: Box::new(expr) }));
                 }
-                _ => return Err(format!("Unexpected token: {:?}", token)),
+                _ => return Err(ParseError::UnexpectedToken { expected: vec![], found: token, span }),
             }
         };
 
-        // 处理连续的下标和上标
+        // 处理连续的下标、上标和导数符号
         loop {
             if self.consume(&Token::Underscore) {
-                expr = self.parse_subscript(expr)?;
+                expr = self.parse_subscript(expr, start)?;
             } else if self.consume(&Token::Caret) {
-                expr = self.parse_superscript(expr)?;
+                expr = self.parse_superscript(expr, start)?;
+            } else if self.check(&Token::Prime) {
+                let order = self.consume_primes();
+                expr = self.primed(start, expr, order);
             } else {
                 break;
             }
         }
 
-        // 处理导数符号
-        if self.consume(&Token::Prime) {
-            expr = MathExpr::Variable(format!("{}'", expr.to_string()));
+        // 处理后缀阶乘 n!
+        while self.consume(&Token::Exclamation) {
+            expr = self.spanned(start, MathExpr::UnaryOp {
+                operator: UnaryOperator::Factorial,
+                expr: Box::new(expr),
+            });
         }
 
         Ok(expr)
     }
 
-    fn parse_function_call(&mut self, name: String) -> Result<MathExpr, String> {
+    fn parse_function_call(&mut self, name: String) -> Result<MathExpr, ParseError> {
         let args = self.parse_function_call_args()?;
         Ok(MathExpr::FunctionCall { name, args })
     }
 
-    fn parse_function_call_args(&mut self) -> Result<Vec<MathExpr>, String> {
+    fn parse_function_call_args(&mut self) -> Result<Vec<MathExpr>, ParseError> {
         let mut args = Vec::new();
 
         if !self.check(&Token::RParen) {
@@ -290,92 +371,121 @@ impl<'a> Parser<'a> {
         Ok(args)
     }
 
-    fn parse_subscript(&mut self, base: MathExpr) -> Result<MathExpr, String> {
+    // 不带花括号的上下标参数只取一个元素：可带正负号的数字、字母、命令，或括号、绝对值整体，
+    // 其后的撇号和阶乘不属于参数；连续的同类上下标向右结合，如 x^2^3 为 x^{2^{3}}
+    fn parse_script_atom(&mut self, superscript: bool) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
+        let operator = match self.peek() {
+            Some(Token::Minus) => Some(UnaryOperator::Minus),
+            Some(Token::Plus) => Some(UnaryOperator::Plus),
+            _ => None,
+        };
+        if let Some(operator) = operator {
+            self.advance();
+            let expr = self.parse_script_atom(superscript)?;
+            return Ok(self.spanned(start, MathExpr::UnaryOp { operator, expr: Box::new(expr) }));
+        }
+
+        let mut expr = if self.consume(&Token::LParen) {
+            let expr = self.parse_expression()?;
+            self.expect(&Token::RParen)?;
+            self.spanned(start, MathExpr::Parenthesized(Box::new(expr)))
+        } else if self.consume(&Token::LBracket) {
+            let expr = self.parse_expression()?;
+            self.expect(&Token::RBracket)?;
+            self.spanned(start, MathExpr::Parenthesized(Box::new(expr)))
+        } else if self.consume(&Token::Pipe) {
+            self.parse_absolute_value()?
+        } else {
+            self.parse_script_argument()?
+        };
+        if superscript && self.consume(&Token::Caret) {
+            expr = self.parse_superscript(expr, start)?;
+        } else if !superscript && self.consume(&Token::Underscore) {
+            expr = self.parse_subscript(expr, start)?;
+        }
+        Ok(expr)
+    }
+
+    fn parse_subscript(&mut self, base: MathExpr, start: usize) -> Result<MathExpr, ParseError> {
         // 支持两种形式：带花括号 x_{sub} 和不带花括号 x_sub
         let subscript = if self.check(&Token::LCurly) {
-            self.tokens.next(); // 消耗 {
+            self.advance(); // 消耗 {
             let expr = self.parse_expression()?;
             self.expect(&Token::RCurly)?;
             expr
         } else {
-            // 不带花括号的简写形式
-            self.parse_unary()?
+            // 不带花括号的简写形式只取一个元素，如 x^2 y 为 x^{2} y
+            self.parse_script_atom(false)?
         };
         
-        Ok(MathExpr::Subscript {
+        Ok(self.spanned(start, MathExpr::Subscript {
             base: Box::new(base),
             subscript: Box::new(subscript),
-        })
+        }))
     }
 
-    fn parse_superscript(&mut self, base: MathExpr) -> Result<MathExpr, String> {
+    fn parse_superscript(&mut self, base: MathExpr, start: usize) -> Result<MathExpr, ParseError> {
         // 支持两种形式：带花括号 x^{sup} 和不带花括号 x^sup
         let superscript = if self.check(&Token::LCurly) {
-            self.tokens.next(); // 消耗 {
+            self.advance(); // 消耗 {
             let expr = self.parse_expression()?;
             self.expect(&Token::RCurly)?;
             expr
         } else {
-            // 不带花括号的简写形式
-            self.parse_unary()?
+            // 不带花括号的简写形式只取一个元素，如 x^2 y 为 x^{2} y
+            self.parse_script_atom(true)?
         };
         
-        Ok(MathExpr::Superscript {
+        Ok(self.spanned(start, MathExpr::Superscript {
             base: Box::new(base),
             superscript: Box::new(superscript),
-        })
+        }))
     }
 
-    fn parse_command(&mut self) -> Result<MathExpr, String> {
-        let token = self.tokens.next().ok_or("Expected command name after backslash")?;
+    fn parse_command(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.last_start();
+        let (token, span) = self.next_token(&[Token::Identifier(String::new())])?;
         
         if let Token::Begin = token {
             return self.parse_matrix_environment();
         }
+
+        // \le 等关系运算符和 \label 等编号命令不能作为操作数
+        if let Token::Identifier(ref cmd) = token
+            && (RelationOperator::from_command(cmd).is_some() || ROW_COMMANDS.contains(&cmd.as_str()))
+        {
+            return Err(ParseError::UnexpectedToken { expected: vec![], found: token, span });
+        }
         
-        if let Token::Identifier(ref cmd) = token {
-            if cmd == "frac" {
-                self.expect(&Token::LCurly)?;
-                let numerator = self.parse_expression()?;
-                self.expect(&Token::RCurly)?;
-                self.expect(&Token::LCurly)?;
-                let denominator = self.parse_expression()?;
-                self.expect(&Token::RCurly)?;
-                
-                // 特殊处理导数运算符 \frac{d}{dx}
-                if let MathExpr::Variable(ref num) = numerator {
-                    if num == "d" {
-                        if let MathExpr::Variable(ref den) = denominator {
-                            if den.starts_with("d") && den.len() > 1 {
-                                // 这是一个导数运算符，检查后面是否有方括号表达式
-                                let variable = den[1..].to_string();
-                                // 检查后面是否跟着方括号表达式
-                                if let Some(Token::LBracket) = self.tokens.peek() {
-                                    self.tokens.next(); // 消耗 [
-                                    let inner_expr = self.parse_expression()?;
-                                    self.expect(&Token::RBracket)?; // 消耗 ]
-                                    
-                                    return Ok(MathExpr::Derivative {
-                                        variable,
-                                        expression: Box::new(inner_expr),
-                                    });
-                                } else {
-                                    // 没有方括号，返回导数运算符
-                                    return Ok(MathExpr::Variable(format!("\\frac{{d}}{{d{}}}", variable)));
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                return Ok(MathExpr::Fraction {
-                    numerator: Box::new(numerator),
-                    denominator: Box::new(denominator),
-                });
+        if let Token::Identifier(ref cmd) = token
+            && cmd == "frac"
+        {
+            self.expect(&Token::LCurly)?;
+            let numerator = self.parse_expression()?;
+            self.expect(&Token::RCurly)?;
+            self.expect(&Token::LCurly)?;
+            let denominator = self.parse_expression()?;
+            self.expect(&Token::RCurly)?;
+
+            // 导数 \frac{d}{dx}[...]、\frac{df}{dx}、\frac{d^2 y}{dx^2}、\frac{\partial f}{\partial x} 等
+            if let Some(derivative) = self.parse_derivative(&numerator, &denominator, start, false)? {
+                return Ok(derivative);
             }
+
+            return Ok(self.spanned(start, MathExpr::Fraction {
+                numerator: Box::new(numerator),
+                denominator: Box::new(denominator),
+            }));
+        }
+
+        if let Token::Identifier(ref cmd) = token
+            && let Some(expr) = self.parse_big_operator(cmd, start, false)?
+        {
+            return Ok(expr);
         }
         
-        let mut expr = if let Token::Identifier(cmd) = token {
+        let expr = if let Token::Identifier(cmd) = token {
             match cmd.as_str() {
                 "sqrt" => {
                     if self.consume(&Token::LBracket) {
@@ -405,19 +515,35 @@ impl<'a> Parser<'a> {
                     self.expect(&Token::LCurly)?;
                     let expr = self.parse_expression()?;
                     self.expect(&Token::RCurly)?;
-                    MathExpr::Variable(format!("\\vec{{{}}}", expr.to_string()))
+                    MathExpr::Variable(format!("\\vec{{{}}}", expr.to_latex()))
+                }
+                "mathbf" => {
+                    // 粗体的向量，与 \vec{...} 相同按变量处理，如 \mathbf{F} \cdot d\mathbf{r}
+                    self.expect(&Token::LCurly)?;
+                    let expr = self.parse_expression()?;
+                    self.expect(&Token::RCurly)?;
+                    MathExpr::Variable(format!("\\mathbf{{{}}}", expr.to_latex()))
                 }
                 "int" => {
-                    // 处理积分符号
+                    // 没有被积函数的积分符号
                     MathExpr::Variable("\\int".to_string())
                 }
+                "mathrm" => {
+                    // 正体只影响排版，\mathrm{d} 按变量 d 处理
+                    self.parse_mathrm()?
+                }
+                // 正文按原文保存，其中可以出现任意字符
+                "text" => MathExpr::Text { command: TextCommand::Text, text: self.parse_raw_argument()? },
+                "operatorname" => MathExpr::Text { command: TextCommand::Operatorname, text: self.parse_text_argument()? },
                 "left" => {
-                    // 处理 \left 命令
-                    // 简单跳过，因为我们主要关注数学表达式的结构
-                    if let Some(next_token) = self.tokens.next() {
+                    // 处理 \left( ... \right) 和 \left[ ... \right]，其他定界符简单跳过
+                    if let Some(next_token) = self.advance() {
                         match next_token {
-                            Token::LBracket => MathExpr::Variable("[".to_string()),
-                            Token::LParen => MathExpr::Variable("(".to_string()),
+                            Token::LBracket | Token::LParen => {
+                                let inner = self.parse_expression()?;
+                                self.expect_right_delimiter()?;
+                                MathExpr::Parenthesized(Box::new(inner))
+                            }
                             _ => MathExpr::Variable(format!("\\left{}", 
                                 match next_token {
                                     Token::Identifier(s) => s,
@@ -430,46 +556,55 @@ impl<'a> Parser<'a> {
                     }
                 }
                 "right" => {
-                    // 处理 \right 命令
-                    // 简单跳过，因为我们主要关注数学表达式的结构
-                    if let Some(next_token) = self.tokens.next() {
-                        match next_token {
-                            Token::RBracket => MathExpr::Variable("]".to_string()),
-                            Token::RParen => MathExpr::Variable(")".to_string()),
-                            _ => MathExpr::Variable(format!("\\right{}", 
-                                match next_token {
-                                    Token::Identifier(s) => s,
-                                    _ => format!("{:?}", next_token)
-                                }
-                            ))
-                        }
-                    } else {
-                        MathExpr::Variable("\\right".to_string())
-                    }
+                    // \right 只能出现在对应的 \left 之后，由 expect_right_delimiter 处理
+                    return Err(ParseError::UnexpectedToken {
+                        expected: Vec::new(),
+                        found: Token::Identifier(cmd),
+                        span,
+                    });
                 }
                 _ => {
-                    // 检查是否是函数调用
+                    if self.strict && !is_known_command(&cmd) {
+                        return Err(ParseError::UnknownCommand { name: cmd, span: start..span.end });
+                    }
+                    // 检查是否是函数调用，导数符号写在参数之前 \sin'(x)
+                    let order = self.consume_derivative_order();
                     if self.consume(&Token::LParen) {
                         let args = self.parse_function_call_args()?;
-                        return Ok(MathExpr::FunctionCall {
+                        let call = self.spanned(start, MathExpr::FunctionCall {
                             name: format!("\\{}", cmd),
                             args,
                         });
+                        return Ok(self.primed(start, call, order));
+                    } else if order > 0 {
+                        let operand = match Symbol::from_command(&cmd) {
+                            Some(symbol) => MathExpr::Symbol(symbol),
+                            None => MathExpr::Variable(format!("\\{}", cmd)),
+                        };
+                        let operand = self.spanned(start, operand);
+                        MathExpr::Prime { expr: Box::new(operand), order }
+                    } else if let Some(symbol) = Symbol::from_command(&cmd) {
+                        MathExpr::Symbol(symbol)
                     } else {
                         MathExpr::Variable(format!("\\{}", cmd))
                     }
                 }
             }
         } else {
-            return Err("Expected command name after backslash".to_string());
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![Token::Identifier(String::new())],
+                found: token,
+                span,
+            });
         };
+        let mut expr = self.spanned(start, expr);
 
         // 处理命令后的下标和上标
         loop {
             if self.consume(&Token::Underscore) {
-                expr = self.parse_subscript(expr)?;
+                expr = self.parse_subscript(expr, start)?;
             } else if self.consume(&Token::Caret) {
-                expr = self.parse_superscript(expr)?;
+                expr = self.parse_superscript(expr, start)?;
             } else {
                 break;
             }
@@ -478,118 +613,416 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
 
-    // 解析矩阵环境
-    fn parse_matrix_environment(&mut self) -> Result<MathExpr, String> {
+    // 解析 \begin{...} ... \end{...} 环境：矩阵、array、cases、对齐环境和交换图
+    fn parse_matrix_environment(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.last_start();
         self.expect(&Token::LCurly)?;
-        
-        // 获取矩阵类型
-        let matrix_type_token = self.tokens.next().ok_or("Expected matrix type")?;
-        let matrix_type = match matrix_type_token {
-            Token::Matrix => "matrix",
-            Token::PMatrix => "pmatrix",
-            Token::BMatrix => "bmatrix",
-            Token::VMatrix => "vmatrix",
-            Token::VMatrixDouble => "Vmatrix",
-            _ => return Err("Expected matrix type".to_string()),
-        };
-        
+        let mut environment = self.parse_environment_name()?;
         self.expect(&Token::RCurly)?;
-        
-        // 解析矩阵内容
+
+        // cases 解析为分段函数，对齐环境解析为公式组
+        if environment == Environment::Cases {
+            return self.parse_piecewise(start);
+        }
+        if environment.is_equation_system() {
+            return self.parse_equation_system(start, environment);
+        }
+
+        // array 的列格式，如 {c|c}
+        if let Environment::Array { columns } = &mut environment {
+            *columns = self.parse_column_spec()?;
+        }
+
         let mut rows: Vec<Vec<MathExpr>> = Vec::new();
         let mut current_row: Vec<MathExpr> = Vec::new();
-        
+
         loop {
-            // 检查是否是结束标记
-            if let Some(Token::End) = self.tokens.peek() {
-                // 消耗结束标记
-                self.tokens.next(); // end
-                self.expect(&Token::LCurly)?;
-                
-                // 检查结束标记类型是否匹配
-                let end_type_token = self.tokens.next().ok_or("Expected matrix type in end command")?;
-                let end_type = match end_type_token {
-                    Token::Matrix => "matrix",
-                    Token::PMatrix => "pmatrix",
-                    Token::BMatrix => "bmatrix",
-                    Token::VMatrix => "vmatrix",
-                    Token::VMatrixDouble => "Vmatrix",
-                    _ => return Err("Expected matrix type in end command".to_string()),
-                };
-                
-                if end_type != matrix_type {
-                    return Err(format!("Mismatched matrix environment: expected {}, found {}", matrix_type, end_type));
+            // 最后一行以 \\ 结尾时不产生空行
+            if self.check(&Token::End) && current_row.is_empty() {
+                break;
+            }
+
+            if environment == Environment::CD {
+                current_row = self.parse_cd_row()?;
+            } else {
+                current_row.push(self.parse_cell()?);
+            }
+
+            match self.peek() {
+                // & 结束当前单元格，继续同一行的下一个元素
+                Some(Token::Ampersand) if environment != Environment::CD => {
+                    self.advance();
                 }
-                
-                self.expect(&Token::RCurly)?;
-                
-                // 将最后一行添加到矩阵中（如果有）
-                if !current_row.is_empty() {
-                    rows.push(current_row);
+                Some(Token::DoubleBackslash) => {
+                    self.advance();
+                    rows.push(std::mem::take(&mut current_row));
                 }
-                
-                return Ok(MathExpr::Matrix { 
-                    rows,
-                    matrix_type: matrix_type.to_string()
-                });
+                Some(Token::End) => {
+                    rows.push(std::mem::take(&mut current_row));
+                    break;
+                }
+                _ => return Err(self.error(&[Token::Ampersand, Token::DoubleBackslash, Token::End])),
             }
-            
-            // 解析矩阵元素
-            let expr = self.parse_expression()?;
-            current_row.push(expr);
-            
-            // 检查下一个符号
-            match self.tokens.peek() {
+        }
+
+        self.parse_environment_end(&environment)?;
+        Ok(self.spanned(start, MathExpr::Matrix { rows, environment }))
+    }
+
+    // 解析 \end{...}，并检查结束标记与开始标记是否匹配
+    fn parse_environment_end(&mut self, environment: &Environment) -> Result<(), ParseError> {
+        self.expect(&Token::End)?;
+        self.expect(&Token::LCurly)?;
+        let end_span = self.current_span();
+        let end = self.parse_environment_name()?;
+        if end.name() != environment.name() {
+            return Err(ParseError::MismatchedEnvironment {
+                expected: environment.name().to_string(),
+                found: end.name().to_string(),
+                span: end_span.start..self.current_span().start,
+            });
+        }
+        self.expect(&Token::RCurly)
+    }
+
+    // 公式组: 每行按 & 分为若干列，行中可以有 \label{...}、\tag{...} 和 \nonumber
+    fn parse_equation_system(&mut self, start: usize, environment: Environment) -> Result<MathExpr, ParseError> {
+        let mut rows = Vec::new();
+        let mut row = EquationRow::default();
+        loop {
+            self.parse_row_commands(&mut row)?;
+            // 最后一行以 \\ 结尾时不产生空行
+            if self.check(&Token::End) && row == EquationRow::default() {
+                break;
+            }
+            row.cells.push(self.parse_cell()?);
+            self.parse_row_commands(&mut row)?;
+
+            match self.peek() {
                 Some(Token::Ampersand) => {
-                    // 遇到 & 符号，表示当前单元格结束，继续同一行的下一个元素
-                    self.tokens.next(); // 消耗 &
+                    self.advance();
                 }
                 Some(Token::DoubleBackslash) => {
-                    // 遇到 \\ 符号，表示当前行结束
-                    self.tokens.next(); // 消耗 \\
-                    rows.push(current_row);
-                    current_row = Vec::new();
+                    self.advance();
+                    rows.push(std::mem::take(&mut row));
                 }
                 Some(Token::End) => {
-                    // 可能是结束标记，继续循环让上面的检查处理
-                    continue;
+                    rows.push(row);
+                    break;
                 }
-                _ => {
-                    // 其他情况，继续解析
+                _ => return Err(self.error(&[Token::Ampersand, Token::DoubleBackslash, Token::End])),
+            }
+        }
+
+        self.parse_environment_end(&environment)?;
+        Ok(self.spanned(start, MathExpr::EquationSystem { environment, rows }))
+    }
+
+    // 解析 \label{...}、\tag{...}、\nonumber 和 \notag
+    fn parse_row_commands(&mut self, row: &mut EquationRow) -> Result<(), ParseError> {
+        while let Some(name) = ROW_COMMANDS.iter().find(|name| self.at_command(name)) {
+            self.advance();
+            self.advance();
+            match *name {
+                "label" => row.label = Some(self.parse_text_argument()?),
+                "tag" => row.tag = Some(self.parse_text_argument()?),
+                _ => row.nonumber = true,
+            }
+        }
+        Ok(())
+    }
+
+    // 花括号中的原文，去掉首尾空格
+    fn parse_text_argument(&mut self) -> Result<String, ParseError> {
+        Ok(self.parse_raw_argument()?.trim().to_string())
+    }
+
+    // 花括号中的原文，可以嵌套花括号；保留首尾空格，\text{if } 中的空格会被排版
+    fn parse_raw_argument(&mut self) -> Result<String, ParseError> {
+        self.expect(&Token::LCurly)?;
+        let begin = self.tokens[self.pos - 1].span.end;
+        let mut depth = 0;
+        loop {
+            let (token, span) = self.next_token(&[Token::RCurly])?;
+            match token {
+                Token::LCurly => depth += 1,
+                Token::RCurly if depth == 0 => return Ok(self.input[begin..span.start].to_string()),
+                Token::RCurly => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+
+    // 分段函数: 每行为 值 & 条件，条件可以省略；值和条件之后可以有逗号或句号，如 x, & x \ge 0
+    fn parse_piecewise(&mut self, start: usize) -> Result<MathExpr, ParseError> {
+        let mut branches = Vec::new();
+        while !self.check(&Token::End) {
+            let value = self.parse_expression()?;
+            self.consume_punctuation();
+            let condition = if self.consume(&Token::Ampersand) {
+                self.parse_condition()?
+            } else {
+                None
+            };
+            branches.push((value, condition));
+            if !self.consume(&Token::DoubleBackslash) && !self.check(&Token::End) {
+                return Err(self.error(&[Token::Ampersand, Token::DoubleBackslash, Token::End]));
+            }
+        }
+        self.parse_environment_end(&Environment::Cases)?;
+        Ok(self.spanned(start, MathExpr::Piecewise { branches }))
+    }
+
+    // 分段函数的条件，\text{otherwise} 和空的条件为 None；其他 \text{...} 作为正文保留在条件中，
+    // 如 \text{if } x > 0、\text{for } x > 0、x \text{ is even}
+    fn parse_condition(&mut self) -> Result<Option<MathExpr>, ParseError> {
+        if matches!(self.peek(), Some(Token::DoubleBackslash | Token::End)) {
+            return Ok(None);
+        }
+        let condition = self.parse_expression()?;
+        self.consume_punctuation();
+        match condition.unspanned() {
+            MathExpr::Text { command: TextCommand::Text, text } if text.trim().trim_end_matches(['.', ',']) == "otherwise" => Ok(None),
+            _ => Ok(Some(condition)),
+        }
+    }
+
+    // 跳过单元格末尾的逗号或句号
+    fn consume_punctuation(&mut self) {
+        if !self.consume(&Token::Comma) {
+            self.consume(&Token::Unknown(".".to_string()));
+        }
+    }
+
+    // 解析环境名称，只接受已支持的环境；align、gather 和 eqnarray 可以带 *
+    fn parse_environment_name(&mut self) -> Result<Environment, ParseError> {
+        const MATRIX_TYPES: [Token; 6] = [
+            Token::Matrix,
+            Token::PMatrix,
+            Token::BMatrix,
+            Token::VMatrix,
+            Token::VMatrixDouble,
+            Token::Identifier(String::new()),
+        ];
+
+        let (token, span) = self.next_token(&MATRIX_TYPES)?;
+        match token {
+            Token::Matrix => Ok(Environment::Matrix),
+            Token::PMatrix => Ok(Environment::PMatrix),
+            Token::BMatrix => Ok(Environment::BMatrix),
+            Token::VMatrix => Ok(Environment::VMatrix),
+            Token::VMatrixDouble => Ok(Environment::VMatrixDouble),
+            Token::Identifier(mut name) => {
+                if matches!(name.as_str(), "align" | "gather" | "eqnarray") && self.consume(&Token::Asterisk) {
+                    name.push('*');
+                }
+                Environment::from_name(&name).ok_or(ParseError::UnknownEnvironment { name, span })
+            }
+            found => Err(ParseError::UnexpectedToken {
+                expected: MATRIX_TYPES.to_vec(),
+                found,
+                span,
+            }),
+        }
+    }
+
+    // 解析 array 的列格式，只支持 l、c、r 和竖线
+    fn parse_column_spec(&mut self) -> Result<String, ParseError> {
+        self.expect(&Token::LCurly)?;
+        let mut columns = String::new();
+        loop {
+            match self.peek() {
+                Some(Token::Identifier(letters)) if letters.chars().all(|c| matches!(c, 'l' | 'c' | 'r')) => {
+                    columns.push_str(letters);
+                }
+                Some(Token::Pipe) => columns.push('|'),
+                Some(Token::RCurly) if !columns.is_empty() => break,
+                _ => return Err(self.error(&[Token::Identifier("c".to_string()), Token::Pipe, Token::RCurly])),
+            }
+            self.advance();
+        }
+        self.advance();
+        Ok(columns)
+    }
+
+    // 解析一个单元格，单元格可以为空，也可以以关系运算符开始，如 aligned 中的 &= b 和 eqnarray 中的 &=&
+    fn parse_cell(&mut self) -> Result<MathExpr, ParseError> {
+        if self.at_cell_end() {
+            return Ok(MathExpr::Empty);
+        }
+        if self.peek_relation().is_none() {
+            return self.parse_expression();
+        }
+
+        let start = self.current_span().start;
+        let mut operands = vec![MathExpr::Empty];
+        let mut operators = Vec::new();
+        while let Some(operator) = self.consume_relation() {
+            operators.push(operator);
+            // eqnarray 中的 &=& 只有关系运算符，右侧也为空
+            operands.push(if self.at_cell_end() { MathExpr::Empty } else { self.parse_additive()? });
+        }
+        Ok(self.relation(start, operands, operators))
+    }
+
+    // 是否到了单元格的末尾
+    fn at_cell_end(&self) -> bool {
+        matches!(self.peek(), Some(Token::Ampersand | Token::DoubleBackslash | Token::End)) || self.at_row_command()
+    }
+
+    // 解析交换图的一行，对象和箭头依次排列，每个对象和箭头各占一个单元格
+    fn parse_cd_row(&mut self) -> Result<Vec<MathExpr>, ParseError> {
+        let mut cells = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::DoubleBackslash | Token::End) | None => return Ok(cells),
+                Some(Token::At) => {
+                    self.advance();
+                    cells.push(self.parse_cd_arrow()?);
+                }
+                _ => cells.push(self.parse_expression()?),
+            }
+        }
+    }
+
+    // 解析 @ 之后的箭头：@>a>b>、@<<<、@VfVV、@A{g}AA、@=、@|、@.
+    fn parse_cd_arrow(&mut self) -> Result<MathExpr, ParseError> {
+        const ARROWS: [Token; 6] = [
+            Token::Greater,
+            Token::Less,
+            Token::Identifier(String::new()),
+            Token::Equals,
+            Token::Pipe,
+            Token::Unknown(String::new()),
+        ];
+
+        let start = self.last_start();
+        let (token, span) = self.next_token(&ARROWS)?;
+        let (direction, first, second) = match token {
+            Token::Greater | Token::Less => {
+                let direction = if token == Token::Greater { CdArrowDirection::Right } else { CdArrowDirection::Left };
+                let first = self.parse_cd_label(&token)?;
+                let second = self.parse_cd_label(&token)?;
+                (direction, first, second)
+            }
+            Token::Identifier(text) if text.starts_with(['V', 'A']) => {
+                let arrow = self.parse_vertical_arrow(text)?;
+                return Ok(self.spanned(start, arrow));
+            }
+            Token::Equals => (CdArrowDirection::Equals, None, None),
+            Token::Pipe => (CdArrowDirection::VerticalEquals, None, None),
+            Token::Unknown(text) if text == "." => (CdArrowDirection::Blank, None, None),
+            found => {
+                return Err(ParseError::UnexpectedToken {
+                    expected: ARROWS.to_vec(),
+                    found,
+                    span,
+                });
+            }
+        };
+
+        Ok(self.spanned(start, MathExpr::CdArrow { direction, first, second }))
+    }
+
+    // 水平箭头的标注，写在两个 > 或 < 之间，可以用花括号括起来
+    fn parse_cd_label(&mut self, marker: &Token) -> Result<Option<Box<MathExpr>>, ParseError> {
+        let label = if self.check(marker) {
+            None
+        } else if self.consume(&Token::LCurly) {
+            let label = self.parse_expression()?;
+            self.expect(&Token::RCurly)?;
+            Some(Box::new(label))
+        } else {
+            Some(Box::new(self.parse_additive()?))
+        };
+        self.expect(marker)?;
+        Ok(label)
+    }
+
+    // 竖直箭头：V 和 A 与标注中的字母会被识别为同一个标识符，如 @VfVV，
+    // 因此按标记字母拆分标识符；花括号中的标注作为整体
+    fn parse_vertical_arrow(&mut self, text: String) -> Result<MathExpr, ParseError> {
+        let (marker, direction) = if text.starts_with('V') {
+            ('V', CdArrowDirection::Down)
+        } else {
+            ('A', CdArrowDirection::Up)
+        };
+        let expected = [Token::Identifier(marker.to_string())];
+
+        // labels[i] 为第 i 个标记之后的内容
+        let mut labels: Vec<Option<MathExpr>> = Vec::new();
+        let mut pending = text;
+        loop {
+            for c in pending.chars() {
+                if c == marker && labels.len() < 3 {
+                    labels.push(None);
                     continue;
                 }
+                if labels.len() == 3 {
+                    return Err(self.error(&expected));
+                }
+                match labels.last_mut() {
+                    Some(label @ None) => *label = Some(MathExpr::Variable(c.to_string())),
+                    Some(Some(MathExpr::Variable(name))) => name.push(c),
+                    _ => return Err(self.error(&expected)),
+                }
+            }
+            if labels.len() == 3 {
+                break;
+            }
+
+            match self.peek() {
+                Some(Token::LCurly) if matches!(labels.last(), Some(None)) => {
+                    self.advance();
+                    let label = self.parse_expression()?;
+                    self.expect(&Token::RCurly)?;
+                    *labels.last_mut().unwrap() = Some(label);
+                    pending = String::new();
+                }
+                Some(Token::Identifier(next)) => {
+                    pending = next.clone();
+                    self.advance();
+                }
+                _ => return Err(self.error(&expected)),
             }
         }
+
+        let mut labels = labels.into_iter().map(|label| label.map(Box::new));
+        let first = labels.next().flatten();
+        let second = labels.next().flatten();
+        Ok(MathExpr::CdArrow { direction, first, second })
     }
 
     // 解析绝对值表达式
-    fn parse_absolute_value(&mut self) -> Result<MathExpr, String> {
+    fn parse_absolute_value(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.last_start();
         // 检查是否是范数（双竖线）
-        if let Some(Token::Pipe) = self.tokens.peek() {
+        if let Some(Token::Pipe) = self.peek() {
             // 连续两个Pipe，这是范数
-            self.tokens.next(); // 消耗第二个Pipe
+            self.advance(); // 消耗第二个Pipe
             let inner_expr = self.parse_expression_until_pipe()?;
             self.expect(&Token::Pipe)?; // 消费第三个Pipe
             self.expect(&Token::Pipe)?; // 消费第四个Pipe
             // 范数用norm函数表示
-            return Ok(MathExpr::FunctionCall {
+            return Ok(self.spanned(start, MathExpr::FunctionCall {
                 name: "norm".to_string(),
                 args: vec![inner_expr],
-            });
+            }));
         }
         
         // 单个绝对值
         let inner_expr = self.parse_expression_until_pipe()?;
         self.expect(&Token::Pipe)?; // 消费结束的Pipe
         
-        Ok(MathExpr::FunctionCall {
+        Ok(self.spanned(start, MathExpr::FunctionCall {
             name: "abs".to_string(),
             args: vec![inner_expr],
-        })
+        }))
     }
     
     // 解析表达式直到遇到Pipe符号（不包括Pipe）
-    fn parse_expression_until_pipe(&mut self) -> Result<MathExpr, String> {
+    fn parse_expression_until_pipe(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
         let mut expr = self.parse_equality_until_pipe()?;
 
         while self.consume(&Token::Equals) {
@@ -598,52 +1031,57 @@ impl<'a> Parser<'a> {
                 break;
             }
             let right = self.parse_equality_until_pipe()?;
-            expr = MathExpr::BinaryOp {
+            expr = self.spanned(start, MathExpr::BinaryOp {
                 left: Box::new(expr),
                 operator: BinaryOperator::Equals,
                 right: Box::new(right),
-            };
+            });
         }
 
         Ok(expr)
     }
     
-    // 等式: expression = expression，但遇到Pipe时停止
-    fn parse_equality_until_pipe(&mut self) -> Result<MathExpr, String> {
-        let mut expr = self.parse_additive_until_pipe()?;
+    // 等式与关系，但遇到Pipe时停止
+    fn parse_equality_until_pipe(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
+        let mut operands = vec![self.parse_additive_until_pipe()?];
+        let mut operators = Vec::new();
 
-        while let Some(token) = self.tokens.peek() {
-            // 遇到Pipe时停止
-            if let Token::Pipe = token {
+        while let Some(operator) = self.consume_relation() {
+            // 检查下一个符号是否是Pipe
+            if self.check(&Token::Pipe) {
                 break;
             }
-            
-            match token {
-                Token::Equals => {
-                    self.tokens.next();
-                    // 检查下一个符号是否是Pipe
-                    if self.check(&Token::Pipe) {
-                        break;
-                    }
-                    let right = self.parse_additive_until_pipe()?;
-                    expr = MathExpr::BinaryOp {
-                        left: Box::new(expr),
-                        operator: BinaryOperator::Equals,
-                        right: Box::new(right),
-                    };
-                }
-                _ => break,
-            }
+            operators.push(operator);
+            operands.push(self.parse_additive_until_pipe()?);
         }
 
-        Ok(expr)
+        Ok(self.relation(start, operands, operators))
+    }
+
+    // 由关系链构造节点：没有关系运算符时为唯一的操作数，单个 = 为 BinaryOp::Equals
+    fn relation(&self, start: usize, mut operands: Vec<MathExpr>, operators: Vec<RelationOperator>) -> MathExpr {
+        match operators.as_slice() {
+            [] => operands.pop().unwrap(),
+            [RelationOperator::Equals] => {
+                let right = operands.pop().unwrap();
+                let left = operands.pop().unwrap();
+                self.spanned(start, MathExpr::BinaryOp {
+                    left: Box::new(left),
+                    operator: BinaryOperator::Equals,
+                    right: Box::new(right),
+                })
+            }
+            _ => self.spanned(start, MathExpr::Relation { operands, operators }),
+        }
     }
     
     // 加减法: additive ( (+ | -) additive )*，但遇到Pipe时停止
-    fn parse_additive_until_pipe(&mut self) -> Result<MathExpr, String> {
+    fn parse_additive_until_pipe(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
         let mut expr = self.parse_multiplicative_until_pipe()?;
 
-        while let Some(token) = self.tokens.peek() {
+        while let Some(token) = self.peek() {
             // 遇到Pipe时停止
             if let Token::Pipe = token {
                 break;
@@ -651,22 +1089,22 @@ impl<'a> Parser<'a> {
             
             match token {
                 Token::Plus => {
-                    self.tokens.next();
+                    self.advance();
                     let right = self.parse_multiplicative_until_pipe()?;
-                    expr = MathExpr::BinaryOp {
+                    expr = self.spanned(start, MathExpr::BinaryOp {
                         left: Box::new(expr),
                         operator: BinaryOperator::Add,
                         right: Box::new(right),
-                    };
+                    });
                 }
                 Token::Minus => {
-                    self.tokens.next();
+                    self.advance();
                     let right = self.parse_multiplicative_until_pipe()?;
-                    expr = MathExpr::BinaryOp {
+                    expr = self.spanned(start, MathExpr::BinaryOp {
                         left: Box::new(expr),
                         operator: BinaryOperator::Subtract,
                         right: Box::new(right),
-                    };
+                    });
                 }
                 _ => break,
             }
@@ -675,53 +1113,45 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
     
-    // 乘除法: multiplicative ( (* | / | \cdot) multiplicative )*，但遇到Pipe时停止
-    fn parse_multiplicative_until_pipe(&mut self) -> Result<MathExpr, String> {
+    // 乘除法: multiplicative ( (* | \times | / | \cdot) multiplicative )*，但遇到Pipe时停止
+    fn parse_multiplicative_until_pipe(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
         let mut expr = self.parse_power_until_pipe()?;
 
-        while let Some(token) = self.tokens.peek() {
+        while let Some(token) = self.peek() {
             // 遇到Pipe时停止
             if let Token::Pipe = token {
                 break;
             }
             
             match token {
-                Token::Asterisk => {
-                    self.tokens.next();
+                Token::Asterisk | Token::Times => {
+                    self.advance();
                     let right = self.parse_power_until_pipe()?;
-                    expr = MathExpr::BinaryOp {
+                    expr = self.spanned(start, MathExpr::BinaryOp {
                         left: Box::new(expr),
                         operator: BinaryOperator::Multiply,
                         right: Box::new(right),
-                    };
+                    });
                 }
                 Token::Slash => {
-                    self.tokens.next();
-                    let right = self.parse_power_until_pipe()?;
-                    // 特殊处理导数运算符 \frac{d}{dx}
-                    if let MathExpr::Variable(ref left) = expr {
-                        if left == "d" {
-                            if let MathExpr::Variable(ref right_var) = right {
-                                if right_var.starts_with("d") && right_var.len() > 1 {
-                                    let variable = &right_var[1..];
-                                    return Ok(MathExpr::Variable(format!("\\frac{{d}}{{d{}}}", variable)));
-                                }
-                            }
-                        }
+                    self.advance();
+                    // 算子形式的导数 d/dx f
+                    if let Some(derivative) = self.parse_slash_derivative(&expr, start, true)? {
+                        expr = derivative;
+                        continue;
                     }
-                    expr = MathExpr::Fraction {
-                        numerator: Box::new(expr),
-                        denominator: Box::new(right),
-                    };
+                    let right = self.parse_power_until_pipe()?;
+                    expr = self.slash_fraction(start, expr, right);
                 }
                 Token::CDot => {
-                    self.tokens.next();
+                    self.advance();
                     let right = self.parse_power_until_pipe()?;
-                    expr = MathExpr::BinaryOp {
+                    expr = self.spanned(start, MathExpr::BinaryOp {
                         left: Box::new(expr),
                         operator: BinaryOperator::DotProduct,
                         right: Box::new(right),
-                    };
+                    });
                 }
                 _ => break,
             }
@@ -731,7 +1161,8 @@ impl<'a> Parser<'a> {
     }
     
     // 幂运算: power (^ power)*，但遇到Pipe时停止
-    fn parse_power_until_pipe(&mut self) -> Result<MathExpr, String> {
+    fn parse_power_until_pipe(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
         let mut expr = self.parse_factor_until_pipe()?;
 
         while self.consume(&Token::Caret) {
@@ -740,27 +1171,41 @@ impl<'a> Parser<'a> {
                 break;
             }
             
-            let right = self.parse_unary()?;  // 使用parse_unary而不是parse_power来避免无限递归
-            expr = MathExpr::BinaryOp {
+            let right = self.parse_unary_until_pipe()?;
+            expr = self.spanned(start, MathExpr::BinaryOp {
                 left: Box::new(expr),
                 operator: BinaryOperator::Power,
                 right: Box::new(right),
-            };
+            });
         }
 
         Ok(expr)
     }
+
+    // 一元运算: (+ | - | !) unary | factor，但遇到Pipe时停止
+    fn parse_unary_until_pipe(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
+        let operator = match self.peek() {
+            Some(Token::Plus) => UnaryOperator::Plus,
+            Some(Token::Minus) => UnaryOperator::Minus,
+            Some(Token::Exclamation) => UnaryOperator::Factorial,
+            _ => return self.parse_factor_until_pipe(),
+        };
+        self.advance();
+        let expr = self.parse_unary_until_pipe()?;
+        Ok(self.spanned(start, MathExpr::UnaryOp {
+            operator,
+            expr: Box::new(expr),
+        }))
+    }
     
     // 因子: primary (primary)* 用于处理连续表达式，如隐式乘法，但遇到Pipe时停止
-    fn parse_factor_until_pipe(&mut self) -> Result<MathExpr, String> {
+    fn parse_factor_until_pipe(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.current_span().start;
         let mut expr = self.parse_primary_until_pipe()?;
 
         // 处理连续的表达式（隐式乘法）
-        loop {
-            let peeked = match self.tokens.peek() {
-                Some(t) => t,
-                None => break,
-            };
+        while let Some(peeked) = self.peek() {
 
             // 遇到Pipe时停止
             if let Token::Pipe = peeked {
@@ -773,16 +1218,19 @@ impl<'a> Parser<'a> {
                 Token::Number(_) | Token::Identifier(_) | Token::LParen | Token::Backslash | Token::LBracket | Token::Pipe => {}
                 _ => break,
             }
+            if self.at_right_delimiter() || self.at_row_command() || self.peek_relation().is_some() {
+                break;
+            }
 
             // 特别注意：如果当前expr已经是Superscript或Subscript，需要防止错误连接
             // 但我们仍然允许如 sin^2 x 这样的隐式乘法
 
             let right = self.parse_primary_until_pipe()?;
-            expr = MathExpr::BinaryOp {
+            expr = self.spanned(start, MathExpr::BinaryOp {
                 left: Box::new(expr),
                 operator: BinaryOperator::Multiply,
                 right: Box::new(right),
-            };
+            });
         }
 
         Ok(expr)
@@ -790,67 +1238,79 @@ impl<'a> Parser<'a> {
     
     // 基本元素: number | identifier | function | subscript | superscript | parentheses | absolute value
     // 但遇到Pipe时停止
-    fn parse_primary_until_pipe(&mut self) -> Result<MathExpr, String> {
+    fn parse_primary_until_pipe(&mut self) -> Result<MathExpr, ParseError> {
+        let (token, span) = self.next_token(&[])?;
+        let start = span.start;
         let mut expr = {
-            let token = self.tokens.next().ok_or("Unexpected end of input")?;
             
             // 遇到Pipe时停止
             if let Token::Pipe = token {
-                return Err("Unexpected Pipe".to_string());
+                return Err(ParseError::UnexpectedToken { expected: vec![], found: token, span });
             }
 
             match token {
-                Token::Number(n) => MathExpr::Number(n),
+                Token::Number(n) => self.spanned(start, MathExpr::Number(n)),
                 Token::Identifier(name) => {
-                    // 检查是否是函数调用或者带导数符号的变量
-                    if self.consume(&Token::LParen) {
+                    // 检查是否带导数符号 f'、f^{(4)}，以及是否是函数调用 f(x)、f'(x)
+                    let order = self.consume_derivative_order();
+                    let expr = if self.consume(&Token::LParen) {
                         self.parse_function_call_args_until_pipe().map(|args| MathExpr::FunctionCall { name, args })?
-                    } else if self.consume(&Token::Prime) {
-                        // 处理导数符号 f'
-                        MathExpr::Variable(format!("{}'", name))
                     } else {
                         MathExpr::Variable(name)
-                    }
+                    };
+                    let expr = self.spanned(start, expr);
+                    self.primed(start, expr, order)
                 }
                 Token::LParen => {
                     let expr = self.parse_expression_until_pipe()?;
                     self.expect(&Token::RParen)?;
-                    MathExpr::Parenthesized(Box::new(expr))
+                    self.spanned(start, MathExpr::Parenthesized(Box::new(expr)))
                 }
                 Token::LBracket => {
                     let expr = self.parse_expression_until_pipe()?;
                     self.expect(&Token::RBracket)?;
-                    MathExpr::Parenthesized(Box::new(expr))
+                    self.spanned(start, MathExpr::Parenthesized(Box::new(expr)))
                 }
                 Token::Pipe => {
                     // 处理嵌套绝对值 |...|
                     let inner_expr = self.parse_expression_until_pipe()?;
                     self.expect(&Token::Pipe)?;  // 消耗结束的Pipe符号
-                    return Ok(MathExpr::FunctionCall {
+                    return Ok(self.spanned(start, MathExpr::FunctionCall {
                         name: "abs".to_string(),
                         args: vec![inner_expr],
-                    });
+                    }));
                 }
                 Token::Begin => {
                     // 直接处理矩阵环境
                     return self.parse_matrix_environment();
                 }
                 Token::Backslash => {
-                    self.parse_command_until_pipe()?
+                    let expr = self.parse_command_until_pipe()?;
+                    // 求和、求积、极限、积分、导数与微分算子的主体已延伸到右侧，之后不再接上下标和后缀
+                    if matches!(
+                        expr.unspanned(),
+                        MathExpr::BigOperator { .. }
+                            | MathExpr::Limit { .. }
+                            | MathExpr::Integral { .. }
+                            | MathExpr::Derivative { .. }
+                            | MathExpr::PartialDerivative { .. }
+                            | MathExpr::DifferentialOperator { .. }
+                    ) {
+                        return Ok(expr);
+                    }
+                    expr
                 }
-                Token::Minus => {
-                    // 处理负号
-                    let expr = self.parse_unary()?;
-                    return Ok(MathExpr::UnaryOp {
-                        operator: UnaryOperator::Minus,
-                        expr: Box::new(expr),
-                    });
+                Token::Minus | Token::Plus => {
+                    // 处理负号，以及 x^{+y} 中的正号
+                    let operator = if token == Token::Minus { UnaryOperator::Minus } else { UnaryOperator::Plus };
+                    let expr = self.parse_unary_until_pipe()?;
+                    return Ok(self.spanned(start, MathExpr::UnaryOp { operator, expr: Box::new(expr) }));
                 }
-                _ => return Err(format!("Unexpected token: {:?}", token)),
+                _ => return Err(ParseError::UnexpectedToken { expected: vec![], found: token, span }),
             }
         };
 
-        // 处理连续的下标和上标
+        // 处理连续的下标、上标和导数符号
         loop {
             // 检查是否遇到Pipe
             if self.check(&Token::Pipe) {
@@ -860,98 +1320,97 @@ impl<'a> Parser<'a> {
             if self.consume(&Token::Underscore) {
                 // 处理下标
                 let subscript = if self.check(&Token::LCurly) {
-                    self.tokens.next(); // 消耗 {
+                    self.advance(); // 消耗 {
                     let expr = self.parse_expression_until_pipe()?;
                     self.expect(&Token::RCurly)?;
                     expr
                 } else {
-                    // 不带花括号的简写形式，但仍需正确处理Pipe边界
-                    self.parse_expression_until_pipe()?
+                    // 不带花括号的简写形式只取一个元素
+                    self.parse_script_atom(false)?
                 };
                 
-                expr = MathExpr::Subscript {
+                expr = self.spanned(start, MathExpr::Subscript {
                     base: Box::new(expr),
                     subscript: Box::new(subscript),
-                };
+                });
             } else if self.consume(&Token::Caret) {
                 // 处理上标
                 let superscript = if self.check(&Token::LCurly) {
-                    self.tokens.next(); // 消耗 {
+                    self.advance(); // 消耗 {
                     let expr = self.parse_expression_until_pipe()?;
                     self.expect(&Token::RCurly)?;
                     expr
                 } else {
-                    // 不带花括号的简写形式，但仍需正确处理Pipe边界
-                    self.parse_expression_until_pipe()?
+                    // 不带花括号的简写形式只取一个元素
+                    self.parse_script_atom(true)?
                 };
                 
-                expr = MathExpr::Superscript {
+                expr = self.spanned(start, MathExpr::Superscript {
                     base: Box::new(expr),
                     superscript: Box::new(superscript),
-                };
+                });
+            } else if self.check(&Token::Prime) {
+                let order = self.consume_primes();
+                expr = self.primed(start, expr, order);
             } else {
                 break;
             }
         }
 
-        // 处理导数符号
-        if self.consume(&Token::Prime) {
-            expr = MathExpr::Variable(format!("{}'", expr.to_string()));
+        // 处理后缀阶乘 n!
+        while self.consume(&Token::Exclamation) {
+            expr = self.spanned(start, MathExpr::UnaryOp {
+                operator: UnaryOperator::Factorial,
+                expr: Box::new(expr),
+            });
         }
 
         Ok(expr)
     }
     
-    fn parse_command_until_pipe(&mut self) -> Result<MathExpr, String> {
-        let token = self.tokens.next().ok_or("Expected command name after backslash")?;
+    fn parse_command_until_pipe(&mut self) -> Result<MathExpr, ParseError> {
+        let start = self.last_start();
+        let (token, span) = self.next_token(&[Token::Identifier(String::new())])?;
         
         if let Token::Begin = token {
             return self.parse_matrix_environment();
         }
+
+        // \le 等关系运算符和 \label 等编号命令不能作为操作数
+        if let Token::Identifier(ref cmd) = token
+            && (RelationOperator::from_command(cmd).is_some() || ROW_COMMANDS.contains(&cmd.as_str()))
+        {
+            return Err(ParseError::UnexpectedToken { expected: vec![], found: token, span });
+        }
         
-        if let Token::Identifier(ref cmd) = token {
-            if cmd == "frac" {
-                self.expect(&Token::LCurly)?;
-                let numerator = self.parse_expression_until_pipe()?;
-                self.expect(&Token::RCurly)?;
-                self.expect(&Token::LCurly)?;
-                let denominator = self.parse_expression_until_pipe()?;
-                self.expect(&Token::RCurly)?;
-                
-                // 特殊处理导数运算符 \frac{d}{dx}
-                if let MathExpr::Variable(ref num) = numerator {
-                    if num == "d" {
-                        if let MathExpr::Variable(ref den) = denominator {
-                            if den.starts_with("d") && den.len() > 1 {
-                                // 这是一个导数运算符，检查后面是否有方括号表达式
-                                let variable = den[1..].to_string();
-                                // 检查后面是否跟着方括号表达式
-                                if let Some(Token::LBracket) = self.tokens.peek() {
-                                    self.tokens.next(); // 消耗 [
-                                    let inner_expr = self.parse_expression_until_pipe()?;
-                                    self.expect(&Token::RBracket)?; // 消耗 ]
-                                    
-                                    return Ok(MathExpr::Derivative {
-                                        variable,
-                                        expression: Box::new(inner_expr),
-                                    });
-                                } else {
-                                    // 没有方括号，返回导数运算符
-                                    return Ok(MathExpr::Variable(format!("\\frac{{d}}{{d{}}}", variable)));
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                return Ok(MathExpr::Fraction {
-                    numerator: Box::new(numerator),
-                    denominator: Box::new(denominator),
-                });
+        if let Token::Identifier(ref cmd) = token
+            && cmd == "frac"
+        {
+            self.expect(&Token::LCurly)?;
+            let numerator = self.parse_expression_until_pipe()?;
+            self.expect(&Token::RCurly)?;
+            self.expect(&Token::LCurly)?;
+            let denominator = self.parse_expression_until_pipe()?;
+            self.expect(&Token::RCurly)?;
+
+            // 导数 \frac{d}{dx}[...]、\frac{df}{dx}、\frac{d^2 y}{dx^2}、\frac{\partial f}{\partial x} 等
+            if let Some(derivative) = self.parse_derivative(&numerator, &denominator, start, true)? {
+                return Ok(derivative);
             }
+
+            return Ok(self.spanned(start, MathExpr::Fraction {
+                numerator: Box::new(numerator),
+                denominator: Box::new(denominator),
+            }));
+        }
+
+        if let Token::Identifier(ref cmd) = token
+            && let Some(expr) = self.parse_big_operator(cmd, start, true)?
+        {
+            return Ok(expr);
         }
         
-        let mut expr = if let Token::Identifier(cmd) = token {
+        let expr = if let Token::Identifier(cmd) = token {
             match cmd.as_str() {
                 "sqrt" => {
                     if self.consume(&Token::LBracket) {
@@ -981,19 +1440,35 @@ impl<'a> Parser<'a> {
                     self.expect(&Token::LCurly)?;
                     let expr = self.parse_expression_until_pipe()?;
                     self.expect(&Token::RCurly)?;
-                    MathExpr::Variable(format!("\\vec{{{}}}", expr.to_string()))
+                    MathExpr::Variable(format!("\\vec{{{}}}", expr.to_latex()))
+                }
+                "mathbf" => {
+                    // 粗体的向量，与 \vec{...} 相同按变量处理，如 \mathbf{F} \cdot d\mathbf{r}
+                    self.expect(&Token::LCurly)?;
+                    let expr = self.parse_expression()?;
+                    self.expect(&Token::RCurly)?;
+                    MathExpr::Variable(format!("\\mathbf{{{}}}", expr.to_latex()))
                 }
                 "int" => {
-                    // 处理积分符号
+                    // 没有被积函数的积分符号
                     MathExpr::Variable("\\int".to_string())
                 }
+                "mathrm" => {
+                    // 正体只影响排版，\mathrm{d} 按变量 d 处理
+                    self.parse_mathrm()?
+                }
+                // 正文按原文保存，其中可以出现任意字符
+                "text" => MathExpr::Text { command: TextCommand::Text, text: self.parse_raw_argument()? },
+                "operatorname" => MathExpr::Text { command: TextCommand::Operatorname, text: self.parse_text_argument()? },
                 "left" => {
-                    // 处理 \left 命令
-                    // 简单跳过，因为我们主要关注数学表达式的结构
-                    if let Some(next_token) = self.tokens.next() {
+                    // 处理 \left( ... \right) 和 \left[ ... \right]，其他定界符简单跳过
+                    if let Some(next_token) = self.advance() {
                         match next_token {
-                            Token::LBracket => MathExpr::Variable("[".to_string()),
-                            Token::LParen => MathExpr::Variable("(".to_string()),
+                            Token::LBracket | Token::LParen => {
+                                let inner = self.parse_expression_until_pipe()?;
+                                self.expect_right_delimiter()?;
+                                MathExpr::Parenthesized(Box::new(inner))
+                            }
                             _ => MathExpr::Variable(format!("\\left{}", 
                                 match next_token {
                                     Token::Identifier(s) => s,
@@ -1006,39 +1481,48 @@ impl<'a> Parser<'a> {
                     }
                 }
                 "right" => {
-                    // 处理 \right 命令
-                    // 简单跳过，因为我们主要关注数学表达式的结构
-                    if let Some(next_token) = self.tokens.next() {
-                        match next_token {
-                            Token::RBracket => MathExpr::Variable("]".to_string()),
-                            Token::RParen => MathExpr::Variable(")".to_string()),
-                            _ => MathExpr::Variable(format!("\\right{}", 
-                                match next_token {
-                                    Token::Identifier(s) => s,
-                                    _ => format!("{:?}", next_token)
-                                }
-                            ))
-                        }
-                    } else {
-                        MathExpr::Variable("\\right".to_string())
-                    }
+                    // \right 只能出现在对应的 \left 之后，由 expect_right_delimiter 处理
+                    return Err(ParseError::UnexpectedToken {
+                        expected: Vec::new(),
+                        found: Token::Identifier(cmd),
+                        span,
+                    });
                 }
                 _ => {
-                    // 检查是否是函数调用
+                    if self.strict && !is_known_command(&cmd) {
+                        return Err(ParseError::UnknownCommand { name: cmd, span: start..span.end });
+                    }
+                    // 检查是否是函数调用，导数符号写在参数之前 \sin'(x)
+                    let order = self.consume_derivative_order();
                     if self.consume(&Token::LParen) {
                         let args = self.parse_function_call_args_until_pipe()?;
-                        return Ok(MathExpr::FunctionCall {
+                        let call = self.spanned(start, MathExpr::FunctionCall {
                             name: format!("\\{}", cmd),
                             args,
                         });
+                        return Ok(self.primed(start, call, order));
+                    } else if order > 0 {
+                        let operand = match Symbol::from_command(&cmd) {
+                            Some(symbol) => MathExpr::Symbol(symbol),
+                            None => MathExpr::Variable(format!("\\{}", cmd)),
+                        };
+                        let operand = self.spanned(start, operand);
+                        MathExpr::Prime { expr: Box::new(operand), order }
+                    } else if let Some(symbol) = Symbol::from_command(&cmd) {
+                        MathExpr::Symbol(symbol)
                     } else {
                         MathExpr::Variable(format!("\\{}", cmd))
                     }
                 }
             }
         } else {
-            return Err("Expected command name after backslash".to_string());
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![Token::Identifier(String::new())],
+                found: token,
+                span,
+            });
         };
+        let mut expr = self.spanned(start, expr);
 
         // 处理命令后的下标和上标
         loop {
@@ -1048,9 +1532,9 @@ impl<'a> Parser<'a> {
             }
             
             if self.consume(&Token::Underscore) {
-                expr = self.parse_subscript(expr)?;
+                expr = self.parse_subscript(expr, start)?;
             } else if self.consume(&Token::Caret) {
-                expr = self.parse_superscript(expr)?;
+                expr = self.parse_superscript(expr, start)?;
             } else {
                 break;
             }
@@ -1059,7 +1543,479 @@ impl<'a> Parser<'a> {
         Ok(expr)
     }
     
-    fn parse_function_call_args_until_pipe(&mut self) -> Result<Vec<MathExpr>, String> {
+    // 求和、求积、极限与积分: \sum_{i=1}^{n} body、\prod_{k=1}^{n} body、\lim_{x \to a} body、
+    // \int_{a}^{b} f(x) dx；主体为其后的乘除法表达式。
+    // 上下限无法解析或后面没有主体时回退位置并返回 None，按普通命令处理
+    fn parse_big_operator(&mut self, cmd: &str, start: usize, until_pipe: bool) -> Result<Option<MathExpr>, ParseError> {
+        let kind = match cmd {
+            "sum" => BigOperatorKind::Sum,
+            "prod" => BigOperatorKind::Product,
+            "lim" => return self.parse_limit(start, until_pipe),
+            "int" => return self.parse_integral(IntegralKind::Single, start, until_pipe),
+            "iint" => return self.parse_integral(IntegralKind::Double, start, until_pipe),
+            "iiint" => return self.parse_integral(IntegralKind::Triple, start, until_pipe),
+            "oint" => return self.parse_integral(IntegralKind::Contour, start, until_pipe),
+            "nabla" => return self.parse_nabla(start, until_pipe),
+            "Delta" => return self.parse_differential_operator(DifferentialOperatorKind::Laplacian, start, until_pipe),
+            _ => return Ok(None),
+        };
+        let saved = self.pos;
+
+        let Some((subscript, upper)) = self.parse_bounds() else {
+            self.pos = saved;
+            return Ok(None);
+        };
+        if !self.at_body_start(until_pipe) {
+            self.pos = saved;
+            return Ok(None);
+        }
+        let body = self.parse_body(until_pipe)?;
+
+        // 下标 i=1 拆分为索引变量和下限，单个变量只作为索引，其他表达式只作为下限
+        let (index, lower) = match subscript {
+            Some(subscript) => split_index(subscript),
+            None => (None, None),
+        };
+
+        Ok(Some(self.spanned(start, MathExpr::BigOperator {
+            kind,
+            index,
+            lower,
+            upper: upper.map(Box::new),
+            body: Box::new(body),
+        })))
+    }
+
+    // 积分 \int_{a}^{b} integrand dx，末尾的 dx、d x、d\theta、\mathrm{d}x 作为微分取出，
+    // 个数不超过积分的重数；整个主体都是微分时被积函数为 1
+    fn parse_integral(&mut self, kind: IntegralKind, start: usize, until_pipe: bool) -> Result<Option<MathExpr>, ParseError> {
+        let saved = self.pos;
+        let Some((lower, upper)) = self.parse_bounds() else {
+            self.pos = saved;
+            return Ok(None);
+        };
+        if !self.at_body_start(until_pipe) {
+            self.pos = saved;
+            return Ok(None);
+        }
+
+        let mut integrand = self.parse_body(until_pipe)?;
+        let mut variables = Vec::new();
+        // 被积函数与微分之间为点积，如 \oint_C \mathbf{F} \cdot d\mathbf{r}
+        let dot_product = match integrand.unspanned() {
+            MathExpr::BinaryOp { left, operator: BinaryOperator::DotProduct, right } => match split_differential(right) {
+                Some((None, found)) if found.len() <= kind.arity() => Some(((**left).clone(), found)),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some((left, found)) = &dot_product {
+            integrand = left.clone();
+            variables = found.clone();
+        }
+        while dot_product.is_none()
+            && variables.len() < kind.arity()
+            && let Some((rest, mut found)) = split_differential(&integrand)
+            && variables.len() + found.len() <= kind.arity()
+        {
+            found.append(&mut variables);
+            variables = found;
+            match rest {
+                Some(rest) => integrand = rest,
+                None => {
+                    integrand = MathExpr::Number(1.0);
+                    break;
+                }
+            }
+        }
+
+        Ok(Some(self.spanned(start, MathExpr::Integral {
+            kind,
+            lower: lower.map(Box::new),
+            upper: upper.map(Box::new),
+            integrand: Box::new(integrand),
+            variables,
+            dot_product: dot_product.is_some(),
+        })))
+    }
+
+    // 大型运算符的上下限 _{...}^{...}，顺序任意，各最多一个；无法解析时返回 None
+    fn parse_bounds(&mut self) -> Option<(Option<MathExpr>, Option<MathExpr>)> {
+        // \limits 和 \nolimits 只影响上下限的排版位置
+        if self.at_command("limits") || self.at_command("nolimits") {
+            self.pos += 2;
+        }
+
+        let mut lower = None;
+        let mut upper = None;
+        loop {
+            let (slot, superscript) = if lower.is_none() && self.consume(&Token::Underscore) {
+                (&mut lower, false)
+            } else if upper.is_none() && self.consume(&Token::Caret) {
+                (&mut upper, true)
+            } else {
+                break;
+            };
+            // 不带花括号的上下限与普通上下标相同，只取一个元素
+            let bound = if self.check(&Token::LCurly) {
+                self.parse_script_argument()
+            } else {
+                self.parse_script_atom(superscript)
+            };
+            *slot = Some(bound.ok()?);
+        }
+        Some((lower, upper))
+    }
+
+    // 极限 \lim_{x \to a} body，下标不是 变量 \to 目标 的形式时返回 None
+    fn parse_limit(&mut self, start: usize, until_pipe: bool) -> Result<Option<MathExpr>, ParseError> {
+        let saved = self.pos;
+        let Some(variable) = self.parse_limit_variable() else {
+            self.pos = saved;
+            return Ok(None);
+        };
+        let target = match self.parse_expression() {
+            Ok(target) if self.consume(&Token::RCurly) => target,
+            _ => {
+                self.pos = saved;
+                return Ok(None);
+            }
+        };
+
+        if !self.at_body_start(until_pipe) {
+            self.pos = saved;
+            return Ok(None);
+        }
+        let body = self.parse_body(until_pipe)?;
+
+        Ok(Some(self.spanned(start, MathExpr::Limit {
+            variable,
+            target: Box::new(target),
+            body: Box::new(body),
+        })))
+    }
+
+    // 极限下标的开头 _{x \to，返回变量名
+    fn parse_limit_variable(&mut self) -> Option<String> {
+        if !self.consume(&Token::Underscore) || !self.consume(&Token::LCurly) {
+            return None;
+        }
+        let variable = match self.advance()? {
+            Token::Identifier(name) => name,
+            Token::Backslash => match self.advance()? {
+                Token::Identifier(name) => format!("\\{}", name),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        if !self.at_command("to") {
+            return None;
+        }
+        self.pos += 2;
+        Some(variable)
+    }
+
+    // 向量微分算子 \nabla f、\nabla \cdot F、\nabla \times F、\nabla^2 f
+    fn parse_nabla(&mut self, start: usize, until_pipe: bool) -> Result<Option<MathExpr>, ParseError> {
+        let saved = self.pos;
+        let kind = if self.consume(&Token::CDot) {
+            DifferentialOperatorKind::Divergence
+        } else if self.consume(&Token::Times) {
+            DifferentialOperatorKind::Curl
+        } else if self.consume_square() {
+            DifferentialOperatorKind::Laplacian
+        } else {
+            DifferentialOperatorKind::Gradient
+        };
+        let operator = self.parse_differential_operator(kind, start, until_pipe)?;
+        if operator.is_none() {
+            self.pos = saved;
+        }
+        Ok(operator)
+    }
+
+    // 微分算子的作用对象为其后的幂运算表达式（包括隐式乘法），\nabla f \cdot g 中的 g 不属于算子；
+    // 后面没有作用对象时返回 None，按普通符号处理
+    fn parse_differential_operator(
+        &mut self,
+        kind: DifferentialOperatorKind,
+        start: usize,
+        until_pipe: bool,
+    ) -> Result<Option<MathExpr>, ParseError> {
+        if !self.at_body_start(until_pipe) {
+            return Ok(None);
+        }
+        let operand = if until_pipe {
+            self.parse_power_until_pipe()?
+        } else {
+            self.parse_power()?
+        };
+        Ok(Some(self.spanned(start, MathExpr::DifferentialOperator {
+            kind,
+            operand: Box::new(ungrouped(operand)),
+        })))
+    }
+
+    // 连续的导数符号，返回其个数
+    fn consume_primes(&mut self) -> u32 {
+        let mut order: u32 = 0;
+        while self.consume(&Token::Prime) {
+            order = order.saturating_add(1);
+        }
+        order
+    }
+
+    // 函数名之后的导数阶数：连续的导数符号，或 ^{(n)}，n 为正整数；都没有时为 0
+    fn consume_derivative_order(&mut self) -> u32 {
+        let order = self.consume_primes();
+        if order > 0 || !self.check(&Token::Caret) {
+            return order;
+        }
+        if let (Some(Token::LCurly), Some(Token::LParen), Some(Token::Number(n)), Some(Token::RParen), Some(Token::RCurly)) = (
+            self.peek_at(1),
+            self.peek_at(2),
+            self.peek_at(3),
+            self.peek_at(4),
+            self.peek_at(5),
+        ) && n.fract() == 0.0
+            && *n >= 1.0
+            && *n <= u32::MAX as f64
+        {
+            let order = *n as u32;
+            self.pos += 6;
+            return order;
+        }
+        0
+    }
+
+    // 阶数不为 0 时为表达式加上导数符号，f^{(4)}' 这样连续的导数合并为一个
+    fn primed(&self, start: usize, expr: MathExpr, order: u32) -> MathExpr {
+        if order == 0 {
+            return expr;
+        }
+        if let MathExpr::Prime { expr: inner, order: inner_order } = expr.unspanned() {
+            return self.spanned(start, MathExpr::Prime {
+                expr: inner.clone(),
+                order: inner_order.saturating_add(order),
+            });
+        }
+        self.spanned(start, MathExpr::Prime { expr: Box::new(expr), order })
+    }
+
+    // \nabla 之后的 ^2 或 ^{2}
+    fn consume_square(&mut self) -> bool {
+        let is_two = |token: Option<&Token>| matches!(token, Some(Token::Number(n)) if *n == 2.0);
+        if !self.check(&Token::Caret) {
+            return false;
+        }
+        if is_two(self.peek_at(1)) {
+            self.pos += 2;
+            return true;
+        }
+        if matches!(self.peek_at(1), Some(Token::LCurly)) && is_two(self.peek_at(2)) && matches!(self.peek_at(3), Some(Token::RCurly)) {
+            self.pos += 4;
+            return true;
+        }
+        false
+    }
+
+    // 由分子和分母组成导数或偏导数。分子中有被求导的表达式时直接得到导数，
+    // 否则为算子形式，与求和相同，被求导的表达式为其后的乘除法表达式；
+    // 阶数不一致或算子之后没有表达式时返回 None，按普通分数处理
+    fn parse_derivative(
+        &mut self,
+        numerator: &MathExpr,
+        denominator: &MathExpr,
+        start: usize,
+        until_pipe: bool,
+    ) -> Result<Option<MathExpr>, ParseError> {
+        if let Some((order, expression)) = derivative_numerator(numerator)
+            && let Some((variable, denominator_order)) = derivative_denominator(denominator)
+            && order == denominator_order
+        {
+            let Some(expression) = self.parse_derivative_expression(expression, until_pipe)? else {
+                return Ok(None);
+            };
+            return Ok(Some(self.spanned(start, MathExpr::Derivative {
+                variable,
+                order,
+                expression: Box::new(expression),
+            })));
+        }
+
+        if let Some((order, expression)) = partial_numerator(numerator)
+            && let Some(variables) = partial_denominator(denominator)
+            && total_order(&variables) == Some(order)
+        {
+            let Some(expression) = self.parse_derivative_expression(expression, until_pipe)? else {
+                return Ok(None);
+            };
+            return Ok(Some(self.spanned(start, MathExpr::PartialDerivative {
+                variables,
+                expression: Box::new(expression),
+            })));
+        }
+
+        Ok(None)
+    }
+
+    // 分子中没有被求导的表达式时，取其后的乘除法表达式
+    fn parse_derivative_expression(&mut self, expression: Option<MathExpr>, until_pipe: bool) -> Result<Option<MathExpr>, ParseError> {
+        match expression {
+            Some(expression) => Ok(Some(expression)),
+            None if self.at_body_start(until_pipe) => Ok(Some(ungrouped(self.parse_body(until_pipe)?))),
+            None => Ok(None),
+        }
+    }
+
+    // 算子形式的 d/dx 之后，分母只取 dx、d x 或 dx^2，其后的内容为被求导的表达式；
+    // 不构成导数时恢复位置，按普通的除法解析
+    fn parse_slash_derivative(&mut self, numerator: &MathExpr, start: usize, until_pipe: bool) -> Result<Option<MathExpr>, ParseError> {
+        if !matches!(derivative_numerator(numerator), Some((_, None))) {
+            return Ok(None);
+        }
+        let saved = self.pos;
+        let denominator_start = self.current_span().start;
+        let mut denominator = self.parse_primary_or_until_pipe(until_pipe);
+        // 分开书写的 d x 和 d\theta
+        if let Ok(d) = &denominator
+            && is_d(d)
+            && matches!(self.peek(), Some(Token::Identifier(_) | Token::Backslash))
+        {
+            denominator = self.parse_primary_or_until_pipe(until_pipe).map(|variable| {
+                self.spanned(denominator_start, MathExpr::BinaryOp {
+                    left: Box::new(d.clone()),
+                    operator: BinaryOperator::Multiply,
+                    right: Box::new(variable),
+                })
+            });
+        }
+        let derivative = match denominator {
+            Ok(denominator) => self.parse_derivative(numerator, &denominator, start, until_pipe)?,
+            Err(_) => None,
+        };
+        if derivative.is_none() {
+            self.pos = saved;
+        }
+        Ok(derivative)
+    }
+
+    // dy/dx 形式的导数和 \partial f/\partial x 形式的偏导数，其他情况为普通分数
+    fn slash_fraction(&self, start: usize, numerator: MathExpr, denominator: MathExpr) -> MathExpr {
+        if let Some((order, Some(expression))) = derivative_numerator(&numerator)
+            && let Some((variable, denominator_order)) = derivative_denominator(&denominator)
+            && order == denominator_order
+        {
+            return self.spanned(start, MathExpr::Derivative {
+                variable,
+                order,
+                expression: Box::new(expression),
+            });
+        }
+        if let Some((order, Some(expression))) = partial_numerator(&numerator)
+            && let Some(variables) = partial_denominator(&denominator)
+            && total_order(&variables) == Some(order)
+        {
+            return self.spanned(start, MathExpr::PartialDerivative {
+                variables,
+                expression: Box::new(expression),
+            });
+        }
+        self.spanned(start, MathExpr::Fraction {
+            numerator: Box::new(numerator),
+            denominator: Box::new(denominator),
+        })
+    }
+
+    fn parse_primary_or_until_pipe(&mut self, until_pipe: bool) -> Result<MathExpr, ParseError> {
+        if until_pipe {
+            self.parse_primary_until_pipe()
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    // \mathrm{name}，内容为一个标识符时结果与直接写出该标识符相同，其他内容按原文保存为 Text
+    fn parse_mathrm(&mut self) -> Result<MathExpr, ParseError> {
+        if let (Some(Token::LCurly), Some(Token::Identifier(name)), Some(Token::RCurly)) =
+            (self.peek(), self.peek_at(1), self.peek_at(2))
+        {
+            let name = name.clone();
+            self.pos += 3;
+            return Ok(MathExpr::Variable(name));
+        }
+        Ok(MathExpr::Text { command: TextCommand::Mathrm, text: self.parse_text_argument()? })
+    }
+
+    // 大型运算符的上下限：带花括号的表达式，或单个数字、字母、命令
+    fn parse_script_argument(&mut self) -> Result<MathExpr, ParseError> {
+        if self.consume(&Token::LCurly) {
+            let expr = self.parse_expression()?;
+            self.expect(&Token::RCurly)?;
+            return Ok(expr);
+        }
+
+        let (token, span) = self.next_token(&[Token::LCurly])?;
+        let start = span.start;
+        let expr = match token {
+            Token::Number(n) => MathExpr::Number(n),
+            Token::Identifier(name) => MathExpr::Variable(name),
+            // 带参数的命令必须写在花括号内
+            Token::Backslash => match self.next_token(&[Token::Identifier(String::new())])? {
+                (Token::Identifier(name), _)
+                    if !matches!(name.as_str(), "frac" | "sqrt" | "vec" | "mathbf" | "mathrm" | "text" | "operatorname" | "left" | "right")
+                        && RelationOperator::from_command(&name).is_none()
+                        && !ROW_COMMANDS.contains(&name.as_str()) =>
+                {
+                    match Symbol::from_command(&name) {
+                        Some(symbol) => MathExpr::Symbol(symbol),
+                        None => MathExpr::Variable(format!("\\{}", name)),
+                    }
+                }
+                (found, span) => {
+                    return Err(ParseError::UnexpectedToken {
+                        expected: vec![Token::Identifier(String::new())],
+                        found,
+                        span,
+                    });
+                }
+            },
+            found => {
+                return Err(ParseError::UnexpectedToken {
+                    expected: vec![Token::LCurly],
+                    found,
+                    span,
+                });
+            }
+        };
+        Ok(self.spanned(start, expr))
+    }
+
+    // 接下来是否可以开始大型运算符的主体
+    fn at_body_start(&self, until_pipe: bool) -> bool {
+        match self.peek() {
+            // 不含 -，\sum - x 仍按减法解析
+            Some(Token::Number(_) | Token::Identifier(_) | Token::LParen | Token::LBracket | Token::Begin) => true,
+            Some(Token::Pipe) => !until_pipe,
+            Some(Token::Backslash) => {
+                matches!(self.peek_at(1), Some(Token::Identifier(_)))
+                    && !self.at_right_delimiter()
+                    && !self.at_row_command()
+                    && self.peek_relation().is_none()
+            }
+            _ => false,
+        }
+    }
+
+    fn parse_body(&mut self, until_pipe: bool) -> Result<MathExpr, ParseError> {
+        if until_pipe {
+            self.parse_multiplicative_until_pipe()
+        } else {
+            self.parse_multiplicative()
+        }
+    }
+
+    fn parse_function_call_args_until_pipe(&mut self) -> Result<Vec<MathExpr>, ParseError> {
         let mut args = Vec::new();
 
         if !self.check(&Token::RParen) {
@@ -1083,24 +2039,798 @@ impl<'a> Parser<'a> {
     }
     
     // 工具函数
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.token)
+    }
+
+    // 当前位置之后第 offset 个标记
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|t| &t.token)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    // 当前标记的字节范围，输入结束时为末尾的空范围
+    fn current_span(&self) -> Range<usize> {
+        match self.tokens.get(self.pos) {
+            Some(t) => t.span.clone(),
+            None => self.input.len()..self.input.len(),
+        }
+    }
+
+    // 取出下一个标记及其位置，输入结束时报告期望的标记
+    fn next_token(&mut self, expected: &[Token]) -> Result<(Token, Range<usize>), ParseError> {
+        let span = self.current_span();
+        match self.advance() {
+            Some(token) => Ok((token, span)),
+            None => Err(ParseError::UnexpectedEof {
+                expected: expected.to_vec(),
+                span,
+            }),
+        }
+    }
+
+    // 根据当前位置的标记构造错误
+    fn error(&self, expected: &[Token]) -> ParseError {
+        let span = self.current_span();
+        match self.peek() {
+            Some(found) => ParseError::UnexpectedToken {
+                expected: expected.to_vec(),
+                found: found.clone(),
+                span,
+            },
+            None => ParseError::UnexpectedEof {
+                expected: expected.to_vec(),
+                span,
+            },
+        }
+    }
+
+    // 接下来是否为 \right，隐式乘法在此处停止，留给对应的 \left 处理
+    fn at_right_delimiter(&self) -> bool {
+        self.at_command("right")
+    }
+
+    // 接下来是否为 \label、\tag 等编号命令，隐式乘法在此处停止
+    fn at_row_command(&self) -> bool {
+        ROW_COMMANDS.iter().any(|name| self.at_command(name))
+    }
+
+    // 接下来的关系运算符: = < > 或 \le、\neq 等命令
+    fn peek_relation(&self) -> Option<RelationOperator> {
+        match self.peek()? {
+            Token::Equals => Some(RelationOperator::Equals),
+            Token::Less => Some(RelationOperator::Less),
+            Token::Greater => Some(RelationOperator::Greater),
+            Token::Backslash => match self.peek_at(1)? {
+                Token::Identifier(cmd) => RelationOperator::from_command(cmd),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn consume_relation(&mut self) -> Option<RelationOperator> {
+        let operator = self.peek_relation()?;
+        if self.advance() == Some(Token::Backslash) {
+            self.advance();
+        }
+        Some(operator)
+    }
+
+    // 接下来是否为命令 \name
+    fn at_command(&self, name: &str) -> bool {
+        self.check(&Token::Backslash) && matches!(self.peek_at(1), Some(Token::Identifier(cmd)) if cmd == name)
+    }
+
+    // \left( 对应的 \right) 或 \right]
+    fn expect_right_delimiter(&mut self) -> Result<(), ParseError> {
+        self.expect(&Token::Backslash)?;
+        let (token, span) = self.next_token(&[Token::Identifier("right".to_string())])?;
+        if token != Token::Identifier("right".to_string()) {
+            return Err(ParseError::UnexpectedToken {
+                expected: vec![Token::Identifier("right".to_string())],
+                found: token,
+                span,
+            });
+        }
+
+        match self.peek() {
+            Some(Token::RParen) | Some(Token::RBracket) => {
+                self.advance();
+                Ok(())
+            }
+            _ => Err(self.error(&[Token::RParen, Token::RBracket])),
+        }
+    }
+
+    // 最近一个已消耗标记的起始位置
+    fn last_start(&self) -> usize {
+        match self.pos.checked_sub(1).and_then(|i| self.tokens.get(i)) {
+            Some(t) => t.span.start,
+            None => 0,
+        }
+    }
+
+    // 启用位置记录时，用 start 到上一个已消耗标记末尾的范围包裹节点
+    fn spanned(&self, start: usize, expr: MathExpr) -> MathExpr {
+        if !self.spans {
+            return expr;
+        }
+
+        let end = match self.pos.checked_sub(1).and_then(|i| self.tokens.get(i)) {
+            Some(t) => t.span.end.max(start),
+            None => start,
+        };
+        MathExpr::Spanned {
+            span: start..end,
+            expr: Box::new(expr),
+        }
+    }
+
     fn consume(&mut self, expected: &Token) -> bool {
         if self.check(expected) {
-            self.tokens.next();
+            self.advance();
             true
         } else {
             false
         }
     }
 
-    fn check(&mut self, expected: &Token) -> bool {
-        self.tokens.peek() == Some(expected)
+    fn check(&self, expected: &Token) -> bool {
+        self.peek() == Some(expected)
     }
 
-    fn expect(&mut self, expected: &Token) -> Result<(), String> {
+    fn expect(&mut self, expected: &Token) -> Result<(), ParseError> {
         if self.consume(expected) {
             Ok(())
         } else {
-            Err(format!("Expected {:?}, found {:?}", expected, self.tokens.peek()))
+            Err(self.error(std::slice::from_ref(expected)))
         }
     }
-}
\ No newline at end of file
+}
+
+// 参数按原文保存的命令，其中可以出现无法识别的字符
+fn is_raw_text_command(name: &str) -> bool {
+    matches!(name, "label" | "tag") || TextCommand::from_name(name).is_some()
+}
+
+fn is_known_command(name: &str) -> bool {
+    is_function_command(name) || OPERATOR_COMMANDS.contains(&name) || Symbol::from_command(name).is_some()
+}
+
+// 不带反斜杠的命令名是否为函数名，如 sin、log
+pub(crate) fn is_function_command(name: &str) -> bool {
+    FUNCTION_COMMANDS.contains(&name)
+}
+
+// 可以作为变量名的节点：变量或希腊字母等符号
+fn variable_name(expr: &MathExpr) -> Option<String> {
+    match expr.unspanned() {
+        MathExpr::Variable(name) => Some(name.clone()),
+        MathExpr::Symbol(symbol) if symbol.kind == SymbolKind::Letter => Some(symbol.command()),
+        _ => None,
+    }
+}
+
+// 把求和下标拆分为索引变量和下限
+fn split_index(subscript: MathExpr) -> (Option<String>, Option<Box<MathExpr>>) {
+    if let Some(name) = variable_name(&subscript) {
+        return (Some(name), None);
+    }
+    if let MathExpr::BinaryOp { left, operator: BinaryOperator::Equals, right } = subscript.unspanned()
+        && let Some(name) = variable_name(left)
+    {
+        return (Some(name), Some(right.clone()));
+    }
+    (None, Some(Box::new(subscript)))
+}
+
+// 从表达式的末尾取出微分 dx、d x、d\theta 或 dxdy，返回剩余部分和其中的积分变量，
+// 剩余部分为 None 表示整个表达式都是微分。不带花括号的指数会吸收其后的元素，
+// 因此 x^2 dx 被解析为 x^{2 dx}，需要进入指数和下标中查找
+fn split_differential(expr: &MathExpr) -> Option<(Option<MathExpr>, Vec<String>)> {
+    match expr {
+        MathExpr::Spanned { expr, .. } => split_differential(expr),
+        MathExpr::Variable(name) => {
+            let letters = name.strip_prefix('d')?;
+            let variables: Vec<String> = letters.split('d').map(str::to_string).collect();
+            let valid = letters.len() == 2 * variables.len() - 1
+                && variables.iter().all(|v| v.len() == 1 && v.chars().all(|c| c.is_ascii_alphabetic()));
+            valid.then_some((None, variables))
+        }
+        // 点积之后的微分与乘法相同，如 \int x \cdot y\,dx；点积的右侧只有微分时由 parse_integral 处理
+        MathExpr::BinaryOp { left, operator: operator @ (BinaryOperator::Multiply | BinaryOperator::DotProduct), right } => {
+            // 分开书写的 d x 和 d\theta
+            if *operator == BinaryOperator::Multiply
+                && let Some(variable) = differential_variable(right)
+            {
+                match left.unspanned() {
+                    MathExpr::Variable(d) if d == "d" => return Some((None, vec![variable])),
+                    MathExpr::BinaryOp { left: rest, operator: BinaryOperator::Multiply, right: d }
+                        if *d.unspanned() == MathExpr::Variable("d".to_string()) =>
+                    {
+                        return Some((Some((**rest).clone()), vec![variable]));
+                    }
+                    _ => {}
+                }
+            }
+            let (rest, variables) = split_differential(right)?;
+            let expr = match rest {
+                Some(rest) => joined(left, &rest, MathExpr::BinaryOp {
+                    left: left.clone(),
+                    operator: operator.clone(),
+                    right: Box::new(rest.clone()),
+                }),
+                None if *operator == BinaryOperator::DotProduct => return None,
+                None => (**left).clone(),
+            };
+            Some((Some(expr), variables))
+        }
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Power, right } => {
+            let (rest, variables) = split_differential(right)?;
+            let rest = rest?;
+            Some((Some(joined(left, &rest, MathExpr::BinaryOp {
+                left: left.clone(),
+                operator: BinaryOperator::Power,
+                right: Box::new(rest.clone()),
+            })), variables))
+        }
+        MathExpr::Subscript { base, subscript } => {
+            let (rest, variables) = split_differential(subscript)?;
+            let rest = rest?;
+            Some((Some(joined(base, &rest, MathExpr::Subscript {
+                base: base.clone(),
+                subscript: Box::new(rest.clone()),
+            })), variables))
+        }
+        MathExpr::Superscript { base, superscript } => {
+            let (rest, variables) = split_differential(superscript)?;
+            let rest = rest?;
+            Some((Some(joined(base, &rest, MathExpr::Superscript {
+                base: base.clone(),
+                superscript: Box::new(rest.clone()),
+            })), variables))
+        }
+        _ => None,
+    }
+}
+
+// d 之后的积分变量：d 以外的单个字母、\theta 这样的希腊字母或 \vec{r}、\mathbf{r}
+fn differential_variable(expr: &MathExpr) -> Option<String> {
+    match expr.unspanned() {
+        MathExpr::Variable(name)
+            if (name.len() == 1 && name != "d" && name.chars().all(|c| c.is_ascii_alphabetic()))
+                || name.starts_with("\\vec{")
+                || name.starts_with("\\mathbf{") =>
+        {
+            Some(name.clone())
+        }
+        MathExpr::Symbol(symbol) if symbol.kind == SymbolKind::Letter => Some(symbol.command()),
+        _ => None,
+    }
+}
+
+// 导数的分子：d、df、d f、d^2、d^2 y、d(...)，返回阶数和被求导的表达式，算子形式时表达式为 None
+fn derivative_numerator(expr: &MathExpr) -> Option<(u32, Option<MathExpr>)> {
+    match expr.unspanned() {
+        MathExpr::Variable(name) if name == "d" => Some((1, None)),
+        MathExpr::Variable(name) => {
+            let rest = differential_rest(name)?;
+            Some((1, Some(after_d(expr, MathExpr::Variable(rest.to_string())))))
+        }
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right } => {
+            let (order, None) = derivative_numerator(left)? else {
+                return None;
+            };
+            Some((order, Some((**right).clone())))
+        }
+        MathExpr::Superscript { base, superscript } if is_d(base) => match superscript.unspanned() {
+            // 不带花括号的 d^2 y 被解析为 d^{2 y}
+            MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right } => {
+                Some((derivative_order(left)?, Some((**right).clone())))
+            }
+            _ => Some((derivative_order(superscript)?, None)),
+        },
+        MathExpr::FunctionCall { name, args } if name == "d" && args.len() == 1 => Some((1, Some(args[0].clone()))),
+        _ => None,
+    }
+}
+
+// 导数的分母：dx、d x、d\theta、dx^2、d x^2，返回求导变量和阶数
+fn derivative_denominator(expr: &MathExpr) -> Option<(String, u32)> {
+    match expr.unspanned() {
+        MathExpr::Variable(name) => Some((differential_rest(name)?.to_string(), 1)),
+        MathExpr::Superscript { base, superscript } => match base.unspanned() {
+            MathExpr::Variable(name) => Some((differential_rest(name)?.to_string(), derivative_order(superscript)?)),
+            _ => None,
+        },
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right } if is_d(left) => match right.unspanned() {
+            MathExpr::Superscript { base, superscript } => Some((derivative_variable(base)?, derivative_order(superscript)?)),
+            _ => Some((derivative_variable(right)?, 1)),
+        },
+        _ => None,
+    }
+}
+
+// 分开书写的 d x 中的求导变量：标识符或希腊字母
+fn derivative_variable(expr: &MathExpr) -> Option<String> {
+    match expr.unspanned() {
+        MathExpr::Variable(name) => name.chars().all(|c| c.is_ascii_alphanumeric()).then(|| name.clone()),
+        _ => variable_name(expr),
+    }
+}
+
+// 偏导数的分子：\partial、\partial f、\partial^2、\partial^2 f，返回阶数和被求导的表达式，算子形式时表达式为 None
+fn partial_numerator(expr: &MathExpr) -> Option<(u32, Option<MathExpr>)> {
+    match expr.unspanned() {
+        _ if is_partial(expr) => Some((1, None)),
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right } => {
+            let (order, None) = partial_numerator(left)? else {
+                return None;
+            };
+            Some((order, Some((**right).clone())))
+        }
+        MathExpr::Superscript { base, superscript } if is_partial(base) => match superscript.unspanned() {
+            // 不带花括号的 \partial^2 f 被解析为 \partial^{2 f}
+            MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right } => {
+                Some((derivative_order(left)?, Some((**right).clone())))
+            }
+            _ => Some((derivative_order(superscript)?, None)),
+        },
+        _ => None,
+    }
+}
+
+// 偏导数的分母：\partial x \partial y、\partial x^2 \partial y，返回求导变量和阶数，相邻的相同变量合并
+fn partial_denominator(expr: &MathExpr) -> Option<Vec<(String, u32)>> {
+    let mut factors = Vec::new();
+    product_factors(expr, &mut factors);
+    let mut factors = factors.into_iter();
+    let mut variables: Vec<(String, u32)> = Vec::new();
+    while let Some(partial) = factors.next() {
+        if !is_partial(&partial) {
+            return None;
+        }
+        let factor = factors.next()?;
+        let (variable, order) = match factor.unspanned() {
+            MathExpr::Superscript { base, superscript } => (derivative_variable(base)?, derivative_order(superscript)?),
+            _ => (derivative_variable(&factor)?, 1),
+        };
+        match variables.last_mut() {
+            Some((last, last_order)) if *last == variable => *last_order = last_order.checked_add(order)?,
+            _ => variables.push((variable, order)),
+        }
+    }
+    (!variables.is_empty()).then_some(variables)
+}
+
+// 把隐式乘法展开为因子序列。不带花括号的指数会吸收其后的元素，
+// \partial x^2 \partial y 被解析为 \partial x^{2 \partial y}，需要把阶数之后的部分拆出来
+fn product_factors(expr: &MathExpr, factors: &mut Vec<MathExpr>) {
+    match expr.unspanned() {
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right } => {
+            product_factors(left, factors);
+            product_factors(right, factors);
+        }
+        MathExpr::Superscript { base, superscript } => {
+            let mut exponent = Vec::new();
+            product_factors(superscript, &mut exponent);
+            if exponent.len() > 1 && derivative_order(&exponent[0]).is_some() {
+                let order = exponent.remove(0);
+                factors.push(MathExpr::Superscript {
+                    base: base.clone(),
+                    superscript: Box::new(order),
+                });
+                factors.append(&mut exponent);
+            } else {
+                factors.push(expr.clone());
+            }
+        }
+        _ => factors.push(expr.clone()),
+    }
+}
+
+// 偏导数的总阶数
+fn total_order(variables: &[(String, u32)]) -> Option<u32> {
+    variables.iter().try_fold(0u32, |total, (_, order)| total.checked_add(*order))
+}
+
+fn is_partial(expr: &MathExpr) -> bool {
+    matches!(expr.unspanned(), MathExpr::Symbol(symbol) if symbol.name == "partial")
+}
+
+// 只有一对括号时，括号仅用于分组，如 \frac{d}{dx}[x^2]、\nabla (f g)
+fn ungrouped(expr: MathExpr) -> MathExpr {
+    match expr.unspanned() {
+        MathExpr::Parenthesized(inner) => (**inner).clone(),
+        _ => expr,
+    }
+}
+
+// 标识符 dx 中 d 之后的变量名，\frac{data}{dog} 这样的多字母标识符不是微分
+fn differential_rest(name: &str) -> Option<&str> {
+    name.strip_prefix('d').filter(|rest| is_compact_differential(rest))
+}
+
+// 可以紧跟在 d 之后写成一个标识符的变量名：一个字母，后面可以跟数字，如 x、t2
+pub(crate) fn is_compact_differential(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic()) && chars.all(|c| c.is_ascii_digit())
+}
+
+fn is_d(expr: &MathExpr) -> bool {
+    matches!(expr.unspanned(), MathExpr::Variable(name) if name == "d")
+}
+
+// 阶数为正整数
+fn derivative_order(expr: &MathExpr) -> Option<u32> {
+    match expr.unspanned() {
+        MathExpr::Number(n) if *n >= 1.0 && n.fract() == 0.0 && *n <= u32::MAX as f64 => Some(*n as u32),
+        _ => None,
+    }
+}
+
+// 从标识符 df 中取出的 f 使用去掉 d 之后的位置
+fn after_d(identifier: &MathExpr, expr: MathExpr) -> MathExpr {
+    match identifier.span() {
+        Some(span) => MathExpr::Spanned {
+            span: span.start + 1..span.end,
+            expr: Box::new(expr),
+        },
+        None => expr,
+    }
+}
+
+// 没有被求导表达式的 \frac{d}{dx} 和 \frac{\partial}{\partial x}，其后的元素会被当作被求导的表达式
+pub(crate) fn is_derivative_operator(numerator: &MathExpr, denominator: &MathExpr) -> bool {
+    let derivative = matches!(
+        (derivative_numerator(numerator), derivative_denominator(denominator)),
+        (Some((order, None)), Some((_, denominator_order))) if order == denominator_order
+    );
+    let partial = matches!(
+        (partial_numerator(numerator), partial_denominator(denominator)),
+        (Some((order, None)), Some(variables)) if total_order(&variables) == Some(order)
+    );
+    derivative || partial
+}
+
+// 重新组合的节点使用首尾两部分的位置
+fn joined(first: &MathExpr, last: &MathExpr, expr: MathExpr) -> MathExpr {
+    match (first.span(), last.span()) {
+        (Some(first), Some(last)) => MathExpr::Spanned {
+            span: first.start..last.end,
+            expr: Box::new(expr),
+        },
+        _ => expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> MathExpr {
+        Parser::new(source).parse().unwrap()
+    }
+
+    #[test]
+    fn derivative_fraction() {
+        assert!(matches!(parse("\\frac{dy}{dx}"), MathExpr::Derivative { ref variable, .. } if variable == "x"));
+        assert!(matches!(parse("\\frac{dy}{dt2}"), MathExpr::Derivative { ref variable, .. } if variable == "t2"));
+        assert!(matches!(parse("\\frac{\\mathrm{d}y}{\\mathrm{d}x}"), MathExpr::Derivative { .. }));
+    }
+
+    // 变量不能与 d 写成一个标识符时，输出的 LaTeX 在 d 之后加空格
+    #[test]
+    fn derivative_roundtrip() {
+        for source in ["\\frac{d dx}{dx}", "\\frac{dy}{d ab}", "\\frac{d^2 dx}{dx^2}", "\\frac{dy}{d\\theta}"] {
+            let expr = parse(source);
+            assert!(matches!(expr, MathExpr::Derivative { .. }), "{}", source);
+            assert_eq!(parse(&expr.to_latex()), expr, "{}", source);
+        }
+    }
+
+    // 交换图中的 @. 不是无法识别的字符
+    #[test]
+    fn strict_commutative_diagram() {
+        let source = "\\begin{CD} A @>f>> B \\\\ @. @VVgV \\\\ C @= D \\end{CD}";
+        assert!(matches!(Parser::new(source).strict(true).parse(), Ok(MathExpr::Matrix { .. })));
+    }
+
+    // 默认模式下无法识别的字符同样报错，而不是只返回之前能解析的部分
+    #[test]
+    fn invalid_character() {
+        for (source, text) in [("a % b", "%"), ("a ? b + c", "?"), ("x + y ~ z", "~"), ("α + β", "α")] {
+            for strict in [false, true] {
+                let result = Parser::new(source).strict(strict).parse();
+                assert!(matches!(result, Err(ParseError::InvalidCharacter { text: ref found, .. }) if found == text), "{}", source);
+            }
+        }
+        assert!(Parser::new("\\begin{align} x &= 1 \\label{eq:α} \\end{align}").parse().is_ok());
+    }
+
+    // \text、\mathrm 和 \operatorname 的内容按原文保存，其中的字符不报错
+    #[test]
+    fn text_argument() {
+        let text = |command, text: &str| MathExpr::Text { command, text: text.to_string() };
+        for strict in [false, true] {
+            let parse = |source: &str| Parser::new(source).strict(strict).parse().unwrap();
+            assert_eq!(parse("\\text{x's}"), text(TextCommand::Text, "x's"));
+            assert_eq!(parse("\\text{otherwise.}"), text(TextCommand::Text, "otherwise."));
+            assert_eq!(parse("\\mathrm{m/s}"), text(TextCommand::Mathrm, "m/s"));
+            assert_eq!(parse("\\mathrm{d}"), MathExpr::Variable("d".to_string()));
+            let MathExpr::BinaryOp { left, .. } = parse("\\operatorname{sgn}(x)") else {
+                panic!("expected a product");
+            };
+            assert_eq!(*left, text(TextCommand::Operatorname, "sgn"));
+        }
+        assert!(matches!(Parser::new("\\text{a} %").parse(), Err(ParseError::InvalidCharacter { .. })));
+        assert!(matches!(Parser::new("\\text{a").parse(), Err(ParseError::UnexpectedEof { .. })));
+    }
+
+    // 公式组按行保存各列，行末的 \label、\tag、\nonumber 属于该行
+    #[test]
+    fn equation_system() {
+        let source = "\\begin{align} x &= 1 \\label{eq:a} \\\\ y &= 2 \\tag{*} \\\\ &= 3 \\nonumber \\end{align}";
+        let expr = parse(source);
+        let MathExpr::EquationSystem { environment, rows } = &expr else {
+            panic!("expected an equation system, found {:?}", expr);
+        };
+        assert_eq!(*environment, Environment::Align { starred: false });
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(|row| row.cells.len() == 2));
+        assert_eq!(rows[0].label.as_deref(), Some("eq:a"));
+        assert_eq!(rows[1].tag.as_deref(), Some("*"));
+        assert!(rows[2].nonumber && rows[2].cells[0] == MathExpr::Empty);
+        assert_eq!(expr.to_latex(), "\\begin{align} x & = 1 \\label{eq:a} \\\\ y & = 2 \\tag{*} \\\\ & = 3 \\nonumber \\end{align}");
+
+        let MathExpr::EquationSystem { rows, .. } = parse("\\begin{eqnarray} a &=& b \\end{eqnarray}") else {
+            panic!("expected an equation system");
+        };
+        assert_eq!(rows[0].cells.len(), 3);
+        let MathExpr::EquationSystem { environment, rows } = parse("\\begin{gather*} a = b \\\\ c \\end{gather*}") else {
+            panic!("expected an equation system");
+        };
+        assert_eq!(environment, Environment::Gather { starred: true });
+        assert_eq!(rows.len(), 2);
+    }
+
+    // cases 环境的每一行为 (值, 条件)，\text{otherwise} 和省略的条件为 None
+    #[test]
+    fn piecewise() {
+        let MathExpr::BinaryOp { right, operator: BinaryOperator::Equals, .. } =
+            parse("f(x) = \\begin{cases} x & x \\ge 0 \\\\ -x & x < 0 \\end{cases}")
+        else {
+            panic!("expected an equation");
+        };
+        let MathExpr::Piecewise { branches } = *right else {
+            panic!("expected a piecewise function, found {:?}", right);
+        };
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[1].0.to_string(), "-x");
+        assert_eq!(branches[1].1.as_ref().map(|condition| condition.to_latex()), Some("x < 0".to_string()));
+
+        let expr = parse("\\begin{cases} 1 & x > 0 \\\\ 0 & \\text{otherwise} \\end{cases}");
+        assert_eq!(expr, parse("\\begin{cases} 1 & x > 0 \\\\ 0 \\end{cases}"));
+        assert_eq!(expr.to_latex(), "\\begin{cases} 1 & x > 0 \\\\ 0 & \\text{otherwise} \\end{cases}");
+    }
+
+    // 分支末尾的标点，以及条件中的 \text{...} 正文
+    #[test]
+    fn piecewise_prose() {
+        let branches = |source: &str| match parse(source) {
+            MathExpr::Piecewise { branches } => branches,
+            expr => panic!("expected a piecewise function, found {:?}", expr),
+        };
+        let parsed = branches("\\begin{cases} x, & x \\ge 0 \\\\ -x, & x < 0. \\end{cases}");
+        assert_eq!(parsed.len(), 2);
+        assert!(parsed.iter().all(|(_, condition)| matches!(condition, Some(MathExpr::Relation { .. }))));
+
+        let parsed = branches(
+            "\\begin{cases} 1 & \\text{for } x>0 \\\\ 2 & \\text{if } x \\text{ is even} \\\\ 0 & \\text{otherwise.} \\end{cases}",
+        );
+        let condition = |i: usize| parsed[i].1.as_ref().unwrap().to_latex();
+        assert_eq!(condition(0), "\\text{for } x > 0");
+        assert_eq!(condition(1), "\\text{if } x \\text{ is even}");
+        assert_eq!(parsed[2].1, None);
+
+        assert!(matches!(Parser::new("\\begin{cases} 1 & x :\\end{cases}").parse(), Err(ParseError::InvalidCharacter { .. })));
+        assert!(Parser::new("\\begin{cases} 1, 2 & x \\end{cases}").parse().is_err());
+    }
+
+    // 连续的比较解析为一个关系链，优先级低于加减法
+    #[test]
+    fn relation_chain() {
+        let var = |name: &str| MathExpr::Variable(name.to_string());
+        assert_eq!(
+            parse("0 \\le x < 1"),
+            MathExpr::Relation {
+                operands: vec![MathExpr::Number(0.0), var("x"), MathExpr::Number(1.0)],
+                operators: vec![RelationOperator::LessEqual, RelationOperator::Less],
+            }
+        );
+        let MathExpr::Relation { operands, operators } = parse("a + b \\leq c") else {
+            panic!("expected a relation");
+        };
+        assert!(matches!(operands[0], MathExpr::BinaryOp { operator: BinaryOperator::Add, .. }));
+        assert_eq!(operators, [RelationOperator::LessEqual]);
+
+        let operators = |source: &str| match parse(source) {
+            MathExpr::Relation { operators, .. } => operators,
+            expr => panic!("expected a relation, found {:?}", expr),
+        };
+        assert_eq!(operators("x \\neq y"), [RelationOperator::NotEquals]);
+        assert_eq!(operators("a \\geq b > c"), [RelationOperator::GreaterEqual, RelationOperator::Greater]);
+        assert_eq!(operators("a \\approx b \\equiv c"), [RelationOperator::Approx, RelationOperator::Equiv]);
+        assert_eq!(operators("f \\sim g \\propto h"), [RelationOperator::Sim, RelationOperator::Propto]);
+        assert_eq!(operators("x = y = z"), [RelationOperator::Equals, RelationOperator::Equals]);
+        // 只有一个 = 时仍为等式
+        assert!(matches!(parse("x = 1"), MathExpr::BinaryOp { operator: BinaryOperator::Equals, .. }));
+    }
+
+    // 已知的符号命令带有类别和 Unicode 字符，未知命令在严格模式下报错
+    #[test]
+    fn symbols() {
+        let symbol = |source: &str| match parse(source) {
+            MathExpr::Symbol(symbol) => (symbol.name, symbol.kind, symbol.unicode),
+            expr => panic!("expected a symbol, found {:?}", expr),
+        };
+        assert_eq!(symbol("\\alpha"), ("alpha".to_string(), SymbolKind::Letter, 'α'));
+        assert_eq!(symbol("\\Omega"), ("Omega".to_string(), SymbolKind::Letter, 'Ω'));
+        assert_eq!(symbol("\\pi"), ("pi".to_string(), SymbolKind::Constant, 'π'));
+        assert_eq!(symbol("\\infty"), ("infty".to_string(), SymbolKind::Constant, '∞'));
+        assert_eq!(symbol("\\exists"), ("exists".to_string(), SymbolKind::Operator, '∃'));
+        assert_eq!(Symbol::from_command("alpha").unwrap().command(), "\\alpha");
+        assert_eq!(Symbol::from_command("foo"), None);
+
+        assert_eq!(parse("\\foo"), MathExpr::Variable("\\foo".to_string()));
+        let strict = |source: &str| Parser::new(source).strict(true).parse();
+        assert!(matches!(strict("x + \\foo"), Err(ParseError::UnknownCommand { ref name, .. }) if name == "foo"));
+        assert!(strict("\\sin(\\theta) + \\hbar").is_ok());
+    }
+
+    // 偏导数记录各变量的阶数，分子的阶数与分母不一致时为普通分数
+    #[test]
+    fn partial_derivative() {
+        let f = || Box::new(MathExpr::Variable("f".to_string()));
+        let partial = |variables: &[(&str, u32)]| MathExpr::PartialDerivative {
+            variables: variables.iter().map(|(v, n)| (v.to_string(), *n)).collect(),
+            expression: f(),
+        };
+        assert_eq!(parse("\\frac{\\partial f}{\\partial x}"), partial(&[("x", 1)]));
+        assert_eq!(parse("\\frac{\\partial^2 f}{\\partial x \\partial y}"), partial(&[("x", 1), ("y", 1)]));
+        assert_eq!(parse("\\frac{\\partial^2 f}{\\partial x^2}"), partial(&[("x", 2)]));
+        assert!(matches!(parse("\\frac{\\partial^3 f}{\\partial x \\partial y}"), MathExpr::Fraction { .. }));
+    }
+
+    #[test]
+    fn differential_operators() {
+        let operator = |source: &str| match parse(source) {
+            MathExpr::DifferentialOperator { kind, operand } => (kind, *operand),
+            expr => panic!("expected a differential operator, found {:?}", expr),
+        };
+        let var = |name: &str| MathExpr::Variable(name.to_string());
+        assert_eq!(operator("\\nabla f"), (DifferentialOperatorKind::Gradient, var("f")));
+        assert_eq!(operator("\\nabla \\cdot F"), (DifferentialOperatorKind::Divergence, var("F")));
+        assert_eq!(operator("\\nabla \\times B"), (DifferentialOperatorKind::Curl, var("B")));
+        assert_eq!(operator("\\nabla^2 u"), (DifferentialOperatorKind::Laplacian, var("u")));
+        assert_eq!(operator("\\Delta u"), (DifferentialOperatorKind::Laplacian, var("u")));
+        // 梯度只作用于其后的幂运算表达式
+        let MathExpr::BinaryOp { left, operator: BinaryOperator::DotProduct, .. } = parse("\\nabla f \\cdot \\vec{v}") else {
+            panic!("expected a dot product");
+        };
+        assert!(matches!(*left, MathExpr::DifferentialOperator { kind: DifferentialOperatorKind::Gradient, .. }));
+    }
+
+    // 不带花括号的上下标只取一个元素
+    #[test]
+    fn unbraced_scripts() {
+        assert_eq!(parse("x^2 y").to_string(), "(x^{2} * y)");
+        assert_eq!(parse("m_1 m_2").to_string(), "(m[1] * m[2])");
+        assert_eq!(parse("x^-1").to_string(), "x^{-1}");
+        assert_eq!(parse("|x^2 + y^2|").to_string(), "|(x^{2} + y^{2})|");
+        assert_eq!(parse("x_1^2").to_string(), "x[1]^{2}");
+        assert_eq!(parse("x^2^3").to_string(), "x^{2^{3}}");
+        assert_eq!(parse("\\int_0^1 x dx").to_string(), "\\int_{0}^{1}(x) dx");
+    }
+
+    // 撇号和 f^{(3)} 解析为 Prime，操作数保留为语法树
+    #[test]
+    fn prime() {
+        let var = |name: &str| Box::new(MathExpr::Variable(name.to_string()));
+        let prime = |expr: Box<MathExpr>, order: u32| MathExpr::Prime { expr, order };
+        assert_eq!(parse("f'"), prime(var("f"), 1));
+        assert_eq!(parse("f'''"), prime(var("f"), 3));
+        assert_eq!(parse("f^{(3)}"), prime(var("f"), 3));
+        let call = |name: &str| Box::new(MathExpr::FunctionCall { name: name.to_string(), args: vec![MathExpr::Variable("x".to_string())] });
+        assert_eq!(parse("y'(x)"), prime(call("y"), 1));
+        assert_eq!(parse("f''(x)"), prime(call("f"), 2));
+        let MathExpr::Prime { expr, order: 1 } = parse("(x^2)'") else {
+            panic!("expected a prime");
+        };
+        assert!(matches!(*expr, MathExpr::Parenthesized(ref inner) if matches!(**inner, MathExpr::Superscript { .. })));
+        // 阶数不是数字时仍为上标
+        assert!(matches!(parse("f^{(n)}"), MathExpr::Superscript { .. }));
+        assert_eq!(parse("f''(x)").to_latex(), "f''(x)");
+    }
+
+    // 积分的上下限、被积函数和末尾微分中的积分变量
+    #[test]
+    fn integral() {
+        let integral = |source: &str| match parse(source) {
+            MathExpr::Integral { kind, lower, upper, integrand, variables, .. } => (kind, lower, upper, *integrand, variables),
+            expr => panic!("expected an integral, found {:?}", expr),
+        };
+        let (kind, lower, upper, integrand, variables) = integral("\\int_0^1 x^2 dx");
+        assert_eq!(kind, IntegralKind::Single);
+        assert_eq!((lower, upper), (Some(Box::new(MathExpr::Number(0.0))), Some(Box::new(MathExpr::Number(1.0)))));
+        assert!(matches!(integrand, MathExpr::Superscript { .. }));
+        assert_eq!(variables, ["x"]);
+
+        assert_eq!(integral("\\int f(x) \\, dx").4, ["x"]);
+        assert_eq!(integral("\\int_0^{2\\pi} \\sin\\theta d\\theta").4, ["\\theta"]);
+        // 只有微分时被积函数为 1
+        let (_, _, _, integrand, variables) = integral("\\int_{a}^{b} \\mathrm{d}x");
+        assert_eq!((integrand, variables), (MathExpr::Number(1.0), vec!["x".to_string()]));
+        // 没有写出微分
+        assert!(integral("\\int x").4.is_empty());
+        // 没有被积函数的积分号
+        assert_eq!(parse("\\int"), MathExpr::Variable("\\int".to_string()));
+    }
+
+    // 重积分的微分个数不超过积分的重数
+    #[test]
+    fn multiple_integral() {
+        let integral = |source: &str| match parse(source) {
+            MathExpr::Integral { kind, lower, variables, .. } => (kind, lower, variables),
+            expr => panic!("expected an integral, found {:?}", expr),
+        };
+        assert_eq!(
+            integral("\\iint_D f\\,dx\\,dy"),
+            (IntegralKind::Double, Some(Box::new(MathExpr::Variable("D".to_string()))), vec!["x".to_string(), "y".to_string()])
+        );
+        assert_eq!(integral("\\iiint x dx dy dz").2, ["x", "y", "z"]);
+        assert_eq!(integral("\\iint x dx").2, ["x"]);
+        assert_eq!(integral("\\oint_C f ds").0, IntegralKind::Contour);
+    }
+
+    // 点积之后的微分是积分变量，如曲线积分 \oint_C \mathbf{F} \cdot d\mathbf{r}
+    #[test]
+    fn dot_product_differential() {
+        let integral = |source: &str| match parse(source) {
+            MathExpr::Integral { integrand, variables, dot_product, .. } => (*integrand, variables, dot_product),
+            expr => panic!("expected an integral, found {:?}", expr),
+        };
+        let (integrand, variables, dot_product) = integral("\\oint_C \\mathbf{F} \\cdot d\\mathbf{r}");
+        assert_eq!(integrand, MathExpr::Variable("\\mathbf{F}".to_string()));
+        assert_eq!(variables, ["\\mathbf{r}"]);
+        assert!(dot_product);
+
+        let (integrand, variables, dot_product) = integral("\\oint_C F \\cdot dr");
+        assert_eq!((integrand, variables, dot_product), (MathExpr::Variable("F".to_string()), vec!["r".to_string()], true));
+
+        // 点积在被积函数内部时，微分之前仍为乘法
+        let (integrand, variables, dot_product) = integral("\\int x \\cdot y\\,dx");
+        assert!(matches!(integrand, MathExpr::BinaryOp { operator: BinaryOperator::DotProduct, .. }));
+        assert_eq!(variables, ["x"]);
+        assert!(!dot_product);
+
+        let expr = parse("\\oint_C \\mathbf{F} \\cdot d\\mathbf{r}");
+        assert_eq!(expr.to_latex(), "\\oint_{C} \\mathbf{F} \\cdot d\\mathbf{r}");
+    }
+
+    // 以 d 开头的多字母标识符是普通分数
+    #[test]
+    fn multi_letter_fraction() {
+        for source in ["\\frac{dist}{dur}", "\\frac{data}{dog}"] {
+            assert!(matches!(parse(source), MathExpr::Fraction { .. }), "{}", source);
+        }
+    }
+}
@@ -0,0 +1,759 @@
+use crate::ast::{BinaryOperator, MathExpr, UnaryOperator};
+
+// 代数化简
+
+// 一次自底向上的化简可能产生新的化简机会，最多重复的次数
+const MAX_PASSES: usize = 16;
+
+// 化简规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    // 常数折叠: 2 + 3 → 5，x 2 3 → 6 x，\frac{6}{3} → 2，\frac{1}{2} + \frac{1}{3} → \frac{5}{6}
+    ConstantFolding,
+    // 恒等式: x 1 → x，x + 0 → x，x^1 → x，0 x → 0，--x → x
+    Identities,
+    // 合并同类项: 2 x + 3 x → 5 x，x y - y x → 0
+    LikeTerms,
+    // 展平嵌套的加法和乘法: a + (b + c) → a + b + c，a - (b - c) → a - b + c
+    Flatten,
+    // 去掉括号节点，输出时按需要重新加上括号；上下标的底数本身带有上下标时保留括号，如 (a^b)^c
+    Parentheses,
+    // 约分: \frac{6}{4} → \frac{3}{2}，\frac{2 x^3}{4 x} → \frac{x^2}{2}
+    Fractions,
+    // 合并同底数的幂: x x^2 → x^3，(x^2)^3 → x^6
+    Powers,
+}
+
+impl Rule {
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+// 启用的化简规则，默认启用全部规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleSet {
+    bits: u8,
+}
+
+impl RuleSet {
+    pub fn all() -> Self {
+        RuleSet { bits: u8::MAX }
+    }
+
+    pub fn none() -> Self {
+        RuleSet { bits: 0 }
+    }
+
+    pub fn with(mut self, rule: Rule) -> Self {
+        self.bits |= rule.bit();
+        self
+    }
+
+    pub fn without(mut self, rule: Rule) -> Self {
+        self.bits &= !rule.bit();
+        self
+    }
+
+    pub fn contains(&self, rule: Rule) -> bool {
+        self.bits & rule.bit() != 0
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet::all()
+    }
+}
+
+// 按 rules 中的规则化简表达式，结果不含位置信息
+pub fn simplify(expr: &MathExpr, rules: RuleSet) -> MathExpr {
+    let mut expr = expr.without_spans();
+    for _ in 0..MAX_PASSES {
+        let next = simplify_tree(expr.clone(), rules);
+        if next == expr {
+            break;
+        }
+        expr = next;
+    }
+    expr
+}
+
+// 先化简子节点，再化简节点本身
+fn simplify_tree(mut expr: MathExpr, rules: RuleSet) -> MathExpr {
+    expr.for_each_child_mut(&mut |child| {
+        let taken = std::mem::replace(child, MathExpr::Number(0.0));
+        *child = simplify_tree(taken, rules);
+    });
+    simplify_node(expr, rules)
+}
+
+fn simplify_node(expr: MathExpr, rules: RuleSet) -> MathExpr {
+    match expr {
+        MathExpr::Parenthesized(inner) if rules.contains(Rule::Parentheses) => *inner,
+        MathExpr::BinaryOp { operator: BinaryOperator::Add | BinaryOperator::Subtract, .. } => sum(expr, rules),
+        MathExpr::BinaryOp { operator: BinaryOperator::Multiply | BinaryOperator::DotProduct, .. } => product(expr, rules),
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Divide, right } => fraction(*left, *right, false, rules),
+        MathExpr::Fraction { numerator, denominator } => fraction(*numerator, *denominator, true, rules),
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Power, right } => power(*left, *right, false, rules),
+        MathExpr::Superscript { base, superscript } => power(*base, *superscript, true, rules),
+        MathExpr::UnaryOp { operator, expr } => unary(operator, *expr, rules),
+        MathExpr::Root { radicand, index } => root(*radicand, index.map(|index| *index), rules),
+        MathExpr::Subscript { base, subscript } => {
+            let nested = matches!(*base, MathExpr::Subscript { .. } | MathExpr::Superscript { .. });
+            MathExpr::Subscript { base: Box::new(script_base(*base, nested)), subscript }
+        }
+        expr => expr,
+    }
+}
+
+// 加减法链拆分为带符号的项；不展平时只沿左侧的链拆分，与解析 a + b - c 得到的结构对应
+fn collect_terms(expr: MathExpr, negated: bool, flatten: bool, terms: &mut Vec<(bool, MathExpr)>) {
+    match expr {
+        MathExpr::BinaryOp { left, operator: operator @ (BinaryOperator::Add | BinaryOperator::Subtract), right } => {
+            collect_terms(*left, negated, flatten, terms);
+            let negated_right = negated != matches!(operator, BinaryOperator::Subtract);
+            if flatten {
+                collect_terms(*right, negated_right, flatten, terms);
+            } else {
+                terms.push((negated_right, *right));
+            }
+        }
+        MathExpr::Parenthesized(inner)
+            if flatten && matches!(*inner, MathExpr::BinaryOp { operator: BinaryOperator::Add | BinaryOperator::Subtract, .. }) =>
+        {
+            collect_terms(*inner, negated, flatten, terms);
+        }
+        expr => terms.push((negated, expr)),
+    }
+}
+
+// 乘法链拆分为因子，规则与 collect_terms 相同；与 evaluate 一致，\cdot 也按数的乘法处理，
+// 不展平时只拆分与 operator 相同的运算
+fn collect_factors(expr: MathExpr, operator: &BinaryOperator, flatten: bool, factors: &mut Vec<MathExpr>) {
+    match expr {
+        MathExpr::BinaryOp { left, operator: inner @ (BinaryOperator::Multiply | BinaryOperator::DotProduct), right }
+            if flatten || inner == *operator =>
+        {
+            collect_factors(*left, operator, flatten, factors);
+            if flatten {
+                collect_factors(*right, operator, flatten, factors);
+            } else {
+                factors.push(*right);
+            }
+        }
+        MathExpr::Parenthesized(inner)
+            if flatten
+                && matches!(*inner, MathExpr::BinaryOp { operator: BinaryOperator::Multiply | BinaryOperator::DotProduct, .. }) =>
+        {
+            collect_factors(*inner, operator, flatten, factors);
+        }
+        expr => factors.push(expr),
+    }
+}
+
+fn sum(expr: MathExpr, rules: RuleSet) -> MathExpr {
+    let mut terms = Vec::new();
+    collect_terms(expr, false, rules.contains(Rule::Flatten), &mut terms);
+
+    if rules.contains(Rule::ConstantFolding) && !fold_rational_terms(&mut terms) {
+        fold_constant_terms(&mut terms);
+    }
+    if rules.contains(Rule::LikeTerms) {
+        collect_like_terms(&mut terms);
+    }
+    if rules.contains(Rule::Identities) && terms.len() > 1 {
+        terms.retain(|(_, term)| constant(term) != Some(0.0));
+    }
+    build_sum(terms, rules.contains(Rule::Identities))
+}
+
+// 两个以上的常数项合并为一项，放在第一个常数项的位置
+fn fold_constant_terms(terms: &mut Vec<(bool, MathExpr)>) {
+    let constants: Vec<usize> = (0..terms.len()).filter(|&i| constant(&terms[i].1).is_some()).collect();
+    if constants.len() < 2 {
+        return;
+    }
+    let total: f64 = constants
+        .iter()
+        .map(|&i| {
+            let value = constant(&terms[i].1).unwrap_or(0.0);
+            if terms[i].0 { -value } else { value }
+        })
+        .sum();
+    terms[constants[0]] = (total < 0.0, MathExpr::Number(total.abs()));
+    for &i in constants[1..].iter().rev() {
+        terms.remove(i);
+    }
+}
+
+// 常数项都是整数或整数之比的分数且其中有分数时，按分数精确相加，如 \frac{1}{2} + \frac{1}{3} → \frac{5}{6}；
+// 含有小数或计算超出 f64 能精确表示的整数范围时返回 false，交给 fold_constant_terms
+fn fold_rational_terms(terms: &mut Vec<(bool, MathExpr)>) -> bool {
+    let constants: Vec<usize> =
+        (0..terms.len()).filter(|&i| constant(&terms[i].1).is_some() || rational(&terms[i].1).is_some()).collect();
+    let has_fraction = constants.iter().any(|&i| constant(&terms[i].1).is_none());
+    if constants.len() < 2 || !has_fraction {
+        return false;
+    }
+    let mut total = (0.0, 1.0);
+    for &i in &constants {
+        let Some((n, d)) = rational(&terms[i].1) else {
+            return false;
+        };
+        let n = if terms[i].0 { -n } else { n };
+        let Some(sum) = reduce(total.0 * d + n * total.1, total.1 * d) else {
+            return false;
+        };
+        total = sum;
+    }
+    terms[constants[0]] = (total.0 < 0.0, rational_expr(total.0.abs(), total.1));
+    for &i in constants[1..].iter().rev() {
+        terms.remove(i);
+    }
+    true
+}
+
+// 除数值系数外相同的项合并，系数之和为 0 的项去掉；只出现一次的项保持原样
+fn collect_like_terms(terms: &mut Vec<(bool, MathExpr)>) {
+    // 每组为 (同类项的键, 系数之和, 去掉系数的部分, 在结果中的位置, 项数)
+    let mut groups: Vec<(Vec<String>, f64, MathExpr, usize, usize)> = Vec::new();
+    let mut result: Vec<Option<(bool, MathExpr)>> = Vec::new();
+    for (negated, term) in terms.drain(..) {
+        let (c, rest) = split_coefficient(&term);
+        let Some(monomial) = rest else {
+            result.push(Some((negated, term)));
+            continue;
+        };
+        let c = if negated { -c } else { c };
+        let key = monomial_key(&monomial);
+        match groups.iter_mut().find(|group| group.0 == key) {
+            Some(group) => {
+                group.1 += c;
+                group.4 += 1;
+                result.push(None);
+            }
+            None => {
+                groups.push((key, c, monomial, result.len(), 1));
+                result.push(Some((negated, term)));
+            }
+        }
+    }
+    for (_, c, monomial, index, count) in groups {
+        if count > 1 {
+            result[index] = (c != 0.0).then(|| (c < 0.0, build_product(c.abs(), vec![monomial], &BinaryOperator::Multiply)));
+        }
+    }
+    terms.extend(result.into_iter().flatten());
+}
+
+// 拆分出数值系数: 2 x y → (2, x y)，-x → (-1, x)，3 → (3, 无)
+fn split_coefficient(term: &MathExpr) -> (f64, Option<MathExpr>) {
+    let (c, factors) = split_product(term.clone());
+    let rest = factors.into_iter().reduce(|left, right| binary(left, BinaryOperator::Multiply, right));
+    (c, rest)
+}
+
+// 乘积拆分为数值系数和其余因子
+fn split_product(expr: MathExpr) -> (f64, Vec<MathExpr>) {
+    if let Some(c) = constant(&expr) {
+        return (c, Vec::new());
+    }
+    match expr {
+        MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr } => {
+            let (c, factors) = split_product(*expr);
+            (-c, factors)
+        }
+        MathExpr::BinaryOp { operator: BinaryOperator::Multiply | BinaryOperator::DotProduct, .. } => {
+            let mut all = Vec::new();
+            collect_factors(expr, &BinaryOperator::Multiply, true, &mut all);
+            let mut c = 1.0;
+            let mut factors = Vec::new();
+            for factor in all {
+                match constant(&factor) {
+                    Some(value) => c *= value,
+                    None => factors.push(factor),
+                }
+            }
+            (c, factors)
+        }
+        expr => (1.0, vec![expr]),
+    }
+}
+
+// 同类项的键：各因子的 LaTeX 排序后的列表，x y 与 y x 为同类项
+fn monomial_key(monomial: &MathExpr) -> Vec<String> {
+    let mut factors = Vec::new();
+    collect_factors(monomial.clone(), &BinaryOperator::Multiply, true, &mut factors);
+    let mut key: Vec<String> = factors.iter().map(MathExpr::to_latex).collect();
+    key.sort();
+    key
+}
+
+// 第一项为负时写成前缀负号；normalize 为 true 时 a + (-b) 写成 a - b
+fn build_sum(terms: Vec<(bool, MathExpr)>, normalize: bool) -> MathExpr {
+    let mut terms = terms.into_iter();
+    let Some((negated, first)) = terms.next() else {
+        return MathExpr::Number(0.0);
+    };
+    let mut expr = if negated { negate(first, normalize) } else { first };
+    for (negated, term) in terms {
+        let (negated, term) = match term {
+            MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr } if normalize => (!negated, *expr),
+            term => (negated, term),
+        };
+        let operator = if negated { BinaryOperator::Subtract } else { BinaryOperator::Add };
+        expr = binary(expr, operator, term);
+    }
+    expr
+}
+
+fn product(expr: MathExpr, rules: RuleSet) -> MathExpr {
+    let operator = match &expr {
+        MathExpr::BinaryOp { operator, .. } => operator.clone(),
+        _ => BinaryOperator::Multiply,
+    };
+    let mut factors = Vec::new();
+    collect_factors(expr, &operator, rules.contains(Rule::Flatten), &mut factors);
+
+    let mut coefficient = 1.0;
+    if rules.contains(Rule::ConstantFolding) {
+        // 数值因子和各因子的负号合并为系数，放在最前面；整数之比的分数与整数系数相乘后仍写成分数
+        let mut rest = Vec::new();
+        let mut fractions = Vec::new();
+        for factor in factors {
+            if let Some(value) = constant(&factor) {
+                coefficient *= value;
+                continue;
+            }
+            if rational(&factor).is_some() {
+                fractions.push(factor);
+                continue;
+            }
+            match factor {
+                MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr } => {
+                    coefficient = -coefficient;
+                    rest.push(*expr);
+                }
+                factor => rest.push(factor),
+            }
+        }
+        if !fractions.is_empty() {
+            let folded = fractions.iter().try_fold((coefficient, 1.0), |(n, d), fraction| {
+                let (numerator, denominator) = rational(fraction)?;
+                reduce(n * numerator, d * denominator)
+            });
+            match folded {
+                Some((n, 1.0)) => coefficient = n,
+                Some((n, d)) => {
+                    coefficient = if n < 0.0 { -1.0 } else { 1.0 };
+                    rest.insert(0, rational_expr(n.abs(), d));
+                }
+                None => {
+                    rest.splice(0..0, fractions);
+                }
+            }
+        }
+        factors = rest;
+        if coefficient == 0.0 {
+            return MathExpr::Number(0.0);
+        }
+    }
+    if rules.contains(Rule::Identities) {
+        if factors.iter().any(|factor| constant(factor) == Some(0.0)) {
+            return MathExpr::Number(0.0);
+        }
+        if factors.len() > 1 || coefficient != 1.0 {
+            factors.retain(|factor| constant(factor) != Some(1.0));
+        }
+    }
+    if rules.contains(Rule::Powers) {
+        factors = merge_powers(factors);
+    }
+    // 与分数相乘时合并为一个分数，之后再约分: x \frac{1}{x} → \frac{x}{x} → 1
+    if rules.contains(Rule::Fractions) && factors.len() > 1 && factors.iter().any(|factor| matches!(factor, MathExpr::Fraction { .. })) {
+        let mut numerators = Vec::new();
+        let mut denominators = Vec::new();
+        for factor in factors {
+            match factor {
+                MathExpr::Fraction { numerator, denominator } => {
+                    numerators.push(*numerator);
+                    denominators.push(*denominator);
+                }
+                factor => numerators.push(factor),
+            }
+        }
+        let fraction = MathExpr::Fraction {
+            numerator: Box::new(build_product(coefficient.abs(), numerators, &operator)),
+            denominator: Box::new(build_product(1.0, denominators, &BinaryOperator::Multiply)),
+        };
+        return if coefficient < 0.0 { negate(fraction, true) } else { fraction };
+    }
+    build_product(coefficient, factors, &operator)
+}
+
+// 同底数的幂合并，指数相加；只出现一次的因子保持原样
+fn merge_powers(factors: Vec<MathExpr>) -> Vec<MathExpr> {
+    // 每组为 (底数, 指数之和, 只有一个因子时的原因子)
+    let mut groups: Vec<(MathExpr, MathExpr, Option<MathExpr>)> = Vec::new();
+    for factor in factors {
+        let (base, exponent) = split_power(&factor);
+        if constant(&base).is_some() {
+            groups.push((base, exponent, Some(factor)));
+            continue;
+        }
+        match groups.iter_mut().find(|group| group.0 == base) {
+            Some(group) => {
+                let sum = std::mem::replace(&mut group.1, MathExpr::Number(0.0));
+                group.1 = add_constants(sum, exponent);
+                group.2 = None;
+            }
+            None => groups.push((base, exponent, Some(factor))),
+        }
+    }
+    groups
+        .into_iter()
+        .filter_map(|(base, exponent, original)| match original {
+            Some(original) => Some(original),
+            None if constant(&exponent) == Some(0.0) => None,
+            None if constant(&exponent) == Some(1.0) => Some(base),
+            None => Some(superscript(base, exponent)),
+        })
+        .collect()
+}
+
+// 幂拆分为底数和指数，其他表达式的指数为 1
+fn split_power(expr: &MathExpr) -> (MathExpr, MathExpr) {
+    match expr {
+        MathExpr::Superscript { base, superscript } => (*base.clone(), *superscript.clone()),
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Power, right } => (*left.clone(), *right.clone()),
+        expr => (expr.clone(), MathExpr::Number(1.0)),
+    }
+}
+
+// 系数在前，负的系数写成前缀负号；因子之间用 operator 连接，系数与第一个因子之间为隐式乘法
+fn build_product(coefficient: f64, factors: Vec<MathExpr>, operator: &BinaryOperator) -> MathExpr {
+    let mut factors = factors.into_iter();
+    let first = factors.next();
+    let mut product = match first {
+        Some(first) if coefficient.abs() == 1.0 => first,
+        Some(first) => binary(MathExpr::Number(coefficient.abs()), BinaryOperator::Multiply, first),
+        None => MathExpr::Number(coefficient.abs()),
+    };
+    for factor in factors {
+        product = binary(product, operator.clone(), factor);
+    }
+    if coefficient < 0.0 {
+        MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr: Box::new(product) }
+    } else {
+        product
+    }
+}
+
+fn fraction(numerator: MathExpr, denominator: MathExpr, is_fraction: bool, rules: RuleSet) -> MathExpr {
+    let rebuild = |numerator: MathExpr, denominator: MathExpr| {
+        if is_fraction {
+            MathExpr::Fraction { numerator: Box::new(numerator), denominator: Box::new(denominator) }
+        } else {
+            binary(numerator, BinaryOperator::Divide, denominator)
+        }
+    };
+    let (n, d) = (constant(&numerator), constant(&denominator));
+    if rules.contains(Rule::Identities) {
+        if d == Some(1.0) {
+            return numerator;
+        }
+        if n == Some(0.0) && d != Some(0.0) {
+            return MathExpr::Number(0.0);
+        }
+    }
+    if rules.contains(Rule::ConstantFolding)
+        && let (Some(n), Some(d)) = (n, d)
+        && d != 0.0
+        && (n / d).fract() == 0.0
+    {
+        return constant_expr(n / d);
+    }
+    if !rules.contains(Rule::Fractions) {
+        return rebuild(numerator, denominator);
+    }
+
+    // 约去数值系数的最大公约数和分子分母中相同底数的幂
+    let (mut nc, mut nf) = split_product(numerator.clone());
+    let (mut dc, mut df) = split_product(denominator.clone());
+    let mut changed = false;
+    if let Some(g) = gcd(nc, dc)
+        && (g != 1.0 || dc < 0.0 || nc < 0.0)
+    {
+        let g = if dc < 0.0 { -g } else { g };
+        nc /= g;
+        dc /= g;
+        changed = true;
+    }
+    let mut i = 0;
+    while i < nf.len() {
+        let (base, n_exp) = split_power(&nf[i]);
+        let Some(j) = df.iter().position(|factor| split_power(factor).0 == base) else {
+            i += 1;
+            continue;
+        };
+        let (_, d_exp) = split_power(&df[j]);
+        let difference = match (constant(&n_exp), constant(&d_exp)) {
+            (Some(a), Some(b)) => a - b,
+            _ if n_exp == d_exp => 0.0,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        changed = true;
+        df.remove(j);
+        if difference > 0.0 {
+            nf[i] = power_of(base, difference);
+            i += 1;
+        } else {
+            nf.remove(i);
+            if difference < 0.0 {
+                df.insert(j, power_of(base, -difference));
+            }
+        }
+    }
+    if !changed {
+        return rebuild(numerator, denominator);
+    }
+
+    let numerator = build_product(nc.abs(), nf, &BinaryOperator::Multiply);
+    let result = if df.is_empty() && dc == 1.0 {
+        numerator
+    } else {
+        rebuild(numerator, build_product(dc, df, &BinaryOperator::Multiply))
+    };
+    if nc < 0.0 { negate(result, true) } else { result }
+}
+
+// 整数或分子分母都是整数的分数，返回 (分子, 分母)，分母为正
+fn rational(expr: &MathExpr) -> Option<(f64, f64)> {
+    match expr {
+        MathExpr::Fraction { numerator, denominator } => reduce(constant(numerator)?, constant(denominator)?),
+        MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr } => rational(expr).map(|(n, d)| (-n, d)),
+        MathExpr::UnaryOp { operator: UnaryOperator::Plus, expr } | MathExpr::Parenthesized(expr) => rational(expr),
+        expr => constant(expr).and_then(|n| reduce(n, 1.0)),
+    }
+}
+
+// 约分，分子或分母不是 f64 能精确表示的整数时返回 None
+fn reduce(n: f64, d: f64) -> Option<(f64, f64)> {
+    let g = gcd(n, d)?;
+    let g = if d < 0.0 { -g } else { g };
+    Some((n / g, d / g))
+}
+
+// 非负的分数常量，分母为 1 时为整数
+fn rational_expr(n: f64, d: f64) -> MathExpr {
+    if d == 1.0 {
+        return MathExpr::Number(n);
+    }
+    MathExpr::Fraction { numerator: Box::new(MathExpr::Number(n)), denominator: Box::new(MathExpr::Number(d)) }
+}
+
+// 两个整数系数的最大公约数
+fn gcd(a: f64, b: f64) -> Option<f64> {
+    const LIMIT: f64 = 9007199254740992.0; // 2^53
+    let integer = |x: f64| x.fract() == 0.0 && x.abs() < LIMIT;
+    if !integer(a) || !integer(b) || b == 0.0 {
+        return None;
+    }
+    let (mut a, mut b) = (a.abs() as u64, b.abs() as u64);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    Some(a as f64)
+}
+
+fn power_of(base: MathExpr, exponent: f64) -> MathExpr {
+    if exponent == 1.0 { base } else { superscript(base, constant_expr(exponent)) }
+}
+
+fn power(base: MathExpr, exponent: MathExpr, is_superscript: bool, rules: RuleSet) -> MathExpr {
+    let rebuild = |base: MathExpr, exponent: MathExpr| {
+        if is_superscript { superscript(base, exponent) } else { binary(base, BinaryOperator::Power, exponent) }
+    };
+    if rules.contains(Rule::Identities) {
+        match constant(&exponent) {
+            Some(1.0) => return base,
+            Some(0.0) => return MathExpr::Number(1.0),
+            _ => {}
+        }
+        if constant(&base) == Some(1.0) {
+            return MathExpr::Number(1.0);
+        }
+    }
+    // 只折叠结果为精确整数的非负整数次幂
+    if rules.contains(Rule::ConstantFolding)
+        && let (Some(b), Some(e)) = (constant(&base), constant(&exponent))
+        && e.fract() == 0.0
+        && e >= 0.0
+        && b.powf(e).abs() < 1e15
+    {
+        return constant_expr(b.powf(e));
+    }
+    // (x^a)^n = x^{a n}，n 为整数
+    if rules.contains(Rule::Powers)
+        && let Some(n) = constant(&exponent)
+        && n.fract() == 0.0
+        && matches!(base, MathExpr::Superscript { .. } | MathExpr::BinaryOp { operator: BinaryOperator::Power, .. })
+    {
+        let (inner_base, inner_exponent) = split_power(&base);
+        let exponent = match constant(&inner_exponent) {
+            Some(a) => constant_expr(a * n),
+            None => binary(exponent, BinaryOperator::Multiply, inner_exponent),
+        };
+        return rebuild(inner_base, exponent);
+    }
+    rebuild(base, exponent)
+}
+
+fn unary(operator: UnaryOperator, expr: MathExpr, rules: RuleSet) -> MathExpr {
+    match operator {
+        UnaryOperator::Plus if rules.contains(Rule::Identities) => expr,
+        UnaryOperator::Minus if rules.contains(Rule::Identities) || rules.contains(Rule::ConstantFolding) => {
+            if constant(&expr) == Some(0.0) {
+                return MathExpr::Number(0.0);
+            }
+            negate(expr, rules.contains(Rule::Identities))
+        }
+        // 20! 是 f64 能精确表示的最大阶乘
+        UnaryOperator::Factorial if rules.contains(Rule::ConstantFolding) => match constant(&expr) {
+            Some(n) if n.fract() == 0.0 && (0.0..=20.0).contains(&n) => {
+                MathExpr::Number((1..=n as u64).product::<u64>() as f64)
+            }
+            _ => MathExpr::UnaryOp { operator, expr: Box::new(expr) },
+        },
+        operator => MathExpr::UnaryOp { operator, expr: Box::new(expr) },
+    }
+}
+
+// 只折叠结果为整数的根式，如 \sqrt{16}、\sqrt[3]{8}
+fn root(radicand: MathExpr, index: Option<MathExpr>, rules: RuleSet) -> MathExpr {
+    if rules.contains(Rule::ConstantFolding)
+        && let Some(r) = constant(&radicand)
+        && r >= 0.0
+    {
+        let n = match &index {
+            Some(index) => constant(index),
+            None => Some(2.0),
+        };
+        if let Some(n) = n
+            && n.fract() == 0.0
+            && n >= 2.0
+        {
+            let value = r.powf(1.0 / n).round();
+            if value.powf(n) == r {
+                return MathExpr::Number(value);
+            }
+        }
+    }
+    MathExpr::Root { radicand: Box::new(radicand), index: index.map(Box::new) }
+}
+
+// 数值常量，-3 的形式与解析结果相同，为负号作用于 3
+fn constant(expr: &MathExpr) -> Option<f64> {
+    match expr {
+        MathExpr::Number(n) => Some(*n),
+        MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr } => constant(expr).map(|value| -value),
+        MathExpr::UnaryOp { operator: UnaryOperator::Plus, expr } | MathExpr::Parenthesized(expr) => constant(expr),
+        _ => None,
+    }
+}
+
+fn constant_expr(value: f64) -> MathExpr {
+    if value < 0.0 {
+        MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr: Box::new(MathExpr::Number(-value)) }
+    } else {
+        MathExpr::Number(value.abs())
+    }
+}
+
+// 两个常数直接相加，否则为加法表达式
+fn add_constants(a: MathExpr, b: MathExpr) -> MathExpr {
+    match (constant(&a), constant(&b)) {
+        (Some(x), Some(y)) => constant_expr(x + y),
+        _ => binary(a, BinaryOperator::Add, b),
+    }
+}
+
+// normalize 为 true 时 -(-x) 写成 x
+fn negate(expr: MathExpr, normalize: bool) -> MathExpr {
+    match expr {
+        MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr } if normalize => *expr,
+        expr => MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr: Box::new(expr) },
+    }
+}
+
+fn binary(left: MathExpr, operator: BinaryOperator, right: MathExpr) -> MathExpr {
+    MathExpr::BinaryOp { left: Box::new(left), operator, right: Box::new(right) }
+}
+
+fn superscript(base: MathExpr, exponent: MathExpr) -> MathExpr {
+    let nested = matches!(base, MathExpr::Superscript { .. });
+    MathExpr::Superscript { base: Box::new(script_base(base, nested)), superscript: Box::new(exponent) }
+}
+
+// 底数本身带有上下标时加上括号，避免输出 a^{b}^{c} 这样的双重上标
+fn script_base(base: MathExpr, nested: bool) -> MathExpr {
+    if nested { MathExpr::Parenthesized(Box::new(base)) } else { base }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_inline_math;
+    use std::collections::HashMap;
+
+    fn simplified(source: &str) -> String {
+        simplify(&parse_inline_math(source).unwrap().expr, RuleSet::all()).to_latex()
+    }
+
+    #[test]
+    fn nested_scripts() {
+        assert_eq!(simplified("(a^b)^c"), "\\left(a^{b}\\right)^{c}");
+        assert_eq!(simplified("(x_1)_2"), "\\left(x_{1}\\right)_{2}");
+        assert_eq!(simplified("(a^2)^3"), "a^{6}");
+    }
+
+    #[test]
+    fn fraction_constants() {
+        assert_eq!(simplified("\\frac{1}{2} + \\frac{1}{3}"), "\\frac{5}{6}");
+        assert_eq!(simplified("1 - \\frac{1}{2} - \\frac{1}{2}"), "0");
+        assert_eq!(simplified("2 \\cdot \\frac{3}{4}"), "\\frac{3}{2}");
+        assert_eq!(simplified("x^{2 \\frac{1}{2}}"), "x");
+        let folding = RuleSet::none().with(Rule::ConstantFolding);
+        let expr = parse_inline_math("x^{2 \\frac{1}{2}}").unwrap().expr;
+        assert_eq!(simplify(&expr, folding).to_latex(), "x^{1}");
+    }
+
+    // 化简前后的值相同，化简结果可以重新解析
+    #[test]
+    fn preserves_value() {
+        let vars = HashMap::from([("a".to_string(), 1.5), ("b".to_string(), 2.0), ("c".to_string(), 3.0), ("x".to_string(), 0.7)]);
+        let sources = [
+            "(a^b)^c",
+            "(a^2)^3",
+            "\\frac{1}{2} + \\frac{1}{3}",
+            "x^{2 \\frac{1}{2}}",
+            "\\frac{1}{2} x + \\frac{1}{3} x - \\frac{1}{6}",
+            "-\\frac{3}{4} x \\cdot \\frac{2}{3}",
+            "0.5 + \\frac{1}{3}",
+            "x x^2 + 2 x^3 - \\frac{4 x}{2}",
+        ];
+        for source in sources {
+            let expr = parse_inline_math(source).unwrap().expr;
+            let simplified = simplify(&expr, RuleSet::all());
+            let reparsed = parse_inline_math(&simplified.to_latex()).unwrap().expr;
+            let before = expr.evaluate(&vars).unwrap();
+            for after in [simplified.evaluate(&vars).unwrap(), reparsed.evaluate(&vars).unwrap()] {
+                assert!((before - after).abs() < 1e-9, "{}: {} != {}", source, before, after);
+            }
+        }
+    }
+}
@@ -0,0 +1,574 @@
+use crate::ast::{BinaryOperator, MathExpr, UnaryOperator};
+use crate::diff::{depends_on, is_variable, operand, substitute};
+use std::collections::HashMap;
+use std::fmt;
+
+// 方程求解
+
+// 展开幂时允许的最大指数，避免 x^{1000} 这样的输入展开出过长的多项式
+const MAX_EXPONENT: f64 = 64.0;
+
+// 求解错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveError {
+    // 输入不是等式
+    NotAnEquation,
+
+    // 方程不是关于未知数的多项式，例如 \sin(x) = 0、\frac{1}{x} = 2
+    NotPolynomial { variable: String },
+
+    // 多项式的次数高于 2
+    DegreeTooHigh { degree: usize },
+
+    // 方程不含未知数，或化简后只剩下含其他变量的等式，例如 x + a = x + b
+    Undetermined { variable: String },
+
+    // 方程对所有取值都成立，或方程组有无穷多组解
+    InfiniteSolutions,
+
+    // 方程组中有非线性的项，或系数不是数
+    NotLinear,
+
+    // 方程组无解
+    Inconsistent,
+
+    // 精确求解时分子或分母超出了整数的范围
+    Overflow,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::NotAnEquation => write!(f, "Expected an equation"),
+            SolveError::NotPolynomial { variable } => {
+                write!(f, "Equation is not polynomial in {}", variable)
+            }
+            SolveError::DegreeTooHigh { degree } => {
+                write!(f, "Cannot solve equations of degree {}", degree)
+            }
+            SolveError::Undetermined { variable } => write!(f, "Equation does not determine {}", variable),
+            SolveError::InfiniteSolutions => write!(f, "Infinitely many solutions"),
+            SolveError::NotLinear => write!(f, "System is not linear with numeric coefficients"),
+            SolveError::Inconsistent => write!(f, "System has no solution"),
+            SolveError::Overflow => write!(f, "Coefficients are too large to solve exactly"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+// 解关于 variable 的一次或二次方程，返回全部实数解，没有实数解时为空
+//
+// 系数可以含有其他变量，如 a x + b = 0 的解为 -\frac{b}{a}，此时假定首项系数不为 0；
+// 二次方程按求根公式求解，判别式为数时负判别式没有实数解，根式中的平方因子会被提出，
+// 系数都是数时解按从小到大排列。结果经过化简，可以用 to_latex 输出。
+pub fn solve(equation: &MathExpr, variable: &str) -> Result<Vec<MathExpr>, SolveError> {
+    let (left, right) = sides(equation)?;
+    if !depends_on(left, variable) && !depends_on(right, variable) {
+        return Err(SolveError::Undetermined { variable: variable.to_string() });
+    }
+    let coefficients = trim(add(polynomial(left, variable)?, negate(polynomial(right, variable)?)));
+
+    match coefficients.as_slice() {
+        [] => Err(SolveError::InfiniteSolutions),
+        [constant] => match constant.evaluate(&HashMap::new()) {
+            Ok(_) => Ok(Vec::new()),
+            Err(_) => Err(SolveError::Undetermined { variable: variable.to_string() }),
+        },
+        // 精确计算溢出时改用化简和数值计算
+        [b, a] => match (Rational::from_expr(a), Rational::from_expr(b)) {
+            (Some(a), Some(b)) if let Some(root) = b.neg().and_then(|b| b.div(a)) => Ok(vec![root.to_expr()]),
+            _ => Ok(vec![simplified(fraction(neg(b.clone()), a.clone()))]),
+        },
+        [c, b, a] => match (Rational::from_expr(a), Rational::from_expr(b), Rational::from_expr(c)) {
+            (Some(a), Some(b), Some(c)) if let Some(roots) = rational_quadratic(a, b, c) => Ok(roots),
+            _ => Ok(quadratic(a, b, c)),
+        },
+        _ => Err(SolveError::DegreeTooHigh { degree: coefficients.len() - 1 }),
+    }
+}
+
+// 解线性方程组，variables 为未知数，返回按 variables 顺序排列的解
+//
+// 每个方程都必须是未知数的线性组合且系数为数（整数、小数或分数），解为精确的分数；
+// 消元过程中分子或分母超出整数范围时返回 SolveError::Overflow。
+pub fn solve_system(equations: &[MathExpr], variables: &[&str]) -> Result<Vec<(String, MathExpr)>, SolveError> {
+    let zero = MathExpr::Number(0.0);
+    // 增广矩阵，每行为一个方程的各未知数的系数和常数项（移到等号右边）
+    let mut rows = Vec::new();
+    for equation in equations {
+        let (left, right) = sides(equation)?;
+        let difference = binary(operand(left), BinaryOperator::Subtract, MathExpr::Parenthesized(Box::new(operand(right))));
+        let mut row = Vec::new();
+        for variable in variables {
+            let coefficients = trim(polynomial(&difference, variable).map_err(|_| SolveError::NotLinear)?);
+            if coefficients.len() > 2 {
+                return Err(SolveError::NotLinear);
+            }
+            row.push(Rational::from_expr(coefficients.get(1).unwrap_or(&zero)).ok_or(SolveError::NotLinear)?);
+        }
+        let constant = variables
+            .iter()
+            .fold(difference, |expr, variable| substitute(&expr, variable, &zero));
+        let constant = Rational::from_expr(&constant.simplify()).ok_or(SolveError::NotLinear)?;
+        row.push(constant.neg().ok_or(SolveError::Overflow)?);
+        rows.push(row);
+    }
+
+    // Gauss-Jordan 消元
+    let n = variables.len();
+    let mut rank = 0;
+    for column in 0..n {
+        let Some(pivot) = (rank..rows.len()).find(|&r| !rows[r][column].is_zero()) else {
+            continue;
+        };
+        rows.swap(rank, pivot);
+        let pivot = rows[rank][column];
+        for value in rows[rank].iter_mut() {
+            *value = value.div(pivot).ok_or(SolveError::Overflow)?;
+        }
+        let pivot_row = rows[rank].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            let factor = row[column];
+            if r != rank && !factor.is_zero() {
+                for (value, pivot_value) in row.iter_mut().zip(&pivot_row) {
+                    *value = factor.mul(*pivot_value).and_then(|p| value.sub(p)).ok_or(SolveError::Overflow)?;
+                }
+            }
+        }
+        rank += 1;
+    }
+
+    if rows[rank..].iter().any(|row| !row[n].is_zero()) {
+        return Err(SolveError::Inconsistent);
+    }
+    if rank < n {
+        return Err(SolveError::InfiniteSolutions);
+    }
+    Ok(variables
+        .iter()
+        .zip(&rows)
+        .map(|(variable, row)| (variable.to_string(), row[n].to_expr()))
+        .collect())
+}
+
+fn sides(equation: &MathExpr) -> Result<(&MathExpr, &MathExpr), SolveError> {
+    match equation.unspanned() {
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Equals, right } => Ok((left, right)),
+        _ => Err(SolveError::NotAnEquation),
+    }
+}
+
+// 求根公式: x = \frac{-b \pm \sqrt{b^2 - 4ac}}{2a}
+fn quadratic(a: &MathExpr, b: &MathExpr, c: &MathExpr) -> Vec<MathExpr> {
+    let four_ac = binary(binary(MathExpr::Number(4.0), BinaryOperator::Multiply, a.clone()), BinaryOperator::Multiply, c.clone());
+    let discriminant = simplified(binary(square(b.clone()), BinaryOperator::Subtract, four_ac));
+    let two_a = binary(MathExpr::Number(2.0), BinaryOperator::Multiply, a.clone());
+    let vertex = simplified(fraction(neg(b.clone()), two_a.clone()));
+
+    let vars = HashMap::new();
+    match discriminant.evaluate(&vars) {
+        Ok(d) if d < 0.0 => return Vec::new(),
+        Ok(0.0) => return vec![vertex],
+        _ => {}
+    }
+    let offset = simplified(fraction(square_root(discriminant), two_a));
+    let smaller = simplified(binary(vertex.clone(), BinaryOperator::Subtract, offset.clone()));
+    let larger = simplified(binary(vertex, BinaryOperator::Add, offset));
+    match a.evaluate(&vars) {
+        Ok(a) if a < 0.0 => vec![larger, smaller],
+        _ => vec![smaller, larger],
+    }
+}
+
+// 系数都是数时精确求解，判别式不是完全平方数时解写成 v \pm r \sqrt{m}，v、r 为分数；
+// 计算溢出时返回 None
+fn rational_quadratic(a: Rational, b: Rational, c: Rational) -> Option<Vec<MathExpr>> {
+    let two_a = Rational::new(2, 1).mul(a)?;
+    let vertex = b.neg()?.div(two_a)?;
+    let discriminant = b.mul(b)?.sub(Rational::new(4, 1).mul(a)?.mul(c)?)?;
+    if discriminant.numerator < 0 {
+        return Some(Vec::new());
+    }
+    if discriminant.is_zero() {
+        return Some(vec![vertex.to_expr()]);
+    }
+
+    // \sqrt{\frac{p}{q}} = \frac{\sqrt{p q}}{q}
+    let (outside, inside) = square_factor(discriminant.numerator.checked_mul(discriminant.denominator)?);
+    let offset = Rational::new(outside, discriminant.denominator).div(two_a)?.abs()?;
+    if inside == 1 {
+        return Some(vec![vertex.sub(offset)?.to_expr(), vertex.add(offset)?.to_expr()]);
+    }
+    let root = MathExpr::Root { radicand: Box::new(MathExpr::Number(inside as f64)), index: None };
+    let scaled = match offset.numerator {
+        1 => root,
+        n => MathExpr::BinaryOp {
+            left: Box::new(MathExpr::Number(n as f64)),
+            operator: BinaryOperator::Multiply,
+            right: Box::new(root),
+        },
+    };
+    let term = match offset.denominator {
+        1 => scaled,
+        d => fraction(scaled, MathExpr::Number(d as f64)),
+    };
+    if vertex.is_zero() {
+        let negative = MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr: Box::new(term.clone()) };
+        return Some(vec![negative, term]);
+    }
+    let roots = [BinaryOperator::Subtract, BinaryOperator::Add]
+        .into_iter()
+        .map(|operator| MathExpr::BinaryOp {
+            left: Box::new(vertex.to_expr()),
+            operator,
+            right: Box::new(term.clone()),
+        })
+        .collect();
+    Some(roots)
+}
+
+// 平方根提出系数中的平方因子: \sqrt{12} → 2 \sqrt{3}，\sqrt{4 a} → 2 \sqrt{a}
+fn square_root(radicand: MathExpr) -> MathExpr {
+    let root = |radicand: MathExpr| MathExpr::Root { radicand: Box::new(radicand), index: None };
+    let (n, rest) = match &radicand {
+        MathExpr::Number(n) => (*n, None),
+        MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply, right } => match **left {
+            MathExpr::Number(n) => (n, Some((**right).clone())),
+            _ => return root(radicand),
+        },
+        _ => return root(radicand),
+    };
+    if n.fract() != 0.0 || n < 0.0 {
+        return root(radicand);
+    }
+    let (outside, inside) = square_factor(n as i128);
+    let inside = match rest {
+        None => MathExpr::Number(inside as f64),
+        Some(rest) if inside == 1 => rest,
+        Some(rest) => binary(MathExpr::Number(inside as f64), BinaryOperator::Multiply, rest),
+    };
+    match (outside, inside) {
+        (outside, MathExpr::Number(1.0)) => MathExpr::Number(outside as f64),
+        (1, inside) => root(inside),
+        (outside, inside) => binary(MathExpr::Number(outside as f64), BinaryOperator::Multiply, root(inside)),
+    }
+}
+
+// n = outside^2 inside，inside 不含平方因子；n 太大时不分解
+fn square_factor(n: i128) -> (i128, i128) {
+    if n > 1_000_000_000_000 {
+        return (1, n);
+    }
+    let (mut outside, mut inside) = (1, n);
+    let mut factor = 2;
+    while factor * factor <= inside {
+        while inside % (factor * factor) == 0 {
+            outside *= factor;
+            inside /= factor * factor;
+        }
+        factor += 1;
+    }
+    (outside, inside)
+}
+
+// 多项式，第 k 项为 variable^k 的系数，系数中不含 variable
+fn polynomial(expr: &MathExpr, variable: &str) -> Result<Vec<MathExpr>, SolveError> {
+    let not_polynomial = || SolveError::NotPolynomial { variable: variable.to_string() };
+    if !depends_on(expr, variable) {
+        return Ok(vec![operand(expr)]);
+    }
+    if is_variable(expr, variable) {
+        return Ok(vec![MathExpr::Number(0.0), MathExpr::Number(1.0)]);
+    }
+    match expr.unspanned() {
+        MathExpr::Parenthesized(inner) => polynomial(inner, variable),
+        // 多字母标识符按各字母的乘积处理: ax → a x
+        MathExpr::Variable(name) => {
+            let rest: String = name.chars().filter(|c| c.to_string() != variable).collect();
+            let mut coefficients = vec![MathExpr::Number(0.0); name.matches(variable).count()];
+            coefficients.push(if rest.is_empty() { MathExpr::Number(1.0) } else { MathExpr::Variable(rest) });
+            Ok(coefficients)
+        }
+        MathExpr::BinaryOp { left, operator, right } => match operator {
+            BinaryOperator::Add => Ok(add(polynomial(left, variable)?, polynomial(right, variable)?)),
+            BinaryOperator::Subtract => Ok(add(polynomial(left, variable)?, negate(polynomial(right, variable)?))),
+            BinaryOperator::Multiply | BinaryOperator::DotProduct => {
+                Ok(multiply(&polynomial(left, variable)?, &polynomial(right, variable)?))
+            }
+            BinaryOperator::Divide => divide(polynomial(left, variable)?, right, variable),
+            BinaryOperator::Power => power(left, right, variable),
+            BinaryOperator::Equals => Err(not_polynomial()),
+        },
+        MathExpr::Superscript { base, superscript } => power(base, superscript, variable),
+        MathExpr::Fraction { numerator, denominator } => divide(polynomial(numerator, variable)?, denominator, variable),
+        MathExpr::UnaryOp { operator: UnaryOperator::Plus, expr } => polynomial(expr, variable),
+        MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr } => Ok(negate(polynomial(expr, variable)?)),
+        _ => Err(not_polynomial()),
+    }
+}
+
+// 指数必须是不含 variable 的非负整数
+fn power(base: &MathExpr, exponent: &MathExpr, variable: &str) -> Result<Vec<MathExpr>, SolveError> {
+    let exponent = match exponent.evaluate(&HashMap::new()) {
+        Ok(n) if n >= 0.0 && n.fract() == 0.0 => n,
+        _ => return Err(SolveError::NotPolynomial { variable: variable.to_string() }),
+    };
+    let base = polynomial(base, variable)?;
+    if exponent > MAX_EXPONENT {
+        return Err(SolveError::DegreeTooHigh { degree: (base.len() - 1).saturating_mul(exponent as usize) });
+    }
+    let mut result = vec![MathExpr::Number(1.0)];
+    for _ in 0..exponent as usize {
+        result = multiply(&result, &base);
+    }
+    Ok(result)
+}
+
+fn divide(numerator: Vec<MathExpr>, denominator: &MathExpr, variable: &str) -> Result<Vec<MathExpr>, SolveError> {
+    if depends_on(denominator, variable) {
+        return Err(SolveError::NotPolynomial { variable: variable.to_string() });
+    }
+    Ok(numerator.into_iter().map(|c| fraction(c, operand(denominator))).collect())
+}
+
+fn add(a: Vec<MathExpr>, b: Vec<MathExpr>) -> Vec<MathExpr> {
+    let (mut long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    for (k, c) in short.into_iter().enumerate() {
+        long[k] = binary(long[k].clone(), BinaryOperator::Add, c);
+    }
+    long
+}
+
+fn negate(p: Vec<MathExpr>) -> Vec<MathExpr> {
+    p.into_iter().map(neg).collect()
+}
+
+// 每次相乘后化简系数，避免系数的表达式随次数增长
+fn multiply(a: &[MathExpr], b: &[MathExpr]) -> Vec<MathExpr> {
+    let mut result = vec![MathExpr::Number(0.0); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            let term = binary(x.clone(), BinaryOperator::Multiply, y.clone());
+            result[i + j] = binary(result[i + j].clone(), BinaryOperator::Add, term);
+        }
+    }
+    result.into_iter().map(simplified).collect()
+}
+
+// 化简系数并去掉最高次的零系数
+fn trim(p: Vec<MathExpr>) -> Vec<MathExpr> {
+    let mut p: Vec<MathExpr> = p.into_iter().map(simplified).collect();
+    while p.last() == Some(&MathExpr::Number(0.0)) {
+        p.pop();
+    }
+    p
+}
+
+fn simplified(expr: MathExpr) -> MathExpr {
+    expr.simplify()
+}
+
+fn binary(left: MathExpr, operator: BinaryOperator, right: MathExpr) -> MathExpr {
+    let group = |expr: MathExpr| match expr {
+        MathExpr::BinaryOp { .. } | MathExpr::UnaryOp { .. } => MathExpr::Parenthesized(Box::new(expr)),
+        expr => expr,
+    };
+    MathExpr::BinaryOp { left: Box::new(left), operator, right: Box::new(group(right)) }
+}
+
+fn neg(expr: MathExpr) -> MathExpr {
+    MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr: Box::new(MathExpr::Parenthesized(Box::new(expr))) }
+}
+
+fn fraction(numerator: MathExpr, denominator: MathExpr) -> MathExpr {
+    MathExpr::Fraction { numerator: Box::new(numerator), denominator: Box::new(denominator) }
+}
+
+fn square(expr: MathExpr) -> MathExpr {
+    MathExpr::Superscript { base: Box::new(MathExpr::Parenthesized(Box::new(expr))), superscript: Box::new(MathExpr::Number(2.0)) }
+}
+
+// 分数，分母总为正
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Rational {
+    numerator: i128,
+    denominator: i128,
+}
+
+impl Rational {
+    fn new(numerator: i128, denominator: i128) -> Self {
+        let g = gcd(numerator.abs(), denominator.abs()).max(1);
+        let sign = if denominator < 0 { -1 } else { 1 };
+        Rational { numerator: sign * numerator / g, denominator: sign * denominator / g }
+    }
+
+    // 化简后的常数: 整数、有限小数、分数，以及它们的和、差、积和相反数
+    fn from_expr(expr: &MathExpr) -> Option<Rational> {
+        match expr.unspanned() {
+            MathExpr::Number(n) => (0..=9).find_map(|digits| {
+                let scale = 10f64.powi(digits);
+                let scaled = n * scale;
+                (scaled.abs() < 1e15 && (scaled - scaled.round()).abs() < 1e-9)
+                    .then(|| Rational::new(scaled.round() as i128, scale as i128))
+            }),
+            MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr } => Rational::from_expr(expr)?.neg(),
+            MathExpr::UnaryOp { operator: UnaryOperator::Plus, expr } | MathExpr::Parenthesized(expr) => {
+                Rational::from_expr(expr)
+            }
+            MathExpr::BinaryOp { left, operator: BinaryOperator::Add, right } => {
+                Rational::from_expr(left)?.add(Rational::from_expr(right)?)
+            }
+            MathExpr::BinaryOp { left, operator: BinaryOperator::Subtract, right } => {
+                Rational::from_expr(left)?.sub(Rational::from_expr(right)?)
+            }
+            MathExpr::BinaryOp { left, operator: BinaryOperator::Multiply | BinaryOperator::DotProduct, right } => {
+                Rational::from_expr(left)?.mul(Rational::from_expr(right)?)
+            }
+            MathExpr::Fraction { numerator, denominator }
+            | MathExpr::BinaryOp { left: numerator, operator: BinaryOperator::Divide, right: denominator } => {
+                Rational::from_expr(numerator)?.div(Rational::from_expr(denominator)?)
+            }
+            _ => None,
+        }
+    }
+
+    fn to_expr(self) -> MathExpr {
+        let magnitude = MathExpr::Number(self.numerator.abs() as f64);
+        let expr = if self.denominator == 1 {
+            magnitude
+        } else {
+            fraction(magnitude, MathExpr::Number(self.denominator as f64))
+        };
+        if self.numerator < 0 {
+            MathExpr::UnaryOp { operator: UnaryOperator::Minus, expr: Box::new(expr) }
+        } else {
+            expr
+        }
+    }
+
+    fn is_zero(self) -> bool {
+        self.numerator == 0
+    }
+
+    // 以下运算在分子或分母超出 i128 的范围时返回 None，除数为 0 时也返回 None
+
+    fn neg(self) -> Option<Rational> {
+        Some(Rational { numerator: self.numerator.checked_neg()?, denominator: self.denominator })
+    }
+
+    fn abs(self) -> Option<Rational> {
+        Some(Rational { numerator: self.numerator.checked_abs()?, denominator: self.denominator })
+    }
+
+    fn add(self, other: Rational) -> Option<Rational> {
+        self.sub(other.neg()?)
+    }
+
+    // 先约去两个分母的公因数再通分
+    fn sub(self, other: Rational) -> Option<Rational> {
+        let g = gcd(self.denominator, other.denominator);
+        let (left, right) = (other.denominator / g, self.denominator / g);
+        let numerator = self.numerator.checked_mul(left)?.checked_sub(other.numerator.checked_mul(right)?)?;
+        Some(Rational::new(numerator, self.denominator.checked_mul(left)?))
+    }
+
+    // 先交叉约分再相乘
+    fn mul(self, other: Rational) -> Option<Rational> {
+        let g1 = gcd(self.numerator.abs(), other.denominator).max(1);
+        let g2 = gcd(other.numerator.abs(), self.denominator).max(1);
+        Some(Rational::new(
+            (self.numerator / g1).checked_mul(other.numerator / g2)?,
+            (self.denominator / g2).checked_mul(other.denominator / g1)?,
+        ))
+    }
+
+    fn div(self, other: Rational) -> Option<Rational> {
+        if other.is_zero() {
+            return None;
+        }
+        let sign = other.numerator.signum();
+        let reciprocal = Rational {
+            numerator: sign * other.denominator,
+            denominator: other.numerator.checked_abs()?,
+        };
+        self.mul(reciprocal)
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_inline_math;
+
+    fn roots(equation: &str) -> Result<Vec<String>, SolveError> {
+        let ast = parse_inline_math(equation).unwrap();
+        Ok(solve(&ast.expr, "x")?.iter().map(|root| root.to_latex()).collect())
+    }
+
+    fn system(equations: &[&str], variables: &[&str]) -> Result<Vec<(String, String)>, SolveError> {
+        let equations: Vec<MathExpr> = equations.iter().map(|e| parse_inline_math(e).unwrap().expr).collect();
+        Ok(solve_system(&equations, variables)?
+            .into_iter()
+            .map(|(name, value)| (name, value.to_latex()))
+            .collect())
+    }
+
+    #[test]
+    fn linear() {
+        assert_eq!(roots("2x + 3 = 7").unwrap(), ["2"]);
+        assert_eq!(roots("\\frac{x}{2} + \\frac{x}{3} = 5").unwrap(), ["6"]);
+    }
+
+    #[test]
+    fn quadratic() {
+        assert_eq!(roots("x^2 - 5x + 6 = 0").unwrap(), ["2", "3"]);
+        assert_eq!(roots("x^2 + x + 1 = 0").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn square_root_roots() {
+        assert_eq!(roots("x^2 = 2").unwrap(), ["-\\sqrt{2}", "\\sqrt{2}"]);
+        assert_eq!(roots("x^2 - 2x - 1 = 0").unwrap(), ["1 - \\sqrt{2}", "1 + \\sqrt{2}"]);
+    }
+
+    // 符号系数的判别式提出系数中的平方因子
+    #[test]
+    fn symbolic_square_roots() {
+        assert_eq!(roots("x^2 = a").unwrap(), ["-\\sqrt{a}", "\\sqrt{a}"]);
+        assert_eq!(roots("x^2 = 8a").unwrap(), ["-2 \\sqrt{2 a}", "2 \\sqrt{2 a}"]);
+        assert_eq!(roots("x^2 = a + 1").unwrap(), ["-\\sqrt{a + 1}", "\\sqrt{a + 1}"]);
+    }
+
+    #[test]
+    fn singular_system() {
+        assert_eq!(system(&["x + y = 1", "2x + 2y = 3"], &["x", "y"]), Err(SolveError::Inconsistent));
+        assert_eq!(system(&["x + y = 1", "2x + 2y = 2"], &["x", "y"]), Err(SolveError::InfiniteSolutions));
+        let solution = system(&["x + y = 3", "x - y = 1"], &["x", "y"]).unwrap();
+        assert_eq!(solution, [("x".to_string(), "2".to_string()), ("y".to_string(), "1".to_string())]);
+    }
+
+    // 精确计算溢出时不应 panic
+    #[test]
+    fn quadratic_overflow() {
+        let roots = roots("0.000000001 x^2 + 99999999999999 x + 0.000000007 = 0").unwrap();
+        assert_eq!(roots.len(), 2);
+    }
+
+    #[test]
+    fn system_overflow() {
+        let result = system(
+            &[
+                "12345678901234 x + 98765432109876 y + 13579135791357 z = 24680246802468",
+                "97531975319753 x + 86420864208642 y + 11223344556677 z = 99887766554433",
+                "31415926535897 x + 27182818284590 y + 14142135623730 z = 17320508075688",
+            ],
+            &["x", "y", "z"],
+        );
+        assert!(matches!(result, Ok(_) | Err(SolveError::Overflow)));
+    }
+}